@@ -0,0 +1,128 @@
+use criterion::{BenchmarkId, Criterion, Throughput, black_box, criterion_group, criterion_main};
+use rust_week_3_exercises::*;
+
+// Build a transaction with `n` inputs, each spending a different output
+// and carrying a P2PKH-sized scriptSig (~107 bytes), plus two outputs, which
+// is roughly what a typical legacy spend looks like on chain.
+fn sample_tx(n: usize) -> BitcoinTransaction {
+    let inputs = (0..n)
+        .map(|i| {
+            let mut txid = [0u8; 32];
+            txid[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            TransactionInput::new(
+                OutPoint::new(txid, i as u32),
+                Script::new(vec![0xAB; 107]),
+                0xFFFFFFFF,
+            )
+        })
+        .collect();
+    let mut tx = BitcoinTransaction::new(2, inputs, 0);
+    // Payment + change, both P2PKH-sized scriptPubKeys
+    tx.add_output(TxOut::new(100_000, Script::new(vec![0x76; 25])))
+        .add_output(TxOut::new(42_000, Script::new(vec![0x76; 25])));
+    tx
+}
+
+fn bench_compact_size(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compact_size");
+    // One value for each of the four encodings
+    for value in [0xFCu64, 0xFFFF, 0xFFFF_FFFF, u64::MAX] {
+        let bytes = CompactSize::new(value).to_bytes();
+        group.bench_with_input(BenchmarkId::new("encode", value), &value, |b, &v| {
+            b.iter(|| CompactSize::new(black_box(v)).to_bytes())
+        });
+        group.bench_with_input(BenchmarkId::new("decode", value), &bytes, |b, bytes| {
+            b.iter(|| CompactSize::decode(black_box(bytes)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_script(c: &mut Criterion) {
+    let mut group = c.benchmark_group("script");
+    // P2PKH scriptPubKey, P2PKH scriptSig and a large (redeem-script sized) blob
+    for len in [25usize, 107, 10_000] {
+        let script = Script::new(vec![0x51; len]);
+        let bytes = script.to_bytes();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("encode", len), &script, |b, s| {
+            b.iter(|| black_box(s).to_bytes())
+        });
+        group.bench_with_input(BenchmarkId::new("decode", len), &bytes, |b, bytes| {
+            b.iter(|| Script::decode(black_box(bytes)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_transaction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transaction");
+    for inputs in [1usize, 10, 1_000] {
+        let tx = sample_tx(inputs);
+        let bytes = tx.to_bytes();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("encode", inputs), &tx, |b, tx| {
+            b.iter(|| black_box(tx).to_bytes())
+        });
+        group.bench_with_input(BenchmarkId::new("decode", inputs), &bytes, |b, bytes| {
+            b.iter(|| BitcoinTransaction::decode(black_box(bytes)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("txid", inputs), &tx, |b, tx| {
+            b.iter(|| black_box(tx).txid())
+        });
+    }
+    group.finish();
+}
+
+// A real mainnet transaction: 100 segwit inputs and 122 outputs, taken from
+// block 000000000000000000000c835b2adcaedc20fdf6ee440009c249452c726dafae.
+// Unlike sample_tx it has witnesses and a realistic mix of script sizes.
+const MAINNET_TX_HEX: &str = include_str!(
+    "data/mainnet_tx_5c2f55fc17854a18f447d74e32356c9ff6faf43bf937ed13611857b2bb3fef3a.hex"
+);
+const MAINNET_TXID: &str = "5c2f55fc17854a18f447d74e32356c9ff6faf43bf937ed13611857b2bb3fef3a";
+
+fn bench_mainnet_transaction(c: &mut Criterion) {
+    let bytes = hex::decode(MAINNET_TX_HEX.trim()).unwrap();
+    let tx = BitcoinTransaction::decode(&bytes).unwrap().into_inner();
+    // Make sure we're timing the right thing before timing it
+    assert_eq!(tx.to_bytes(), bytes);
+    assert_eq!(tx.txid().to_string(), MAINNET_TXID);
+
+    let mut group = c.benchmark_group("mainnet_tx");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| BitcoinTransaction::decode(black_box(&bytes)).unwrap())
+    });
+    group.bench_function("encode", |b| b.iter(|| black_box(&tx).to_bytes()));
+    group.bench_function("txid", |b| b.iter(|| black_box(&tx).txid()));
+    group.finish();
+}
+
+fn bench_merkle(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merkle");
+    // Roughly a full block's worth of txids
+    let leaves: Vec<[u8; 32]> = (0..4_000u32)
+        .map(|i| {
+            let mut leaf = [0u8; 32];
+            leaf[..4].copy_from_slice(&i.to_le_bytes());
+            leaf
+        })
+        .collect();
+    group.bench_function("root/4000", |b| b.iter(|| merkle_root(black_box(&leaves))));
+    let branch = MerkleBranch::from_leaves(&leaves, 1234).unwrap();
+    group.bench_function("derive_root/4000", |b| {
+        b.iter(|| branch.derive_root(black_box(leaves[1234])))
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_compact_size,
+    bench_script,
+    bench_transaction,
+    bench_mainnet_transaction,
+    bench_merkle
+);
+criterion_main!(benches);
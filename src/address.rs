@@ -0,0 +1,219 @@
+use crate::network::Network;
+use crate::{BitcoinError, Script, base58, bech32};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+// What an address actually commits to. Base58 addresses carry a 20-byte
+// hash; segwit addresses carry a witness version and program, which covers
+// P2WPKH (v0, 20 bytes), P2WSH (v0, 32 bytes), P2TR (v1, 32 bytes) and any
+// future version without needing a new variant.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum AddressPayload {
+    P2pkh([u8; 20]),
+    P2sh([u8; 20]),
+    Segwit { version: u8, program: Vec<u8> },
+}
+
+// An address is a scriptPubKey written for humans, plus the network it's
+// meant for. Convert with `from_script` / `script_pubkey`, and parse or print
+// with FromStr / Display. The payload is only set through the constructors,
+// which keep segwit versions and program lengths within BIP141, so every
+// Address can be printed.
+//
+// Several networks share address formats: base58 addresses can't tell the
+// test networks and regtest apart, and testnet, testnet4 and signet share
+// the "tb" HRP. Parsing picks Network::Testnet (or Regtest for "bcrt"), so
+// callers that know which chain they're on should use `require_network`.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Address {
+    pub network: Network,
+    payload: AddressPayload,
+}
+
+impl Address {
+    pub fn p2pkh(pubkey_hash: [u8; 20], network: Network) -> Self {
+        Address {
+            network,
+            payload: AddressPayload::P2pkh(pubkey_hash),
+        }
+    }
+
+    pub fn p2sh(script_hash: [u8; 20], network: Network) -> Self {
+        Address {
+            network,
+            payload: AddressPayload::P2sh(script_hash),
+        }
+    }
+
+    pub fn p2wpkh(pubkey_hash: [u8; 20], network: Network) -> Self {
+        Self::segwit(0, pubkey_hash.to_vec(), network)
+    }
+
+    pub fn p2wsh(script_hash: [u8; 32], network: Network) -> Self {
+        Self::segwit(0, script_hash.to_vec(), network)
+    }
+
+    // Takes the tweaked x-only output key
+    pub fn p2tr(output_key: [u8; 32], network: Network) -> Self {
+        Self::segwit(1, output_key.to_vec(), network)
+    }
+
+    // Any witness version 0-16 with a 2-40 byte program (20 or 32 for v0)
+    pub fn from_witness_program(
+        version: u8,
+        program: &[u8],
+        network: Network,
+    ) -> Result<Self, BitcoinError> {
+        bech32::check_witness_program(version, program)?;
+        Ok(Self::segwit(version, program.to_vec(), network))
+    }
+
+    // Callers must have checked the program already
+    fn segwit(version: u8, program: Vec<u8>, network: Network) -> Self {
+        Address {
+            network,
+            payload: AddressPayload::Segwit { version, program },
+        }
+    }
+
+    // Only scripts with an address form work: P2PKH, P2SH and witness
+    // programs. Bare pubkeys, multisig and OP_RETURN have none.
+    pub fn from_script(script: &Script, network: Network) -> Result<Self, BitcoinError> {
+        let b = &script.bytes;
+        if script.is_p2pkh() {
+            Ok(Self::p2pkh(b[3..23].try_into().unwrap(), network))
+        } else if script.is_p2sh() {
+            Ok(Self::p2sh(b[2..22].try_into().unwrap(), network))
+        } else if let Some((version, program)) = script.witness_program() {
+            Self::from_witness_program(version, program, network)
+        } else {
+            Err(BitcoinError::InvalidAddress(
+                "script has no address form".into(),
+            ))
+        }
+    }
+
+    pub fn payload(&self) -> &AddressPayload {
+        &self.payload
+    }
+
+    // Whether this address would be written the same way on `network`, e.g.
+    // a parsed "tb1..." address is valid for testnet4 and signet too, and a
+    // base58 testnet one for regtest.
+    pub fn is_valid_for_network(&self, network: Network) -> bool {
+        match self.payload {
+            AddressPayload::P2pkh(_) => self.network.p2pkh_prefix() == network.p2pkh_prefix(),
+            AddressPayload::P2sh(_) => self.network.p2sh_prefix() == network.p2sh_prefix(),
+            AddressPayload::Segwit { .. } => self.network.bech32_hrp() == network.bech32_hrp(),
+        }
+    }
+
+    // Checks the address belongs to `network` and tags it with it
+    pub fn require_network(mut self, network: Network) -> Result<Self, BitcoinError> {
+        if !self.is_valid_for_network(network) {
+            return Err(BitcoinError::InvalidAddress(format!(
+                "address is for {}, expected {}",
+                self.network, network
+            )));
+        }
+        self.network = network;
+        Ok(self)
+    }
+
+    pub fn script_pubkey(&self) -> Script {
+        let mut bytes = Vec::new();
+        match &self.payload {
+            AddressPayload::P2pkh(hash) => {
+                bytes.extend_from_slice(&[0x76, 0xa9, 0x14]);
+                bytes.extend_from_slice(hash);
+                bytes.extend_from_slice(&[0x88, 0xac]);
+            }
+            AddressPayload::P2sh(hash) => {
+                bytes.extend_from_slice(&[0xa9, 0x14]);
+                bytes.extend_from_slice(hash);
+                bytes.push(0x87);
+            }
+            AddressPayload::Segwit { version, program } => {
+                // OP_0, or OP_1..OP_16
+                bytes.push(if *version == 0 { 0x00 } else { 0x50 + version });
+                bytes.push(program.len() as u8);
+                bytes.extend_from_slice(program);
+            }
+        }
+        Script::new(bytes)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let encoded = match &self.payload {
+            AddressPayload::P2pkh(hash) => {
+                base58::encode_check(&[&[self.network.p2pkh_prefix()][..], hash].concat())
+            }
+            AddressPayload::P2sh(hash) => {
+                base58::encode_check(&[&[self.network.p2sh_prefix()][..], hash].concat())
+            }
+            AddressPayload::Segwit { version, program } => {
+                bech32::encode_segwit(self.network.bech32_hrp(), *version, program)
+                    .expect("segwit payloads are checked when the address is built")
+            }
+        };
+        write!(f, "{}", encoded)
+    }
+}
+
+impl FromStr for Address {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Segwit if it starts with a known HRP, otherwise try base58. The
+        // prefix only picks the decoder: the HRP bech32 decodes must then
+        // match a network's exactly, so "bc1x1..." isn't taken for "bc". The
+        // other networks sharing "tb" parse as Testnet.
+        let networks = [Network::Mainnet, Network::Testnet, Network::Regtest];
+        let lower = s.to_lowercase();
+        if networks
+            .iter()
+            .any(|network| lower.starts_with(&format!("{}1", network.bech32_hrp())))
+        {
+            let (hrp, version, program) = bech32::decode_segwit(s)?;
+            let network = networks
+                .into_iter()
+                .find(|network| network.bech32_hrp() == hrp)
+                .ok_or_else(|| BitcoinError::InvalidAddress(format!("unknown HRP {:?}", hrp)))?;
+            return Ok(Self::segwit(version, program, network));
+        }
+
+        let data = base58::decode_check(s)?;
+        if data.len() != 21 {
+            return Err(BitcoinError::InvalidAddress(
+                "invalid base58 payload length".into(),
+            ));
+        }
+        let hash: [u8; 20] = data[1..].try_into().unwrap();
+        match data[0] {
+            0x00 => Ok(Self::p2pkh(hash, Network::Mainnet)),
+            0x05 => Ok(Self::p2sh(hash, Network::Mainnet)),
+            0x6f => Ok(Self::p2pkh(hash, Network::Testnet)),
+            0xc4 => Ok(Self::p2sh(hash, Network::Testnet)),
+            _ => Err(BitcoinError::InvalidAddress(
+                "unknown address version byte".into(),
+            )),
+        }
+    }
+}
+
+// Addresses go through serde as their string form
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
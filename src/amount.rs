@@ -0,0 +1,113 @@
+use crate::BitcoinError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// A quantity of bitcoin, stored as satoshis. Keeping it out of plain u64
+// means values can't be mixed up with counts or sizes, and the arithmetic is
+// checked so fee math can't silently wrap.
+//
+// Any u64 can be held (a decoded output can claim anything), but only
+// amounts up to MAX_MONEY are valid in a transaction. `from_sat` doesn't
+// check; `from_sat_checked` and `from_btc` do.
+//
+// Serializes as the bare satoshi integer, same as the old u64 field.
+#[derive(
+    Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const ONE_SAT: Amount = Amount(1);
+    pub const ONE_BTC: Amount = Amount(100_000_000);
+    // 21 million BTC, Core's MAX_MONEY. Not the exact supply, just a cap no
+    // valid amount can exceed.
+    pub const MAX_MONEY: Amount = Amount(21_000_000 * 100_000_000);
+
+    pub const fn from_sat(sat: u64) -> Self {
+        Amount(sat)
+    }
+
+    pub fn from_sat_checked(sat: u64) -> Result<Self, BitcoinError> {
+        let amount = Amount(sat);
+        if !amount.is_valid() {
+            return Err(BitcoinError::InvalidAmount(format!(
+                "{} sats exceeds MAX_MONEY",
+                sat
+            )));
+        }
+        Ok(amount)
+    }
+
+    // Rounds to the nearest satoshi, like Core's AmountFromValue
+    pub fn from_btc(btc: f64) -> Result<Self, BitcoinError> {
+        if !btc.is_finite() || btc < 0.0 {
+            return Err(BitcoinError::InvalidAmount(format!("{} BTC", btc)));
+        }
+        let sat = (btc * Self::ONE_BTC.0 as f64).round();
+        if sat > Self::MAX_MONEY.0 as f64 {
+            return Err(BitcoinError::InvalidAmount(format!(
+                "{} BTC exceeds MAX_MONEY",
+                btc
+            )));
+        }
+        Ok(Amount(sat as u64))
+    }
+
+    pub const fn to_sat(self) -> u64 {
+        self.0
+    }
+
+    // Lossy past ~90M BTC, which is well beyond MAX_MONEY
+    pub fn to_btc(self) -> f64 {
+        self.0 as f64 / Self::ONE_BTC.0 as f64
+    }
+
+    // Core's MoneyRange
+    pub fn is_valid(self) -> bool {
+        self <= Self::MAX_MONEY
+    }
+
+    pub fn checked_add(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_add(rhs.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_sub(rhs.0).map(Amount)
+    }
+
+    pub fn checked_mul(self, rhs: u64) -> Option<Amount> {
+        self.0.checked_mul(rhs).map(Amount)
+    }
+
+    pub fn checked_div(self, rhs: u64) -> Option<Amount> {
+        self.0.checked_div(rhs).map(Amount)
+    }
+
+    // Total of a list of amounts, None on overflow
+    pub fn checked_sum<I: IntoIterator<Item = Amount>>(amounts: I) -> Option<Amount> {
+        amounts
+            .into_iter()
+            .try_fold(Amount::ZERO, |total, amount| total.checked_add(amount))
+    }
+}
+
+// Unchecked, like Amount::from_sat, so `TxOut::new(1_000, ...)` keeps working
+impl From<u64> for Amount {
+    fn from(sat: u64) -> Self {
+        Amount(sat)
+    }
+}
+
+// Exact BTC with all 8 decimals, e.g. "0.00100000 BTC"
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{:08} BTC",
+            self.0 / Self::ONE_BTC.0,
+            self.0 % Self::ONE_BTC.0
+        )
+    }
+}
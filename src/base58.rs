@@ -0,0 +1,119 @@
+use crate::BitcoinError;
+use crate::hashes::sha256d;
+#[cfg(feature = "secp256k1")]
+use zeroize::Zeroizing;
+
+// Base58 is base-58 big-number encoding with an alphabet that leaves out
+// 0/O/I/l so addresses are harder to mistype. Leading zero bytes have no
+// numeric value, so each one is written as a '1' to keep them.
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+pub(crate) fn encode(data: &[u8]) -> String {
+    encode_with_scratch(data, &mut Vec::new())
+}
+
+// `digits` is the working buffer, so callers encoding a secret can pass one
+// they wipe afterwards. It's reserved up front (base-58 needs at most 138
+// digits per 100 bytes) so it never reallocates and leaves a copy behind.
+fn encode_with_scratch(data: &[u8], digits: &mut Vec<u8>) -> String {
+    let zeros = data.iter().take_while(|&&b| b == 0).count();
+    // Base-58 digits, least significant first
+    digits.clear();
+    digits.reserve(data.len() * 138 / 100 + 1);
+    for &byte in &data[zeros..] {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let mut out = String::with_capacity(zeros + digits.len());
+    out.extend(std::iter::repeat_n('1', zeros));
+    out.extend(digits.iter().rev().map(|&d| ALPHABET[d as usize] as char));
+    out
+}
+
+pub(crate) fn decode(s: &str) -> Result<Vec<u8>, BitcoinError> {
+    decode_with_scratch(s, &mut Vec::new())
+}
+
+// Same idea as encode_with_scratch; the result is sized exactly, so it
+// doesn't reallocate either
+fn decode_with_scratch(s: &str, bytes: &mut Vec<u8>) -> Result<Vec<u8>, BitcoinError> {
+    let zeros = s.bytes().take_while(|&b| b == b'1').count();
+    // Bytes, least significant first
+    bytes.clear();
+    bytes.reserve(s.len() * 733 / 1000 + 1);
+    for c in s.chars().skip(zeros) {
+        let value = ALPHABET
+            .iter()
+            .position(|&a| a as char == c)
+            .ok_or(BitcoinError::InvalidCharacter(c))?;
+        let mut carry = value as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    let mut out = Vec::with_capacity(zeros + bytes.len());
+    out.resize(zeros, 0);
+    out.extend(bytes.iter().rev());
+    Ok(out)
+}
+
+// Base58Check: payload followed by the first 4 bytes of its SHA256d
+pub(crate) fn encode_check(payload: &[u8]) -> String {
+    let mut data = payload.to_vec();
+    data.extend_from_slice(&sha256d(payload)[..4]);
+    encode(&data)
+}
+
+pub(crate) fn decode_check(s: &str) -> Result<Vec<u8>, BitcoinError> {
+    let mut data = decode(s)?;
+    if data.len() < 4 {
+        return Err(BitcoinError::InvalidChecksum);
+    }
+    let checksum = data.split_off(data.len() - 4);
+    if sha256d(&data)[..4] != checksum[..] {
+        return Err(BitcoinError::InvalidChecksum);
+    }
+    Ok(data)
+}
+
+// The same for secrets (WIF keys, xprvs): every intermediate buffer,
+// checksum included, is wiped when it goes out of scope
+#[cfg(feature = "secp256k1")]
+pub(crate) fn encode_check_zeroizing(payload: &[u8]) -> String {
+    let mut data = Zeroizing::new(Vec::with_capacity(payload.len() + 4));
+    data.extend_from_slice(payload);
+    data.extend_from_slice(&Zeroizing::new(sha256d(payload))[..4]);
+    encode_with_scratch(&data, &mut Zeroizing::new(Vec::new()))
+}
+
+#[cfg(feature = "secp256k1")]
+pub(crate) fn decode_check_zeroizing(s: &str) -> Result<Zeroizing<Vec<u8>>, BitcoinError> {
+    let mut data = Zeroizing::new(decode_with_scratch(s, &mut Zeroizing::new(Vec::new()))?);
+    if data.len() < 4 {
+        return Err(BitcoinError::InvalidChecksum);
+    }
+    let body = data.len() - 4;
+    if Zeroizing::new(sha256d(&data[..body]))[..4] != data[body..] {
+        return Err(BitcoinError::InvalidChecksum);
+    }
+    // Zeroizing<Vec> clears the whole capacity, so the dropped checksum
+    // bytes are wiped too
+    data.truncate(body);
+    Ok(data)
+}
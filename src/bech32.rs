@@ -0,0 +1,213 @@
+use crate::BitcoinError;
+
+// BIP173 bech32 and its BIP350 fix, bech32m. Both are the same format - a
+// human-readable part (HRP), a '1', then 5-bit data characters ending in a
+// 6-character BCH checksum - and only differ in the constant the checksum is
+// XORed with.
+//
+// `encode`/`decode` work on raw 5-bit data for any HRP. The `_segwit`
+// versions add the witness version and the 8 <-> 5 bit conversion, and pick
+// bech32 or bech32m from the witness version as BIP350 requires.
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+pub const MAX_LENGTH: usize = 90;
+const MAX_HRP_LENGTH: usize = 83;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Variant {
+    Bech32,
+    Bech32m,
+}
+
+impl Variant {
+    // BIP350: version 0 programs use bech32, everything newer uses bech32m
+    pub fn for_witness_version(version: u8) -> Self {
+        if version == 0 {
+            Variant::Bech32
+        } else {
+            Variant::Bech32m
+        }
+    }
+
+    fn constant(self) -> u32 {
+        match self {
+            Variant::Bech32 => 1,
+            Variant::Bech32m => 0x2bc830a3,
+        }
+    }
+}
+
+fn polymod(values: impl Iterator<Item = u8>) -> u32 {
+    let mut chk: u32 = 1;
+    for value in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x1ffffff) << 5) ^ value as u32;
+        for (i, g) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    chk
+}
+
+// The HRP goes into the checksum as its high bits, a zero, then its low bits
+fn hrp_expand(hrp: &str) -> impl Iterator<Item = u8> + '_ {
+    hrp.bytes()
+        .map(|b| b >> 5)
+        .chain(std::iter::once(0))
+        .chain(hrp.bytes().map(|b| b & 31))
+}
+
+fn checksum(hrp: &str, data: &[u8], variant: Variant) -> [u8; 6] {
+    let values = hrp_expand(hrp).chain(data.iter().copied()).chain([0u8; 6]);
+    let pm = polymod(values) ^ variant.constant();
+    let mut out = [0u8; 6];
+    for (i, c) in out.iter_mut().enumerate() {
+        *c = ((pm >> (5 * (5 - i))) & 31) as u8;
+    }
+    out
+}
+
+// 1 to 83 printable ASCII characters. Lowercase is canonical; uppercase is
+// accepted and lowered when encoding.
+pub fn validate_hrp(hrp: &str) -> Result<(), BitcoinError> {
+    if hrp.is_empty() || hrp.len() > MAX_HRP_LENGTH {
+        return Err(BitcoinError::InvalidAddress("invalid HRP length".into()));
+    }
+    match hrp.chars().find(|c| !(33..=126).contains(&(*c as u32))) {
+        Some(c) => Err(BitcoinError::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+// `data` is 5-bit values
+pub fn encode(hrp: &str, data: &[u8], variant: Variant) -> Result<String, BitcoinError> {
+    validate_hrp(hrp)?;
+    if data.iter().any(|&d| d >= 32) {
+        return Err(BitcoinError::InvalidFormat);
+    }
+    if hrp.len() + 1 + data.len() + 6 > MAX_LENGTH {
+        return Err(BitcoinError::InvalidAddress(
+            "bech32 string too long".into(),
+        ));
+    }
+    let hrp = hrp.to_lowercase();
+    let mut out = format!("{}1", hrp);
+    for &d in data.iter().chain(checksum(&hrp, data, variant).iter()) {
+        out.push(CHARSET[d as usize] as char);
+    }
+    Ok(out)
+}
+
+// Returns the (lowercased) HRP, the 5-bit data without the checksum, and
+// which checksum constant matched
+pub fn decode(s: &str) -> Result<(String, Vec<u8>, Variant), BitcoinError> {
+    if s.len() > MAX_LENGTH {
+        return Err(BitcoinError::InvalidAddress(
+            "bech32 string too long".into(),
+        ));
+    }
+    // All upper or all lower case, never mixed
+    if s.chars().any(|c| c.is_ascii_lowercase()) && s.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err(BitcoinError::InvalidAddress("mixed case".into()));
+    }
+    let s = s.to_lowercase();
+    let sep = s
+        .rfind('1')
+        .ok_or_else(|| BitcoinError::InvalidAddress("missing separator".into()))?;
+    let (hrp, rest) = (&s[..sep], &s[sep + 1..]);
+    validate_hrp(hrp)?;
+    if rest.len() < 6 {
+        return Err(BitcoinError::InvalidAddress("too short".into()));
+    }
+
+    let mut data = Vec::with_capacity(rest.len());
+    for c in rest.chars() {
+        let value = CHARSET
+            .iter()
+            .position(|&x| x as char == c)
+            .ok_or(BitcoinError::InvalidCharacter(c))?;
+        data.push(value as u8);
+    }
+
+    let variant = match polymod(hrp_expand(hrp).chain(data.iter().copied())) {
+        1 => Variant::Bech32,
+        0x2bc830a3 => Variant::Bech32m,
+        _ => return Err(BitcoinError::InvalidChecksum),
+    };
+    data.truncate(data.len() - 6);
+    Ok((hrp.to_string(), data, variant))
+}
+
+// Regroup a bit string, e.g. bytes (8) into bech32 characters (5). When
+// decoding (pad = false) any leftover bits must be zero padding of less than
+// one group.
+pub fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut acc: u32 = 0;
+    let mut bits: u32 = 0;
+    let max = (1u32 << to) - 1;
+    let mut out = Vec::with_capacity(data.len() * from as usize / to as usize + 1);
+    for &value in data {
+        if (value as u32) >> from != 0 {
+            return None;
+        }
+        acc = (acc << from) | value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            out.push(((acc >> bits) & max) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            out.push(((acc << (to - bits)) & max) as u8);
+        }
+    } else if bits >= from || (acc << (to - bits)) & max != 0 {
+        return None;
+    }
+    Some(out)
+}
+
+pub fn encode_segwit(hrp: &str, version: u8, program: &[u8]) -> Result<String, BitcoinError> {
+    check_witness_program(version, program)?;
+    let mut data = vec![version];
+    data.extend(convert_bits(program, 8, 5, true).expect("bytes always regroup"));
+    encode(hrp, &data, Variant::for_witness_version(version))
+}
+
+// Returns (HRP, witness version, program). Callers expecting a particular
+// network should check the HRP.
+pub fn decode_segwit(s: &str) -> Result<(String, u8, Vec<u8>), BitcoinError> {
+    let (hrp, data, variant) = decode(s)?;
+    let (&version, rest) = data
+        .split_first()
+        .ok_or_else(|| BitcoinError::InvalidAddress("missing witness version".into()))?;
+    let program = convert_bits(rest, 5, 8, false)
+        .ok_or_else(|| BitcoinError::InvalidAddress("invalid padding".into()))?;
+    check_witness_program(version, &program)?;
+    if variant != Variant::for_witness_version(version) {
+        return Err(BitcoinError::InvalidChecksum);
+    }
+    Ok((hrp, version, program))
+}
+
+// BIP141 limits: version 0-16, program 2-40 bytes, and v0 is 20 or 32
+pub(crate) fn check_witness_program(version: u8, program: &[u8]) -> Result<(), BitcoinError> {
+    if version > 16 {
+        return Err(BitcoinError::InvalidAddress(
+            "invalid witness version".into(),
+        ));
+    }
+    if program.len() < 2 || program.len() > 40 {
+        return Err(BitcoinError::InvalidAddress(
+            "invalid program length".into(),
+        ));
+    }
+    if version == 0 && program.len() != 20 && program.len() != 32 {
+        return Err(BitcoinError::InvalidAddress(
+            "invalid v0 program length".into(),
+        ));
+    }
+    Ok(())
+}
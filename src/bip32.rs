@@ -0,0 +1,438 @@
+use crate::keys::{PrivateKey, PublicKey};
+use crate::network::Network;
+use crate::{BitcoinError, base58, hashes, sign};
+use secp256k1::{Scalar, SecretKey};
+use std::fmt;
+use std::str::FromStr;
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
+
+// BIP32 hierarchical deterministic keys (`secp256k1` feature). An extended
+// key is a key plus a 32-byte chain code; hashing the two with a child index
+// gives the next key down the tree. Xpriv can derive every child, Xpub only
+// the non-hardened ones, which is what lets a watch-only wallet generate
+// receive addresses without ever seeing a private key.
+//
+// Child indexes follow the same convention as DerivationInfo: hardened ones
+// have the top bit set.
+
+pub const HARDENED: u32 = 0x8000_0000;
+
+// 78-byte serialization: version, depth, parent fingerprint, child index,
+// chain code, then the key (0x00 + secret, or the compressed public key)
+const ENCODED_LEN: usize = 78;
+const XPRV_MAINNET: [u8; 4] = [0x04, 0x88, 0xAD, 0xE4];
+const XPUB_MAINNET: [u8; 4] = [0x04, 0x88, 0xB2, 0x1E];
+const XPRV_TESTNET: [u8; 4] = [0x04, 0x35, 0x83, 0x94];
+const XPUB_TESTNET: [u8; 4] = [0x04, 0x35, 0x87, 0xCF];
+
+// A path like m/84'/0'/0'/0/5, as the list of child indexes under the
+// master key
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+pub struct DerivationPath(pub Vec<u32>);
+
+impl DerivationPath {
+    pub fn master() -> Self {
+        DerivationPath(Vec::new())
+    }
+
+    // This path extended by one more step
+    pub fn child(&self, index: u32) -> Self {
+        let mut path = self.0.clone();
+        path.push(index);
+        DerivationPath(path)
+    }
+
+    pub fn is_master(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+impl From<Vec<u32>> for DerivationPath {
+    fn from(path: Vec<u32>) -> Self {
+        DerivationPath(path)
+    }
+}
+
+impl From<DerivationPath> for Vec<u32> {
+    fn from(path: DerivationPath) -> Self {
+        path.0
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m")?;
+        for &index in &self.0 {
+            if index >= HARDENED {
+                write!(f, "/{}'", index - HARDENED)?;
+            } else {
+                write!(f, "/{}", index)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Accepts ' or h (or H) for hardened steps, with or without the leading "m"
+impl FromStr for DerivationPath {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| BitcoinError::InvalidDerivationPath(reason.into());
+        let rest = match s.strip_prefix('m') {
+            Some("") => return Ok(DerivationPath::master()),
+            Some(rest) => rest
+                .strip_prefix('/')
+                .ok_or_else(|| invalid("expected / after m"))?,
+            None => s,
+        };
+        rest.split('/')
+            .map(|step| {
+                let (number, hardened) = match step.strip_suffix(['\'', 'h', 'H']) {
+                    Some(number) => (number, true),
+                    None => (step, false),
+                };
+                let index: u32 = number
+                    .parse()
+                    .map_err(|_| invalid("child index is not a number"))?;
+                if index >= HARDENED {
+                    return Err(invalid("child index out of range"));
+                }
+                Ok(if hardened { index + HARDENED } else { index })
+            })
+            .collect::<Result<_, _>>()
+            .map(DerivationPath)
+    }
+}
+
+// The private key and chain code are both secret: together they give every
+// key below this one. Not Copy, compared in constant time, and erased on
+// drop, like PrivateKey.
+#[derive(Debug, Clone)]
+pub struct Xpriv {
+    pub network: Network,
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+    pub chain_code: [u8; 32],
+    pub private_key: SecretKey,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Xpub {
+    pub network: Network,
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+    pub chain_code: [u8; 32],
+    pub public_key: secp256k1::PublicKey,
+}
+
+impl Xpriv {
+    // The root of the tree: HMAC-SHA512 keyed with "Bitcoin seed". BIP32
+    // allows seeds of 16 to 64 bytes.
+    pub fn new_master(network: Network, seed: &[u8]) -> Result<Self, BitcoinError> {
+        if !(16..=64).contains(&seed.len()) {
+            return Err(BitcoinError::InvalidKey);
+        }
+        let i = Zeroizing::new(hashes::hmac_sha512(b"Bitcoin seed", seed));
+        Ok(Xpriv {
+            network,
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: 0,
+            chain_code: i[32..].try_into().unwrap(),
+            private_key: SecretKey::from_slice(&i[..32]).map_err(|_| BitcoinError::InvalidKey)?,
+        })
+    }
+
+    pub fn derive_child(&self, index: u32) -> Result<Self, BitcoinError> {
+        let mut data = Zeroizing::new(Vec::with_capacity(37));
+        if index >= HARDENED {
+            data.push(0);
+            data.extend(self.private_key.secret_bytes());
+        } else {
+            data.extend(self.public_key().serialize());
+        }
+        data.extend(index.to_be_bytes());
+        let (tweak, chain_code) = child_tweak(&self.chain_code, &data)?;
+
+        // Fails (with negligible probability) if the sum is zero
+        let private_key = self
+            .private_key
+            .add_tweak(&tweak)
+            .map_err(|_| BitcoinError::InvalidKey)?;
+        Ok(Xpriv {
+            network: self.network,
+            depth: self.depth.checked_add(1).ok_or(BitcoinError::InvalidKey)?,
+            parent_fingerprint: self.fingerprint(),
+            child_number: index,
+            chain_code,
+            private_key,
+        })
+    }
+
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, BitcoinError> {
+        path.0
+            .iter()
+            .try_fold(self.clone(), |key, &index| key.derive_child(index))
+    }
+
+    pub fn to_xpub(&self) -> Xpub {
+        Xpub {
+            network: self.network,
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+            chain_code: self.chain_code,
+            public_key: self.public_key(),
+        }
+    }
+
+    pub fn to_private_key(&self) -> PrivateKey {
+        PrivateKey::new(self.private_key, self.network)
+    }
+
+    // First 4 bytes of hash160 of the public key: how children (and PSBTs)
+    // refer to this key
+    pub fn fingerprint(&self) -> [u8; 4] {
+        self.to_xpub().fingerprint()
+    }
+
+    fn public_key(&self) -> secp256k1::PublicKey {
+        secp256k1::PublicKey::from_secret_key(sign::secp(), &self.private_key)
+    }
+}
+
+impl PartialEq for Xpriv {
+    fn eq(&self, other: &Self) -> bool {
+        let ours = Zeroizing::new(self.private_key.secret_bytes());
+        let theirs = Zeroizing::new(other.private_key.secret_bytes());
+        let secrets = ours.ct_eq(&*theirs) & self.chain_code.ct_eq(&other.chain_code);
+        bool::from(secrets)
+            & (self.network == other.network)
+            & (self.depth == other.depth)
+            & (self.parent_fingerprint == other.parent_fingerprint)
+            & (self.child_number == other.child_number)
+    }
+}
+
+impl Eq for Xpriv {}
+
+impl Drop for Xpriv {
+    fn drop(&mut self) {
+        self.private_key.non_secure_erase();
+        self.chain_code.zeroize();
+    }
+}
+
+impl Xpub {
+    // Only non-hardened children can be derived from a public key
+    pub fn derive_child(&self, index: u32) -> Result<Self, BitcoinError> {
+        if index >= HARDENED {
+            return Err(BitcoinError::HardenedDerivationFromXpub(index));
+        }
+        let mut data = self.public_key.serialize().to_vec();
+        data.extend(index.to_be_bytes());
+        let (tweak, chain_code) = child_tweak(&self.chain_code, &data)?;
+
+        let public_key = self
+            .public_key
+            .add_exp_tweak(sign::secp(), &tweak)
+            .map_err(|_| BitcoinError::InvalidKey)?;
+        Ok(Xpub {
+            network: self.network,
+            depth: self.depth.checked_add(1).ok_or(BitcoinError::InvalidKey)?,
+            parent_fingerprint: self.fingerprint(),
+            child_number: index,
+            chain_code,
+            public_key,
+        })
+    }
+
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, BitcoinError> {
+        path.0
+            .iter()
+            .try_fold(*self, |key, &index| key.derive_child(index))
+    }
+
+    pub fn to_public_key(&self) -> PublicKey {
+        PublicKey::new(self.public_key)
+    }
+
+    pub fn fingerprint(&self) -> [u8; 4] {
+        hashes::hash160(&self.public_key.serialize())[..4]
+            .try_into()
+            .unwrap()
+    }
+}
+
+// HMAC-SHA512(chain_code, data), split into the key tweak (left half) and
+// the child's chain code (right half). The tweak must be below the curve
+// order; BIP32 says to skip to the next index if it isn't, which we leave to
+// the caller.
+fn child_tweak(chain_code: &[u8; 32], data: &[u8]) -> Result<(Scalar, [u8; 32]), BitcoinError> {
+    let i = Zeroizing::new(hashes::hmac_sha512(chain_code, data));
+    let tweak =
+        Scalar::from_be_bytes(i[..32].try_into().unwrap()).map_err(|_| BitcoinError::InvalidKey)?;
+    Ok((tweak, i[32..].try_into().unwrap()))
+}
+
+fn encode(
+    version: [u8; 4],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: &[u8; 32],
+    key: &[u8],
+) -> String {
+    let mut data = Zeroizing::new(Vec::with_capacity(ENCODED_LEN));
+    data.extend(version);
+    data.push(depth);
+    data.extend(parent_fingerprint);
+    data.extend(child_number.to_be_bytes());
+    data.extend(chain_code);
+    data.extend(key);
+    base58::encode_check_zeroizing(&data)
+}
+
+// The fields common to both key types, after checking length and the
+// depth-0 invariants. Test networks share one version, so they come back
+// as Testnet.
+struct Decoded {
+    network: Network,
+    private: bool,
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: [u8; 32],
+    key: [u8; 33],
+}
+
+// For an xprv, `key` and `chain_code` are secret
+impl Drop for Decoded {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        self.chain_code.zeroize();
+    }
+}
+
+fn decode(s: &str) -> Result<Decoded, BitcoinError> {
+    let invalid = |reason: &str| BitcoinError::InvalidExtendedKey(reason.into());
+    let data = base58::decode_check_zeroizing(s)?;
+    if data.len() != ENCODED_LEN {
+        return Err(invalid("invalid length"));
+    }
+    let (network, private) = match data[..4].try_into().unwrap() {
+        XPRV_MAINNET => (Network::Mainnet, true),
+        XPUB_MAINNET => (Network::Mainnet, false),
+        XPRV_TESTNET => (Network::Testnet, true),
+        XPUB_TESTNET => (Network::Testnet, false),
+        _ => return Err(invalid("unknown version bytes")),
+    };
+    let decoded = Decoded {
+        network,
+        private,
+        depth: data[4],
+        parent_fingerprint: data[5..9].try_into().unwrap(),
+        child_number: u32::from_be_bytes(data[9..13].try_into().unwrap()),
+        chain_code: data[13..45].try_into().unwrap(),
+        key: data[45..].try_into().unwrap(),
+    };
+    if decoded.depth == 0 && (decoded.parent_fingerprint != [0; 4] || decoded.child_number != 0) {
+        return Err(invalid("master key with a parent"));
+    }
+    Ok(decoded)
+}
+
+impl fmt::Display for Xpriv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = match self.network {
+            Network::Mainnet => XPRV_MAINNET,
+            _ => XPRV_TESTNET,
+        };
+        let mut key = Zeroizing::new(vec![0]);
+        key.extend(self.private_key.secret_bytes());
+        let encoded = encode(
+            version,
+            self.depth,
+            self.parent_fingerprint,
+            self.child_number,
+            &self.chain_code,
+            &key,
+        );
+        write!(f, "{}", encoded)
+    }
+}
+
+impl FromStr for Xpriv {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let decoded = decode(s)?;
+        if !decoded.private {
+            return Err(BitcoinError::InvalidExtendedKey(
+                "expected a private key".into(),
+            ));
+        }
+        if decoded.key[0] != 0 {
+            return Err(BitcoinError::InvalidExtendedKey(
+                "private key must start with 0x00".into(),
+            ));
+        }
+        Ok(Xpriv {
+            network: decoded.network,
+            depth: decoded.depth,
+            parent_fingerprint: decoded.parent_fingerprint,
+            child_number: decoded.child_number,
+            chain_code: decoded.chain_code,
+            private_key: SecretKey::from_slice(&decoded.key[1..])
+                .map_err(|_| BitcoinError::InvalidKey)?,
+        })
+    }
+}
+
+impl fmt::Display for Xpub {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = match self.network {
+            Network::Mainnet => XPUB_MAINNET,
+            _ => XPUB_TESTNET,
+        };
+        let encoded = encode(
+            version,
+            self.depth,
+            self.parent_fingerprint,
+            self.child_number,
+            &self.chain_code,
+            &self.public_key.serialize(),
+        );
+        write!(f, "{}", encoded)
+    }
+}
+
+impl FromStr for Xpub {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let decoded = decode(s)?;
+        if decoded.private {
+            return Err(BitcoinError::InvalidExtendedKey(
+                "expected a public key".into(),
+            ));
+        }
+        Ok(Xpub {
+            network: decoded.network,
+            depth: decoded.depth,
+            parent_fingerprint: decoded.parent_fingerprint,
+            child_number: decoded.child_number,
+            chain_code: decoded.chain_code,
+            public_key: secp256k1::PublicKey::from_slice(&decoded.key)
+                .map_err(|_| BitcoinError::InvalidKey)?,
+        })
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{BitcoinError, hashes};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use subtle::ConstantTimeEq;
+use unicode_normalization::UnicodeNormalization;
+use zeroize::{Zeroize, Zeroizing};
+
+// BIP39 mnemonics (`bip39` feature). A phrase is entropy (128 to 256 bits)
+// plus a short SHA-256 checksum, cut into 11-bit groups that each pick one
+// of 2048 words. The phrase itself is never used as a key: it's stretched
+// into a 64-byte seed with PBKDF2, which then becomes the BIP32 master key.
+//
+// Only the English wordlist is included. Passphrases are NFKD-normalized
+// as the BIP requires, so the same passphrase typed as composed or
+// decomposed Unicode gives the same wallet.
+//
+// The entropy is the whole wallet, so it's compared in constant time and
+// erased on drop, and so are the phrase and PBKDF2 buffers made from it.
+
+const WORDLIST: &str = include_str!("bip39/english.txt");
+const PBKDF2_ROUNDS: u32 = 2048;
+
+fn wordlist() -> &'static [&'static str] {
+    static WORDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    WORDS.get_or_init(|| WORDLIST.lines().collect())
+}
+
+#[derive(Clone)]
+pub struct Mnemonic {
+    entropy: Vec<u8>,
+}
+
+impl Mnemonic {
+    // A fresh phrase of 12, 15, 18, 21 or 24 words from OS randomness
+    pub fn generate(word_count: usize) -> Result<Self, BitcoinError> {
+        if !(12..=24).contains(&word_count) || !word_count.is_multiple_of(3) {
+            return Err(BitcoinError::InvalidMnemonic(format!(
+                "can't have {} words",
+                word_count
+            )));
+        }
+        let mut entropy = Zeroizing::new(vec![0u8; word_count / 3 * 4]);
+        getrandom::getrandom(&mut entropy).map_err(|_| BitcoinError::Io(io::ErrorKind::Other))?;
+        Self::from_entropy(&entropy)
+    }
+
+    // 16 to 32 bytes, in steps of 4
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self, BitcoinError> {
+        if !(16..=32).contains(&entropy.len()) || !entropy.len().is_multiple_of(4) {
+            return Err(BitcoinError::InvalidMnemonic(format!(
+                "entropy can't be {} bytes",
+                entropy.len()
+            )));
+        }
+        Ok(Mnemonic {
+            entropy: entropy.to_vec(),
+        })
+    }
+
+    // Checks word count, that every word is in the list, and the checksum.
+    // Words can be separated by any whitespace and are matched lowercase.
+    pub fn parse(phrase: &str) -> Result<Self, BitcoinError> {
+        let words: Zeroizing<Vec<String>> =
+            Zeroizing::new(phrase.split_whitespace().map(str::to_lowercase).collect());
+        if !(12..=24).contains(&words.len()) || !words.len().is_multiple_of(3) {
+            return Err(BitcoinError::InvalidMnemonic(format!(
+                "can't have {} words",
+                words.len()
+            )));
+        }
+
+        // Concatenate the 11-bit word indexes into one bit string
+        let mut bits = Zeroizing::new(Vec::with_capacity(words.len() * 11));
+        for word in words.iter() {
+            let index = wordlist()
+                .binary_search(&word.as_str())
+                .map_err(|_| BitcoinError::InvalidMnemonic("unknown word".into()))?;
+            bits.extend((0..11).rev().map(|bit| (index >> bit) & 1 == 1));
+        }
+
+        // The last 1/33 of the bits are the checksum
+        let checksum_bits = bits.len() / 33;
+        let (entropy_bits, checksum) = bits.split_at(bits.len() - checksum_bits);
+        let entropy: Vec<u8> = entropy_bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, &bit| (acc << 1) | bit as u8))
+            .collect();
+        let mnemonic = Mnemonic { entropy };
+        if *mnemonic.checksum_bits() != checksum {
+            return Err(BitcoinError::InvalidMnemonic("bad checksum".into()));
+        }
+        Ok(mnemonic)
+    }
+
+    pub fn entropy(&self) -> &[u8] {
+        &self.entropy
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.entropy.len() * 3 / 4
+    }
+
+    pub fn words(&self) -> Vec<&'static str> {
+        let mut bits: Zeroizing<Vec<bool>> = Zeroizing::new(
+            self.entropy
+                .iter()
+                .flat_map(|byte| (0..8).rev().map(move |bit| (byte >> bit) & 1 == 1))
+                .collect(),
+        );
+        bits.extend(self.checksum_bits().iter());
+        bits.chunks(11)
+            .map(|chunk| {
+                let index = chunk
+                    .iter()
+                    .fold(0usize, |acc, &bit| (acc << 1) | bit as usize);
+                wordlist()[index]
+            })
+            .collect()
+    }
+
+    // PBKDF2-HMAC-SHA512 over the phrase, salted with "mnemonic" +
+    // passphrase. Any passphrase gives a valid (different) wallet, so a typo
+    // here can't be detected.
+    pub fn to_seed(&self, passphrase: &str) -> [u8; 64] {
+        let phrase = Zeroizing::new(self.to_string());
+        // Sized up front so the salt never reallocates and leaves an unwiped
+        // copy of the passphrase behind
+        let normalized_len: usize = passphrase.nfkd().map(char::len_utf8).sum();
+        let mut salt = Zeroizing::new(Vec::with_capacity(8 + normalized_len + 4));
+        salt.extend(b"mnemonic");
+        for c in passphrase.nfkd() {
+            salt.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+        // We only ever need the first (and only) 64-byte block
+        salt.extend(1u32.to_be_bytes());
+
+        let mut u = Zeroizing::new(hashes::hmac_sha512(phrase.as_bytes(), &salt));
+        let mut seed = *u;
+        for _ in 1..PBKDF2_ROUNDS {
+            *u = hashes::hmac_sha512(phrase.as_bytes(), &*u);
+            seed.iter_mut().zip(u.iter()).for_each(|(s, u)| *s ^= u);
+        }
+        seed
+    }
+
+    // The BIP32 master key for this phrase and passphrase
+    #[cfg(feature = "secp256k1")]
+    pub fn to_xpriv(
+        &self,
+        network: crate::Network,
+        passphrase: &str,
+    ) -> Result<crate::Xpriv, BitcoinError> {
+        crate::Xpriv::new_master(network, &Zeroizing::new(self.to_seed(passphrase))[..])
+    }
+
+    // First entropy_bits / 32 bits of SHA256(entropy)
+    fn checksum_bits(&self) -> Zeroizing<Vec<bool>> {
+        let hash = Zeroizing::new(hashes::sha256(&self.entropy));
+        Zeroizing::new(
+            (0..self.entropy.len() / 4)
+                .map(|i| (hash[i / 8] >> (7 - i % 8)) & 1 == 1)
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.words().join(" "))
+    }
+}
+
+impl PartialEq for Mnemonic {
+    fn eq(&self, other: &Self) -> bool {
+        // Lengths aren't secret; ct_eq is false for different lengths anyway
+        bool::from(self.entropy.ct_eq(&other.entropy))
+    }
+}
+
+impl Eq for Mnemonic {}
+
+impl Drop for Mnemonic {
+    fn drop(&mut self) {
+        self.entropy.zeroize();
+    }
+}
+
+// The phrase is as secret as a private key, so keep it out of debug logs
+impl fmt::Debug for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mnemonic")
+            .field("word_count", &self.word_count())
+            .finish_non_exhaustive()
+    }
+}
+
+impl FromStr for Mnemonic {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Mnemonic::parse(s)
+    }
+}
@@ -0,0 +1,163 @@
+use crate::hashes::murmur3_32;
+use crate::{Txid, Wtxid};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+// A port of Bitcoin Core's CRollingBloomFilter. It remembers roughly the
+// last `max_elements` items inserted (somewhere between that and 1.5x) and
+// forgets older ones, so it can sit in front of a never-ending stream
+// without growing or needing to be cleared.
+//
+// Each bit position holds a 2-bit generation number (1-3) split across a pair
+// of u64 words. Every max_elements/2 inserts we move to the next generation
+// and wipe the bits belonging to the one before last.
+//
+// A bloom filter can say "seen" for something that wasn't (at roughly
+// `fp_rate`), but never says "not seen" for something recent.
+#[derive(Debug, Clone)]
+pub struct RollingBloomFilter {
+    data: Vec<u64>,
+    hash_funcs: u32,
+    entries_per_generation: u32,
+    entries_this_generation: u32,
+    generation: u32,
+    tweak: u32,
+}
+
+impl RollingBloomFilter {
+    pub fn new(max_elements: u32, fp_rate: f64) -> Self {
+        let log_fp_rate = fp_rate.ln();
+        let hash_funcs = ((log_fp_rate / 0.5f64.ln()).round() as i64).clamp(1, 50) as u32;
+        let entries_per_generation = max_elements.div_ceil(2).max(1);
+        let max_entries = entries_per_generation as f64 * 3.0;
+        let filter_bits = (-(hash_funcs as f64) * max_entries
+            / (1.0 - (log_fp_rate / hash_funcs as f64).exp()).ln())
+        .ceil() as usize;
+
+        let mut filter = RollingBloomFilter {
+            data: vec![0; filter_bits.div_ceil(64) * 2],
+            hash_funcs,
+            entries_per_generation,
+            entries_this_generation: 0,
+            generation: 1,
+            tweak: 0,
+        };
+        filter.reset();
+        filter
+    }
+
+    pub fn insert(&mut self, key: &[u8]) {
+        if self.entries_this_generation == self.entries_per_generation {
+            self.entries_this_generation = 0;
+            self.generation += 1;
+            if self.generation == 4 {
+                self.generation = 1;
+            }
+            // Clear every bit whose stored generation equals the new one
+            let mask1 = 0u64.wrapping_sub((self.generation & 1) as u64);
+            let mask2 = 0u64.wrapping_sub((self.generation >> 1) as u64);
+            for pair in self.data.chunks_exact_mut(2) {
+                let keep = (pair[0] ^ mask1) | (pair[1] ^ mask2);
+                pair[0] &= keep;
+                pair[1] &= keep;
+            }
+        }
+        self.entries_this_generation += 1;
+
+        for n in 0..self.hash_funcs {
+            let (pos, bit) = self.locate(n, key);
+            let lo = (self.generation & 1) as u64;
+            let hi = (self.generation >> 1) as u64;
+            self.data[pos] = (self.data[pos] & !(1 << bit)) | (lo << bit);
+            self.data[pos + 1] = (self.data[pos + 1] & !(1 << bit)) | (hi << bit);
+        }
+    }
+
+    pub fn contains(&self, key: &[u8]) -> bool {
+        (0..self.hash_funcs).all(|n| {
+            let (pos, bit) = self.locate(n, key);
+            ((self.data[pos] | self.data[pos + 1]) >> bit) & 1 == 1
+        })
+    }
+
+    // Forget everything. A fresh random tweak means an attacker who learned
+    // which items collide can't reuse that against the new filter.
+    pub fn reset(&mut self) {
+        self.tweak = RandomState::new().build_hasher().finish() as u32;
+        self.entries_this_generation = 0;
+        self.generation = 1;
+        self.data.fill(0);
+    }
+
+    // Index of the first word of the pair, and the bit within it
+    fn locate(&self, hash_num: u32, key: &[u8]) -> (usize, u32) {
+        let h = murmur3_32(
+            hash_num.wrapping_mul(0xFBA4C795).wrapping_add(self.tweak),
+            key,
+        );
+        // Core's FastMod: maps h onto 0..len using the high bits, which
+        // don't overlap with the 6 low bits used for the bit index
+        let pos = ((h as u64 * self.data.len() as u64) >> 32) as usize;
+        (pos & !1, h & 0x3F)
+    }
+}
+
+// Remembers which transactions we've recently seen announced or received,
+// so a relay or mempool monitor can skip ones it has already processed. Sized
+// like Core's recent-rejects filter by default.
+#[derive(Debug, Clone)]
+pub struct SeenTracker {
+    filter: RollingBloomFilter,
+}
+
+impl SeenTracker {
+    pub fn new(max_elements: u32, fp_rate: f64) -> Self {
+        SeenTracker {
+            filter: RollingBloomFilter::new(max_elements, fp_rate),
+        }
+    }
+
+    pub fn insert_txid(&mut self, txid: &Txid) {
+        self.filter.insert(&txid.0);
+    }
+
+    pub fn insert_wtxid(&mut self, wtxid: &Wtxid) {
+        self.filter.insert(&wtxid.0);
+    }
+
+    pub fn contains_txid(&self, txid: &Txid) -> bool {
+        self.filter.contains(&txid.0)
+    }
+
+    pub fn contains_wtxid(&self, wtxid: &Wtxid) -> bool {
+        self.filter.contains(&wtxid.0)
+    }
+
+    // Marks the txid as seen and returns whether it's new, i.e. whether the
+    // caller should go ahead and process it
+    pub fn check_and_insert_txid(&mut self, txid: &Txid) -> bool {
+        let new = !self.contains_txid(txid);
+        if new {
+            self.insert_txid(txid);
+        }
+        new
+    }
+
+    pub fn check_and_insert_wtxid(&mut self, wtxid: &Wtxid) -> bool {
+        let new = !self.contains_wtxid(wtxid);
+        if new {
+            self.insert_wtxid(wtxid);
+        }
+        new
+    }
+
+    pub fn reset(&mut self) {
+        self.filter.reset();
+    }
+}
+
+impl Default for SeenTracker {
+    fn default() -> Self {
+        SeenTracker::new(120_000, 0.000_001)
+    }
+}
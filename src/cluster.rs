@@ -0,0 +1,149 @@
+use crate::{BitcoinTransaction, OutPoint, Script, Txid};
+use std::collections::{HashMap, HashSet};
+
+// Common-input-ownership clustering: every input of a transaction has to be
+// signed, so in the usual case all the scripts being spent belong to the
+// same wallet. Feeding transactions through this merges their input scripts
+// into one cluster. The heuristic breaks on CoinJoins, where several people
+// contribute inputs, so those txids can be excluded up front.
+//
+// Transactions only reference what they spend by outpoint, so the caller
+// supplies a resolver that maps an outpoint back to its scriptPubKey (from a
+// UTXO set, an indexer, earlier transactions, ...). Inputs it can't resolve
+// are just skipped.
+//
+// Internally this is a standard union-find (disjoint set) keyed by script.
+#[derive(Debug, Default, Clone)]
+pub struct AddressClusters {
+    index: HashMap<Script, usize>,
+    scripts: Vec<Script>,
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    excluded: HashSet<Txid>,
+}
+
+impl AddressClusters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Known CoinJoins (or anything else multi-party). Their outputs are still
+    // recorded, but their inputs are not merged.
+    pub fn exclude(&mut self, txid: Txid) -> &mut Self {
+        self.excluded.insert(txid);
+        self
+    }
+
+    pub fn add_transaction<F>(&mut self, tx: &BitcoinTransaction, mut resolve_prevout: F)
+    where
+        F: FnMut(&OutPoint) -> Option<Script>,
+    {
+        // Outputs get a (possibly singleton) cluster so they can be queried
+        for output in &tx.outputs {
+            self.insert(&output.script_pubkey);
+        }
+
+        if tx.is_coinbase() || self.excluded.contains(&tx.txid()) {
+            return;
+        }
+
+        let mut first = None;
+        for input in tx.iter_inputs() {
+            let Some(script) = resolve_prevout(&input.previous_output) else {
+                continue;
+            };
+            let node = self.insert(&script);
+            match first {
+                None => first = Some(node),
+                Some(first) => self.union(first, node),
+            }
+        }
+    }
+
+    // Number of distinct scripts seen so far
+    pub fn len(&self) -> usize {
+        self.scripts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    pub fn contains(&self, script: &Script) -> bool {
+        self.index.contains_key(script)
+    }
+
+    pub fn same_cluster(&self, a: &Script, b: &Script) -> bool {
+        match (self.index.get(a), self.index.get(b)) {
+            (Some(&a), Some(&b)) => self.root(a) == self.root(b),
+            _ => false,
+        }
+    }
+
+    // Every script in the same cluster as `script`, including itself, or None
+    // if we've never seen it
+    pub fn cluster_of(&self, script: &Script) -> Option<Vec<&Script>> {
+        let root = self.root(*self.index.get(script)?);
+        Some(
+            (0..self.scripts.len())
+                .filter(|&node| self.root(node) == root)
+                .map(|node| &self.scripts[node])
+                .collect(),
+        )
+    }
+
+    pub fn cluster_size(&self, script: &Script) -> Option<usize> {
+        let root = self.root(*self.index.get(script)?);
+        Some(self.size[root])
+    }
+
+    // All clusters, each as a list of scripts, in the order their first
+    // script was seen
+    pub fn clusters(&self) -> Vec<Vec<&Script>> {
+        let mut by_root: HashMap<usize, usize> = HashMap::new();
+        let mut clusters: Vec<Vec<&Script>> = Vec::new();
+        for (node, script) in self.scripts.iter().enumerate() {
+            let slot = *by_root.entry(self.root(node)).or_insert_with(|| {
+                clusters.push(Vec::new());
+                clusters.len() - 1
+            });
+            clusters[slot].push(script);
+        }
+        clusters
+    }
+
+    fn insert(&mut self, script: &Script) -> usize {
+        if let Some(&node) = self.index.get(script) {
+            return node;
+        }
+        let node = self.scripts.len();
+        self.index.insert(script.clone(), node);
+        self.scripts.push(script.clone());
+        self.parent.push(node);
+        self.size.push(1);
+        node
+    }
+
+    // Union by size keeps trees O(log n) deep, so lookups through a shared
+    // reference don't need path compression to stay cheap
+    fn root(&self, mut node: usize) -> usize {
+        while self.parent[node] != node {
+            node = self.parent[node];
+        }
+        node
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.root(a), self.root(b));
+        if a == b {
+            return;
+        }
+        let (big, small) = if self.size[a] >= self.size[b] {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        self.parent[small] = big;
+        self.size[big] += self.size[small];
+    }
+}
@@ -0,0 +1,769 @@
+use crate::trace::{debug, trace};
+use crate::{
+    Amount, BitcoinError, BitcoinTransaction, CompactSize, Decoded, OutPoint, Script,
+    TransactionInput, TransactionOutput, Txid, Witness, length_to_usize,
+};
+use std::fmt;
+use std::io::{self, Read, Write};
+
+// Consensus encoding over std::io, so values can be written straight into a
+// socket or file and read back from any `Read` without first buffering the
+// whole thing into a slice. The slice-based `to_bytes`/`decode` methods on
+// each type are convenience wrappers around the same format.
+//
+// Most consensus structures are just their fields written one after another:
+// fixed-width little-endian integers, 32-byte hashes, and CompactSize-prefixed
+// lists. Each field type implements these traits once and
+// `impl_consensus_codec!` stitches the fields of a struct together.
+pub trait ConsensusEncodable {
+    // Returns the number of bytes written
+    fn consensus_encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<usize>;
+}
+
+pub trait ConsensusDecodable: Sized {
+    fn consensus_decode<R: Read + ?Sized>(reader: &mut R) -> Result<Self, BitcoinError>;
+
+    // Decode through a position-tracking Decoder. Types made of several
+    // fields override this to label each field, and implement
+    // consensus_decode as `Self::consensus_decode_from(&mut Decoder::new(reader))`.
+    // Simple leaf types (integers, hashes, byte strings) can keep the default.
+    fn consensus_decode_from<R: Read + ?Sized>(
+        decoder: &mut Decoder<'_, R>,
+    ) -> Result<Self, BitcoinError> {
+        Self::consensus_decode(decoder)
+    }
+}
+
+// Wraps a reader and counts how many bytes have gone through it, so when a
+// nested decode fails we can say exactly where, e.g.
+// `inputs[3].script_sig` starting at byte 245, instead of a bare
+// InsufficientBytes on a 300-byte blob.
+pub struct Decoder<'r, R: Read + ?Sized> {
+    reader: &'r mut R,
+    position: usize,
+    peeked: Option<u8>,
+    limits: DecodeLimits,
+    budget: DecodeBudget,
+    elements: u64,
+    depth: u32,
+}
+
+// Caps on what untrusted input is allowed to make us decode. Every count and
+// length prefix is checked against these (and against what's left of
+// max_total_size) before anything is read, so a crafted CompactSize of 2^32
+// fails immediately with OversizedAllocation instead of churning through
+// memory. The defaults are the most a transaction could need and still fit
+// in a block, so no valid transaction is rejected by them.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DecodeLimits {
+    pub max_inputs: u64,
+    pub max_outputs: u64,
+    // Applies to scriptSigs, scriptPubKeys and individual witness items
+    pub max_script_len: u64,
+    pub max_total_size: u64,
+    // Reject CompactSizes written longer than needed (NonMinimalVarInt), as
+    // Core's ReadCompactSize does. Off by default so legacy data that padded
+    // its counts still decodes.
+    pub minimal_compact_size: bool,
+}
+
+impl DecodeLimits {
+    // 4M weight units, and a byte costs at least one unit
+    pub const MAX_TX_SIZE: u64 = 4_000_000;
+
+    pub fn unlimited() -> Self {
+        DecodeLimits {
+            max_inputs: u64::MAX,
+            max_outputs: u64::MAX,
+            max_script_len: u64::MAX,
+            max_total_size: u64::MAX,
+            minimal_compact_size: false,
+        }
+    }
+
+    // The defaults, plus minimal CompactSize encodings, for consensus data
+    pub fn minimal() -> Self {
+        DecodeLimits {
+            minimal_compact_size: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits {
+            // Smallest input is 41 bytes, smallest output 9
+            max_inputs: Self::MAX_TX_SIZE / 41,
+            max_outputs: Self::MAX_TX_SIZE / 9,
+            max_script_len: Self::MAX_TX_SIZE,
+            max_total_size: Self::MAX_TX_SIZE,
+            minimal_compact_size: false,
+        }
+    }
+}
+
+// A budget for decoding one untrusted message as a whole, on top of the
+// per-field DecodeLimits: total bytes read, total elements (list items and
+// witness items) across every list, and how deeply fields may nest. Running
+// out of any of them fails with BudgetExceeded, so a peer can't make us do
+// unbounded work with lots of small items or deep structures. The defaults
+// fit anything that fits in a 4 MB message.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DecodeBudget {
+    pub max_bytes: u64,
+    pub max_elements: u64,
+    pub max_depth: u32,
+}
+
+// Which part of the budget ran out
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum BudgetResource {
+    Bytes,
+    Elements,
+    Depth,
+}
+
+impl DecodeBudget {
+    pub fn unlimited() -> Self {
+        DecodeBudget {
+            max_bytes: u64::MAX,
+            max_elements: u64::MAX,
+            max_depth: u32::MAX,
+        }
+    }
+}
+
+impl Default for DecodeBudget {
+    fn default() -> Self {
+        DecodeBudget {
+            max_bytes: DecodeLimits::MAX_TX_SIZE,
+            // Every element takes at least a byte
+            max_elements: DecodeLimits::MAX_TX_SIZE,
+            // A transaction nests 4 deep (inputs[i].previous_output.txid)
+            max_depth: 32,
+        }
+    }
+}
+
+impl std::fmt::Display for BudgetResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BudgetResource::Bytes => "bytes",
+            BudgetResource::Elements => "elements",
+            BudgetResource::Depth => "nesting depth",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Initial capacity for a list is capped to this many bytes' worth of items;
+// beyond that the Vec grows as items actually decode
+const MAX_PREALLOC_BYTES: usize = 64 * 1024;
+
+// Clamp in u64 first, so a count over usize::MAX on a 32-bit target can't
+// truncate on the way to with_capacity
+fn prealloc_capacity<T>(count: u64) -> usize {
+    let prealloc = MAX_PREALLOC_BYTES / std::mem::size_of::<T>().max(1);
+    count.min(prealloc as u64) as usize
+}
+
+// One step of the field path in a decode error. List indexes stay numbers
+// until an error is actually built, so decoding a list doesn't format a
+// label for every element.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PathSegment<'a> {
+    Name(&'a str),
+    Index(u64),               // "[i]"
+    NamedIndex(&'a str, u64), // "name[i]"
+}
+
+impl fmt::Display for PathSegment<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Name(name) => write!(f, "{}", name),
+            PathSegment::Index(i) => write!(f, "[{}]", i),
+            PathSegment::NamedIndex(name, i) => write!(f, "{}[{}]", name, i),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for PathSegment<'a> {
+    fn from(name: &'a str) -> Self {
+        PathSegment::Name(name)
+    }
+}
+
+impl<'r, R: Read + ?Sized> Decoder<'r, R> {
+    pub fn new(reader: &'r mut R) -> Self {
+        Self::with_limits(reader, DecodeLimits::default())
+    }
+
+    pub fn with_limits(reader: &'r mut R, limits: DecodeLimits) -> Self {
+        Self::with_budget(reader, limits, DecodeBudget::default())
+    }
+
+    pub fn with_budget(reader: &'r mut R, limits: DecodeLimits, budget: DecodeBudget) -> Self {
+        Decoder {
+            reader,
+            position: 0,
+            peeked: None,
+            limits,
+            budget,
+            elements: 0,
+            depth: 0,
+        }
+    }
+
+    // Bytes consumed since this decoder was created
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn limits(&self) -> &DecodeLimits {
+        &self.limits
+    }
+
+    pub fn budget(&self) -> &DecodeBudget {
+        &self.budget
+    }
+
+    // Reject a count or length prefix that's over `limit`, or that couldn't
+    // possibly fit in what's left of the total size budget (every item takes
+    // at least a byte)
+    pub fn check_len(&self, requested: u64, limit: u64) -> Result<(), BitcoinError> {
+        let remaining = self
+            .limits
+            .max_total_size
+            .saturating_sub(self.position as u64);
+        let limit = limit.min(remaining);
+        if requested > limit {
+            debug!(
+                requested,
+                limit,
+                position = self.position,
+                "length prefix over decode limit"
+            );
+            return Err(BitcoinError::OversizedAllocation { requested, limit });
+        }
+        let bytes_left = self.budget.max_bytes.saturating_sub(self.position as u64);
+        if requested > bytes_left {
+            return Err(budget_exceeded(
+                BudgetResource::Bytes,
+                self.budget.max_bytes,
+            ));
+        }
+        Ok(())
+    }
+
+    // Charge `count` list or witness items against the element budget
+    pub fn count_elements(&mut self, count: u64) -> Result<(), BitcoinError> {
+        self.elements = self.elements.saturating_add(count);
+        if self.elements > self.budget.max_elements {
+            return Err(budget_exceeded(
+                BudgetResource::Elements,
+                self.budget.max_elements,
+            ));
+        }
+        Ok(())
+    }
+
+    // A CompactSize count followed by that many items, each labelled "[i]"
+    pub fn decode_list<T: ConsensusDecodable>(
+        &mut self,
+        max_items: u64,
+    ) -> Result<Vec<T>, BitcoinError> {
+        let count = CompactSize::consensus_decode_from(self)?.value;
+        self.check_len(count, max_items)?;
+        self.count_elements(count)?;
+        let mut items = Vec::with_capacity(prealloc_capacity::<T>(count));
+        for i in 0..count {
+            items.push(self.decode(PathSegment::Index(i))?);
+        }
+        Ok(items)
+    }
+
+    // A CompactSize length followed by that many bytes
+    pub fn read_var_bytes(&mut self) -> Result<Vec<u8>, BitcoinError> {
+        let len = CompactSize::consensus_decode_from(self)?.value;
+        self.check_len(len, self.limits.max_script_len)?;
+        read_exact_vec(self, length_to_usize(len)?)
+    }
+
+    // Look at the next byte without consuming it. Needed where the format
+    // itself is ambiguous until you've seen a byte (the segwit marker).
+    pub fn peek_byte(&mut self) -> Result<u8, BitcoinError> {
+        if let Some(byte) = self.peeked {
+            return Ok(byte);
+        }
+        let mut buf = [0u8; 1];
+        fill(self.reader, &mut buf)?;
+        self.peeked = Some(buf[0]);
+        Ok(buf[0])
+    }
+
+    // Run `f` as the named field. If it fails, the error records the field
+    // name and the offset the field started at. Nested calls build up a
+    // path, and the innermost (most precise) offset is the one kept.
+    pub fn field<'n, T>(
+        &mut self,
+        name: impl Into<PathSegment<'n>>,
+        f: impl FnOnce(&mut Self) -> Result<T, BitcoinError>,
+    ) -> Result<T, BitcoinError> {
+        let name = name.into();
+        let start = self.position;
+        if self.depth >= self.budget.max_depth {
+            let err = budget_exceeded(BudgetResource::Depth, self.budget.max_depth as u64);
+            return Err(err.in_field(name, start));
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        let value = result.map_err(|err| {
+            trace!(field = %name, offset = start, error = %err, "field failed to decode");
+            err.in_field(name, start)
+        })?;
+        if self.position as u64 > self.limits.max_total_size {
+            let err = BitcoinError::OversizedAllocation {
+                requested: self.position as u64,
+                limit: self.limits.max_total_size,
+            };
+            return Err(err.in_field(name, start));
+        }
+        if self.position as u64 > self.budget.max_bytes {
+            let err = budget_exceeded(BudgetResource::Bytes, self.budget.max_bytes);
+            return Err(err.in_field(name, start));
+        }
+        Ok(value)
+    }
+
+    pub fn decode<'n, T: ConsensusDecodable>(
+        &mut self,
+        name: impl Into<PathSegment<'n>>,
+    ) -> Result<T, BitcoinError> {
+        self.field(name, |d| T::consensus_decode_from(d))
+    }
+}
+
+impl<R: Read + ?Sized> Read for Decoder<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(byte) = self.peeked.take() {
+            buf[0] = byte;
+            self.position += 1;
+            return Ok(1);
+        }
+        let n = self.reader.read(buf)?;
+        self.position += n;
+        Ok(n)
+    }
+}
+
+// Run a streaming decoder over a slice and report how much of it was used
+pub(crate) fn decode_slice<T: ConsensusDecodable>(
+    bytes: &[u8],
+) -> Result<Decoded<T>, BitcoinError> {
+    decode_slice_with_limits(bytes, DecodeLimits::default())
+}
+
+pub(crate) fn decode_slice_with_limits<T: ConsensusDecodable>(
+    bytes: &[u8],
+    limits: DecodeLimits,
+) -> Result<Decoded<T>, BitcoinError> {
+    decode_slice_with_budget(bytes, limits, DecodeBudget::default())
+}
+
+pub(crate) fn decode_slice_with_budget<T: ConsensusDecodable>(
+    bytes: &[u8],
+    limits: DecodeLimits,
+    budget: DecodeBudget,
+) -> Result<Decoded<T>, BitcoinError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(
+        "decode",
+        target = std::any::type_name::<T>(),
+        len = bytes.len()
+    )
+    .entered();
+
+    let mut cursor = io::Cursor::new(bytes);
+    let mut decoder = Decoder::with_budget(&mut cursor, limits, budget);
+    let result = T::consensus_decode_from(&mut decoder);
+    #[cfg(feature = "tracing")]
+    if let Err(err) = &result {
+        tracing::debug!(error = %err, root_cause = %err.root_cause(), "decode failed");
+    }
+    let value = result?;
+    let consumed = decoder.position();
+    trace!(consumed, "decoded");
+    Ok(Decoded::new(value, consumed))
+}
+
+pub(crate) fn encode_to_vec<T: ConsensusEncodable + ?Sized>(value: &T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    value
+        .consensus_encode(&mut bytes)
+        .expect("writing to a Vec can't fail");
+    bytes
+}
+
+fn budget_exceeded(resource: BudgetResource, limit: u64) -> BitcoinError {
+    debug!(%resource, limit, "decode budget exceeded");
+    BitcoinError::BudgetExceeded { resource, limit }
+}
+
+// read_exact, but on a short read we report how far we got
+fn fill<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> Result<(), BitcoinError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => {
+                return Err(BitcoinError::UnexpectedEof {
+                    needed: buf.len(),
+                    available: filled,
+                });
+            }
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+fn read_array<const N: usize, R: Read + ?Sized>(reader: &mut R) -> Result<[u8; N], BitcoinError> {
+    let mut buf = [0u8; N];
+    fill(reader, &mut buf)?;
+    Ok(buf)
+}
+
+// Read exactly `len` bytes. `take` + `read_to_end` only grows the buffer as
+// data actually arrives, so a lying length prefix can't make us allocate
+// gigabytes up front.
+fn read_exact_vec<R: Read + ?Sized>(reader: &mut R, len: usize) -> Result<Vec<u8>, BitcoinError> {
+    let mut buf = Vec::new();
+    reader.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() < len {
+        return Err(BitcoinError::UnexpectedEof {
+            needed: len,
+            available: buf.len(),
+        });
+    }
+    Ok(buf)
+}
+
+fn write_var_bytes<W: Write + ?Sized>(writer: &mut W, bytes: &[u8]) -> io::Result<usize> {
+    let prefix = CompactSize::new(bytes.len() as u64).consensus_encode(writer)?;
+    writer.write_all(bytes)?;
+    Ok(prefix + bytes.len())
+}
+
+impl ConsensusEncodable for u32 {
+    fn consensus_encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<usize> {
+        writer.write_all(&self.to_le_bytes())?;
+        Ok(4)
+    }
+}
+
+impl ConsensusDecodable for u32 {
+    fn consensus_decode<R: Read + ?Sized>(reader: &mut R) -> Result<Self, BitcoinError> {
+        Ok(u32::from_le_bytes(read_array(reader)?))
+    }
+}
+
+impl ConsensusEncodable for u64 {
+    fn consensus_encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<usize> {
+        writer.write_all(&self.to_le_bytes())?;
+        Ok(8)
+    }
+}
+
+impl ConsensusDecodable for u64 {
+    fn consensus_decode<R: Read + ?Sized>(reader: &mut R) -> Result<Self, BitcoinError> {
+        Ok(u64::from_le_bytes(read_array(reader)?))
+    }
+}
+
+// 8-byte little-endian satoshis, same as the u64 it wraps
+impl ConsensusEncodable for Amount {
+    fn consensus_encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<usize> {
+        self.to_sat().consensus_encode(writer)
+    }
+}
+
+impl ConsensusDecodable for Amount {
+    fn consensus_decode<R: Read + ?Sized>(reader: &mut R) -> Result<Self, BitcoinError> {
+        u64::consensus_decode(reader).map(Amount::from_sat)
+    }
+}
+
+impl ConsensusEncodable for Txid {
+    fn consensus_encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<usize> {
+        writer.write_all(&self.0)?;
+        Ok(32)
+    }
+}
+
+impl ConsensusDecodable for Txid {
+    fn consensus_decode<R: Read + ?Sized>(reader: &mut R) -> Result<Self, BitcoinError> {
+        Ok(Txid(read_array(reader)?))
+    }
+}
+
+impl ConsensusEncodable for CompactSize {
+    fn consensus_encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<usize> {
+        let bytes = self.to_bytes();
+        writer.write_all(&bytes)?;
+        Ok(bytes.len())
+    }
+}
+
+// The first byte says how many more bytes to read (see CompactSize::decode).
+// Returns the value and how many bytes it took.
+fn read_compact_size<R: Read + ?Sized>(
+    reader: &mut R,
+) -> Result<(CompactSize, usize), BitcoinError> {
+    let [prefix] = read_array(reader)?;
+    let (value, len) = match prefix {
+        0xFD => (u16::from_le_bytes(read_array(reader)?) as u64, 3),
+        0xFE => (u32::from_le_bytes(read_array(reader)?) as u64, 5),
+        0xFF => (u64::from_le_bytes(read_array(reader)?), 9),
+        small => (small as u64, 1),
+    };
+    Ok((CompactSize::new(value), len))
+}
+
+// Lenient on a bare reader, like CompactSize::decode. Through a Decoder it
+// follows DecodeLimits::minimal_compact_size.
+impl ConsensusDecodable for CompactSize {
+    fn consensus_decode<R: Read + ?Sized>(reader: &mut R) -> Result<Self, BitcoinError> {
+        read_compact_size(reader).map(|(size, _)| size)
+    }
+
+    fn consensus_decode_from<R: Read + ?Sized>(
+        decoder: &mut Decoder<'_, R>,
+    ) -> Result<Self, BitcoinError> {
+        let (size, len) = read_compact_size(decoder)?;
+        if decoder.limits().minimal_compact_size && size.encoded_len() != len {
+            return Err(BitcoinError::NonMinimalVarInt);
+        }
+        Ok(size)
+    }
+}
+
+impl ConsensusEncodable for OutPoint {
+    fn consensus_encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<usize> {
+        Ok(self.txid.consensus_encode(writer)? + self.vout.consensus_encode(writer)?)
+    }
+}
+
+impl ConsensusDecodable for OutPoint {
+    fn consensus_decode<R: Read + ?Sized>(reader: &mut R) -> Result<Self, BitcoinError> {
+        Self::consensus_decode_from(&mut Decoder::new(reader))
+    }
+
+    fn consensus_decode_from<R: Read + ?Sized>(
+        decoder: &mut Decoder<'_, R>,
+    ) -> Result<Self, BitcoinError> {
+        let txid = decoder.decode("txid")?;
+        let vout = decoder.decode("vout")?;
+        Ok(OutPoint { txid, vout })
+    }
+}
+
+impl ConsensusEncodable for Script {
+    fn consensus_encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<usize> {
+        write_var_bytes(writer, &self.bytes)
+    }
+}
+
+impl ConsensusDecodable for Script {
+    fn consensus_decode<R: Read + ?Sized>(reader: &mut R) -> Result<Self, BitcoinError> {
+        Self::consensus_decode_from(&mut Decoder::new(reader))
+    }
+
+    fn consensus_decode_from<R: Read + ?Sized>(
+        decoder: &mut Decoder<'_, R>,
+    ) -> Result<Self, BitcoinError> {
+        Ok(Script::new(decoder.read_var_bytes()?))
+    }
+}
+
+impl ConsensusEncodable for Witness {
+    fn consensus_encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<usize> {
+        let mut written = CompactSize::new(self.items.len() as u64).consensus_encode(writer)?;
+        for item in &self.items {
+            written += write_var_bytes(writer, item)?;
+        }
+        Ok(written)
+    }
+}
+
+impl ConsensusDecodable for Witness {
+    fn consensus_decode<R: Read + ?Sized>(reader: &mut R) -> Result<Self, BitcoinError> {
+        Self::consensus_decode_from(&mut Decoder::new(reader))
+    }
+
+    fn consensus_decode_from<R: Read + ?Sized>(
+        decoder: &mut Decoder<'_, R>,
+    ) -> Result<Self, BitcoinError> {
+        let count = CompactSize::consensus_decode_from(decoder)?.value;
+        decoder.check_len(count, u64::MAX)?;
+        decoder.count_elements(count)?;
+        let mut items = Vec::with_capacity(prealloc_capacity::<Vec<u8>>(count));
+        for i in 0..count {
+            items.push(decoder.field(PathSegment::Index(i), |d| d.read_var_bytes())?);
+        }
+        Ok(Witness::from_items(items))
+    }
+}
+
+// A list is a CompactSize count followed by that many items. The count comes
+// from untrusted input, so it's checked against the decoder's size budget and
+// preallocation is capped (see Decoder::decode_list).
+impl<T: ConsensusEncodable> ConsensusEncodable for Vec<T> {
+    fn consensus_encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<usize> {
+        let mut written = CompactSize::new(self.len() as u64).consensus_encode(writer)?;
+        for item in self {
+            written += item.consensus_encode(writer)?;
+        }
+        Ok(written)
+    }
+}
+
+impl<T: ConsensusDecodable> ConsensusDecodable for Vec<T> {
+    fn consensus_decode<R: Read + ?Sized>(reader: &mut R) -> Result<Self, BitcoinError> {
+        Self::consensus_decode_from(&mut Decoder::new(reader))
+    }
+
+    fn consensus_decode_from<R: Read + ?Sized>(
+        decoder: &mut Decoder<'_, R>,
+    ) -> Result<Self, BitcoinError> {
+        decoder.decode_list(u64::MAX)
+    }
+}
+
+impl ConsensusEncodable for BitcoinTransaction {
+    fn consensus_encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<usize> {
+        self.encode_to(writer, self.has_witness())
+    }
+}
+
+impl ConsensusDecodable for BitcoinTransaction {
+    fn consensus_decode<R: Read + ?Sized>(reader: &mut R) -> Result<Self, BitcoinError> {
+        Self::consensus_decode_from(&mut Decoder::new(reader))
+    }
+
+    fn consensus_decode_from<R: Read + ?Sized>(
+        decoder: &mut Decoder<'_, R>,
+    ) -> Result<Self, BitcoinError> {
+        let version = decoder.decode("version")?;
+
+        // 0x00 0x01 right after the version means BIP144 segwit encoding.
+        // 0x00 0x00 is a legacy tx with no inputs and no outputs; that second
+        // byte is the output count, so we only peek at it. Any other flag is
+        // an error, as in Core.
+        let max_inputs = decoder.limits().max_inputs;
+        let max_outputs = decoder.limits().max_outputs;
+        let mut segwit = false;
+        let mut inputs: Vec<TransactionInput> = if decoder.field("inputs", |d| d.peek_byte())?
+            == 0x00
+        {
+            decoder.field("inputs", read_array::<1, _>)?;
+            match decoder.field("flag", |d| d.peek_byte())? {
+                0x00 => Vec::new(),
+                0x01 => {
+                    decoder.field("flag", read_array::<1, _>)?;
+                    segwit = true;
+                    decoder.field("inputs", |d| d.decode_list(max_inputs))?
+                }
+                flag => {
+                    return decoder.field("flag", |_| Err(BitcoinError::UnknownSegwitFlag(flag)));
+                }
+            }
+        } else {
+            decoder.field("inputs", |d| d.decode_list(max_inputs))?
+        };
+        let outputs: Vec<TransactionOutput> =
+            decoder.field("outputs", |d| d.decode_list(max_outputs))?;
+
+        if segwit {
+            // One witness stack per input, in input order
+            for (i, input) in inputs.iter_mut().enumerate() {
+                input.witness = decoder.decode(PathSegment::NamedIndex("witnesses", i as u64))?;
+            }
+        }
+
+        let lock_time = decoder.decode("lock_time")?;
+        Ok(BitcoinTransaction {
+            version,
+            inputs,
+            outputs,
+            lock_time,
+        })
+    }
+}
+
+// Generates ConsensusEncodable/ConsensusDecodable plus the usual public
+// `to_bytes`, `decode` and `from_bytes` methods for a struct whose wire
+// format is simply its fields in declaration order:
+//
+//     impl_consensus_codec!(OutPoint { txid: Txid, vout: u32 });
+//
+// Fields that live in the struct but aren't part of this particular encoding
+// can be listed after `default`; decode fills them with Default::default().
+//
+//     impl_consensus_codec!(Foo { a: u32 }, default { cache });
+macro_rules! impl_consensus_codec {
+    ($type:ident { $($field:ident : $field_type:ty),+ $(,)? } $(, default { $($skip:ident),+ })?) => {
+        impl $crate::ConsensusEncodable for $type {
+            fn consensus_encode<W: std::io::Write + ?Sized>(
+                &self,
+                writer: &mut W,
+            ) -> std::io::Result<usize> {
+                let mut written = 0;
+                $( written += $crate::ConsensusEncodable::consensus_encode(&self.$field, writer)?; )+
+                Ok(written)
+            }
+        }
+
+        impl $crate::ConsensusDecodable for $type {
+            fn consensus_decode<R: std::io::Read + ?Sized>(
+                reader: &mut R,
+            ) -> Result<Self, $crate::BitcoinError> {
+                Self::consensus_decode_from(&mut $crate::Decoder::new(reader))
+            }
+
+            fn consensus_decode_from<R: std::io::Read + ?Sized>(
+                decoder: &mut $crate::Decoder<'_, R>,
+            ) -> Result<Self, $crate::BitcoinError> {
+                $(
+                    let $field: $field_type = decoder.decode(stringify!($field))?;
+                )+
+                Ok($type {
+                    $($field,)+
+                    $($($skip: Default::default(),)+)?
+                })
+            }
+        }
+
+        impl $type {
+            pub fn to_bytes(&self) -> Vec<u8> {
+                $crate::codec::encode_to_vec(self)
+            }
+
+            pub fn decode(bytes: &[u8]) -> Result<$crate::Decoded<Self>, $crate::BitcoinError> {
+                $crate::codec::decode_slice(bytes)
+            }
+
+            #[deprecated(note = "use decode")]
+            pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), $crate::BitcoinError> {
+                Self::decode(bytes).map($crate::Decoded::into_parts)
+            }
+        }
+    };
+}
+
+pub(crate) use impl_consensus_codec;
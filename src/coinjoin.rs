@@ -0,0 +1,106 @@
+use crate::{Amount, BitcoinTransaction};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Heuristic CoinJoin detection. None of these are certain - a CoinJoin is
+// just a transaction and nothing on chain marks it as one - but the big
+// implementations leave very recognisable shapes:
+//
+// - Whirlpool (Samourai): exactly 5 inputs and 5 outputs, all outputs the
+//   same pool denomination
+// - Wasabi: a large number of inputs and a large set of equal outputs
+//   (0.1 BTC-ish in 1.x, a ladder of standard denominations in 2.0)
+// - JoinMarket: n equal outputs for the n participants plus up to n change
+//   outputs, and at least n inputs
+//
+// The most common output value is taken to be the mixing denomination.
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoinJoinKind {
+    Whirlpool,
+    Wasabi,
+    JoinMarket,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct CoinJoinClassification {
+    pub kind: CoinJoinKind,
+    // 0.0 - 1.0, how strongly the shape matches
+    pub confidence: f64,
+    // Value shared by the equal outputs, and how many there are
+    pub denomination: Amount,
+    pub equal_outputs: usize,
+}
+
+// Whirlpool pool sizes: 0.001, 0.01, 0.05 and 0.5 BTC
+const WHIRLPOOL_POOLS: [Amount; 4] = [
+    Amount::from_sat(100_000),
+    Amount::from_sat(1_000_000),
+    Amount::from_sat(5_000_000),
+    Amount::from_sat(50_000_000),
+];
+
+// Below this many equal outputs it's more likely JoinMarket than Wasabi
+const WASABI_MIN_EQUAL_OUTPUTS: usize = 10;
+
+pub fn classify_coinjoin(tx: &BitcoinTransaction) -> Option<CoinJoinClassification> {
+    if tx.is_coinbase() {
+        return None;
+    }
+    let (denomination, equal_outputs) = most_common_output_value(tx)?;
+    if equal_outputs < 2 {
+        return None;
+    }
+
+    let inputs = tx.inputs.len();
+    let outputs = tx.outputs.len();
+    let classify = |kind, confidence| {
+        Some(CoinJoinClassification {
+            kind,
+            confidence,
+            denomination,
+            equal_outputs,
+        })
+    };
+
+    if inputs == 5 && outputs == 5 && equal_outputs == 5 {
+        let confidence = if WHIRLPOOL_POOLS.contains(&denomination) {
+            0.95
+        } else {
+            0.6
+        };
+        return classify(CoinJoinKind::Whirlpool, confidence);
+    }
+
+    // Every participant brings at least one input
+    if inputs < equal_outputs {
+        return None;
+    }
+
+    if equal_outputs >= WASABI_MIN_EQUAL_OUTPUTS {
+        let confidence = if inputs >= 50 { 0.9 } else { 0.8 };
+        return classify(CoinJoinKind::Wasabi, confidence);
+    }
+
+    // At most one change output per participant
+    if outputs <= 2 * equal_outputs {
+        // Two equal outputs could easily be a coincidence, more are less so
+        let confidence = (0.5 + 0.05 * equal_outputs as f64).min(0.85);
+        return classify(CoinJoinKind::JoinMarket, confidence);
+    }
+
+    None
+}
+
+// (value, count) of the most frequent output value. Ties go to the larger
+// value, since mixing denominations are usually bigger than the change.
+fn most_common_output_value(tx: &BitcoinTransaction) -> Option<(Amount, usize)> {
+    let mut counts: HashMap<Amount, usize> = HashMap::new();
+    for output in &tx.outputs {
+        *counts.entry(output.value).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(value, count)| (count, value))
+}
@@ -0,0 +1,538 @@
+use crate::bip32::{DerivationPath, Xpub};
+use crate::keys::PublicKey;
+use crate::miniscript::{Miniscript, ScriptContext};
+use crate::network::Network;
+use crate::opcodes::Opcode;
+use crate::taproot::{TapTree, TaprootSpendInfo};
+use crate::{Address, BitcoinError, DerivationInfo, Script, hashes};
+use secp256k1::XOnlyPublicKey;
+use std::fmt;
+use std::str::FromStr;
+
+// Output descriptors (BIP380-386, `secp256k1` feature): a string like
+// "wpkh([d34db33f/84'/0'/0']xpub.../0/*)#cjjspncu" that says exactly which
+// scripts a wallet watches. Ranged descriptors (ending in /*) stand for one
+// script per child index, which is how a watch-only wallet enumerates its
+// receive addresses.
+//
+// Supported: pkh(), wpkh(), sh(wpkh()), wsh(multi()), wsh(sortedmulti()),
+// wsh() of any other miniscript, and tr() with an optional tree of
+// miniscript leaves. Keys are hex public keys or xpubs; private keys aren't
+// accepted.
+
+// multi() under wsh is limited by the standardness of the witness script
+const MAX_MULTISIG_KEYS: usize = 20;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Descriptor {
+    Pkh(DescriptorKey),
+    Wpkh(DescriptorKey),
+    ShWpkh(DescriptorKey),
+    WshMulti {
+        threshold: usize,
+        keys: Vec<DescriptorKey>,
+        // sortedmulti(): keys ordered by their serialization at each index
+        sorted: bool,
+    },
+    Wsh(Miniscript),
+    Tr {
+        internal_key: DescriptorKey,
+        tree: Option<DescriptorTree>,
+    },
+}
+
+// tr() script tree: {A,B} branches with tapscript miniscript leaves
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DescriptorTree {
+    Leaf(Miniscript),
+    Branch(Box<DescriptorTree>, Box<DescriptorTree>),
+}
+
+// A key expression, with the optional [fingerprint/path] origin telling
+// signers where it came from
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DescriptorKey {
+    pub origin: Option<DerivationInfo>,
+    pub source: KeySource,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum KeySource {
+    Single(PublicKey),
+    // 64-hex-character keys, only valid inside tr()
+    XOnly(XOnlyPublicKey),
+    // An xpub, the path below it, and whether the index goes on the end
+    Xpub {
+        xpub: Xpub,
+        path: DerivationPath,
+        wildcard: bool,
+    },
+}
+
+// Where a key is allowed to appear decides which encodings it may use
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Context {
+    Legacy,
+    Segwit,
+    Taproot,
+}
+
+impl Descriptor {
+    // True if the descriptor has a /* anywhere, i.e. the index matters
+    pub fn is_ranged(&self) -> bool {
+        self.keys().iter().any(|key| key.is_ranged())
+    }
+
+    // The scriptPubKey at child `index` (ignored for non-ranged descriptors)
+    pub fn script_pubkey(&self, index: u32) -> Result<Script, BitcoinError> {
+        // Any network will do: only the script is kept
+        self.address(index, Network::Mainnet)
+            .map(|address| address.script_pubkey())
+    }
+
+    pub fn address(&self, index: u32, network: Network) -> Result<Address, BitcoinError> {
+        match self {
+            Descriptor::Pkh(key) => Ok(key.derive(index)?.p2pkh(network)),
+            Descriptor::Wpkh(key) => key.derive(index)?.p2wpkh(network),
+            Descriptor::ShWpkh(key) => {
+                let redeem_script = key.derive(index)?.p2wpkh(network)?.script_pubkey();
+                Ok(Address::p2sh(hashes::hash160(&redeem_script), network))
+            }
+            Descriptor::WshMulti { .. } | Descriptor::Wsh(_) => {
+                let witness_script = self.witness_script(index)?.unwrap();
+                Ok(Address::p2wsh(hashes::sha256(&witness_script), network))
+            }
+            Descriptor::Tr { .. } => {
+                let output_key = self.taproot_spend_info(index)?.unwrap().output_key;
+                Ok(Address::p2tr(output_key, network))
+            }
+        }
+    }
+
+    // For wsh(): the script the output commits to, which the spender reveals
+    pub fn witness_script(&self, index: u32) -> Result<Option<Script>, BitcoinError> {
+        let (threshold, keys, sorted) = match self {
+            Descriptor::WshMulti {
+                threshold,
+                keys,
+                sorted,
+            } => (threshold, keys, sorted),
+            Descriptor::Wsh(miniscript) => return miniscript.to_script(index).map(Some),
+            _ => return Ok(None),
+        };
+        let mut keys = keys
+            .iter()
+            .map(|key| key.derive(index).map(|key| key.to_bytes()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if *sorted {
+            keys.sort();
+        }
+        let mut builder = Script::builder().push_int(*threshold as i64);
+        for key in &keys {
+            builder = builder.push_key(key);
+        }
+        Ok(Some(
+            builder
+                .push_int(keys.len() as i64)
+                .push_opcode(Opcode::CheckMultiSig)
+                .into_script(),
+        ))
+    }
+
+    // For tr(): output key, leaves and control blocks at `index`
+    pub fn taproot_spend_info(&self, index: u32) -> Result<Option<TaprootSpendInfo>, BitcoinError> {
+        let Descriptor::Tr { internal_key, tree } = self else {
+            return Ok(None);
+        };
+        let tree = tree.as_ref().map(|tree| tree.derive(index)).transpose()?;
+        TaprootSpendInfo::new(&internal_key.derive_x_only(index)?, tree.as_ref()).map(Some)
+    }
+
+    fn keys(&self) -> Vec<&DescriptorKey> {
+        match self {
+            Descriptor::Pkh(key) | Descriptor::Wpkh(key) | Descriptor::ShWpkh(key) => vec![key],
+            Descriptor::WshMulti { keys, .. } => keys.iter().collect(),
+            Descriptor::Wsh(miniscript) => miniscript.keys(),
+            Descriptor::Tr { internal_key, tree } => {
+                let mut keys = vec![internal_key];
+                if let Some(tree) = tree {
+                    tree.collect_keys(&mut keys);
+                }
+                keys
+            }
+        }
+    }
+
+    fn parse(s: &str) -> Result<Self, BitcoinError> {
+        let (name, args) = call(s)?;
+        match name {
+            "pkh" => Ok(Descriptor::Pkh(DescriptorKey::parse(
+                args,
+                Context::Legacy,
+            )?)),
+            "wpkh" => Ok(Descriptor::Wpkh(DescriptorKey::parse(
+                args,
+                Context::Segwit,
+            )?)),
+            "sh" => match call(args)? {
+                ("wpkh", key) => Ok(Descriptor::ShWpkh(DescriptorKey::parse(
+                    key,
+                    Context::Segwit,
+                )?)),
+                (other, _) => Err(invalid(&format!("sh({}()) is not supported", other))),
+            },
+            "wsh" => {
+                let script = args;
+                let (name, args) = call(script)?;
+                let sorted = match name {
+                    "multi" => false,
+                    "sortedmulti" => true,
+                    _ => {
+                        return Ok(Descriptor::Wsh(Miniscript::parse(
+                            script,
+                            ScriptContext::Segwitv0,
+                        )?));
+                    }
+                };
+                let args = split_args(args);
+                let threshold: usize = args[0]
+                    .parse()
+                    .map_err(|_| invalid("multisig threshold is not a number"))?;
+                let keys = args[1..]
+                    .iter()
+                    .map(|key| DescriptorKey::parse(key, Context::Segwit))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if threshold == 0 || threshold > keys.len() || keys.len() > MAX_MULTISIG_KEYS {
+                    return Err(invalid("bad multisig threshold or key count"));
+                }
+                Ok(Descriptor::WshMulti {
+                    threshold,
+                    keys,
+                    sorted,
+                })
+            }
+            "tr" => {
+                let args = split_args(args);
+                let tree = match args.as_slice() {
+                    [_] => None,
+                    [_, tree] => Some(DescriptorTree::parse(tree)?),
+                    _ => return Err(invalid("tr() takes a key and an optional tree")),
+                };
+                Ok(Descriptor::Tr {
+                    internal_key: DescriptorKey::parse(args[0], Context::Taproot)?,
+                    tree,
+                })
+            }
+            other => Err(invalid(&format!("unknown descriptor {}()", other))),
+        }
+    }
+
+    // The descriptor without its checksum
+    fn body(&self) -> String {
+        match self {
+            Descriptor::Pkh(key) => format!("pkh({})", key),
+            Descriptor::Wpkh(key) => format!("wpkh({})", key),
+            Descriptor::ShWpkh(key) => format!("sh(wpkh({}))", key),
+            Descriptor::WshMulti {
+                threshold,
+                keys,
+                sorted,
+            } => {
+                let name = if *sorted { "sortedmulti" } else { "multi" };
+                let keys: Vec<String> = keys.iter().map(ToString::to_string).collect();
+                format!("wsh({}({},{}))", name, threshold, keys.join(","))
+            }
+            Descriptor::Wsh(miniscript) => format!("wsh({})", miniscript),
+            Descriptor::Tr {
+                internal_key,
+                tree: None,
+            } => format!("tr({})", internal_key),
+            Descriptor::Tr {
+                internal_key,
+                tree: Some(tree),
+            } => format!("tr({},{})", internal_key, tree),
+        }
+    }
+}
+
+impl DescriptorTree {
+    fn parse(s: &str) -> Result<Self, BitcoinError> {
+        if let Some(inner) = s.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            return match split_args(inner).as_slice() {
+                [left, right] => Ok(DescriptorTree::Branch(
+                    Box::new(Self::parse(left)?),
+                    Box::new(Self::parse(right)?),
+                )),
+                _ => Err(invalid("a tree branch needs exactly two children")),
+            };
+        }
+        Ok(DescriptorTree::Leaf(Miniscript::parse(
+            s,
+            ScriptContext::Tapscript,
+        )?))
+    }
+
+    // A pk(KEY) leaf becomes <x-only key> OP_CHECKSIG
+    fn derive(&self, index: u32) -> Result<TapTree, BitcoinError> {
+        Ok(match self {
+            DescriptorTree::Leaf(miniscript) => TapTree::leaf(miniscript.to_script(index)?),
+            DescriptorTree::Branch(left, right) => {
+                TapTree::branch(left.derive(index)?, right.derive(index)?)
+            }
+        })
+    }
+
+    fn collect_keys<'a>(&'a self, keys: &mut Vec<&'a DescriptorKey>) {
+        match self {
+            DescriptorTree::Leaf(miniscript) => keys.extend(miniscript.keys()),
+            DescriptorTree::Branch(left, right) => {
+                left.collect_keys(keys);
+                right.collect_keys(keys);
+            }
+        }
+    }
+}
+
+impl DescriptorKey {
+    pub fn is_ranged(&self) -> bool {
+        matches!(self.source, KeySource::Xpub { wildcard: true, .. })
+    }
+
+    // The concrete key at child `index`. Fails for x-only keys, which have
+    // no full public key form.
+    pub fn derive(&self, index: u32) -> Result<PublicKey, BitcoinError> {
+        match &self.source {
+            KeySource::Single(key) => Ok(*key),
+            KeySource::XOnly(_) => Err(invalid("x-only keys are only valid in tr()")),
+            KeySource::Xpub {
+                xpub,
+                path,
+                wildcard,
+            } => {
+                let mut key = xpub.derive_path(path)?;
+                if *wildcard {
+                    key = key.derive_child(index)?;
+                }
+                Ok(key.to_public_key())
+            }
+        }
+    }
+
+    pub fn derive_x_only(&self, index: u32) -> Result<XOnlyPublicKey, BitcoinError> {
+        match &self.source {
+            KeySource::XOnly(key) => Ok(*key),
+            _ => Ok(self.derive(index)?.inner.x_only_public_key().0),
+        }
+    }
+
+    pub(crate) fn parse(s: &str, context: Context) -> Result<Self, BitcoinError> {
+        // [fingerprint/path]
+        let (origin, key) = match s.strip_prefix('[') {
+            Some(rest) => {
+                let (origin, key) = rest
+                    .split_once(']')
+                    .ok_or_else(|| invalid("unclosed key origin"))?;
+                let (fingerprint, path) = origin.split_once('/').unwrap_or((origin, ""));
+                let fingerprint: [u8; 4] = hex::decode(fingerprint)
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .ok_or_else(|| invalid("origin fingerprint must be 8 hex characters"))?;
+                let path: DerivationPath = if path.is_empty() {
+                    DerivationPath::master()
+                } else {
+                    path.parse()?
+                };
+                (Some(DerivationInfo::new(fingerprint, path.into())), key)
+            }
+            None => (None, s),
+        };
+
+        let source = if key.starts_with("xpub") || key.starts_with("tpub") {
+            let mut steps = key.split('/');
+            let xpub: Xpub = steps.next().unwrap().parse()?;
+            let mut steps: Vec<&str> = steps.collect();
+            let wildcard = match steps.last() {
+                Some(&"*") => {
+                    steps.pop();
+                    true
+                }
+                Some(step) if step.starts_with('*') => {
+                    return Err(invalid("hardened wildcards need a private key"));
+                }
+                _ => false,
+            };
+            let path = if steps.is_empty() {
+                DerivationPath::master()
+            } else {
+                steps.join("/").parse()?
+            };
+            KeySource::Xpub {
+                xpub,
+                path,
+                wildcard,
+            }
+        } else {
+            let bytes = hex::decode(key).map_err(|_| invalid("unrecognised key"))?;
+            match (bytes.len(), context) {
+                (32, Context::Taproot) => KeySource::XOnly(
+                    XOnlyPublicKey::from_slice(&bytes).map_err(|_| BitcoinError::InvalidKey)?,
+                ),
+                (65, Context::Segwit | Context::Taproot) => {
+                    return Err(invalid("uncompressed keys aren't allowed in segwit"));
+                }
+                _ => KeySource::Single(PublicKey::from_slice(&bytes)?),
+            }
+        };
+        Ok(DescriptorKey { origin, source })
+    }
+}
+
+impl fmt::Display for DescriptorKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(origin) = &self.origin {
+            // DerivationPath prints as "m/..."; origins leave off the m
+            let path = DerivationPath(origin.path.clone()).to_string();
+            write!(f, "[{}{}]", hex::encode(origin.fingerprint), &path[1..])?;
+        }
+        match &self.source {
+            KeySource::Single(key) => write!(f, "{}", key),
+            KeySource::XOnly(key) => write!(f, "{}", key),
+            KeySource::Xpub {
+                xpub,
+                path,
+                wildcard,
+            } => {
+                write!(f, "{}{}", xpub, &path.to_string()[1..])?;
+                if *wildcard {
+                    write!(f, "/*")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for DescriptorTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DescriptorTree::Leaf(miniscript) => write!(f, "{}", miniscript),
+            DescriptorTree::Branch(left, right) => write!(f, "{{{},{}}}", left, right),
+        }
+    }
+}
+
+// Always printed with its checksum
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let body = self.body();
+        let checksum = checksum(&body).expect("we only print valid characters");
+        write!(f, "{}#{}", body, checksum)
+    }
+}
+
+// The checksum is optional, but checked if present
+impl FromStr for Descriptor {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let body = match s.split_once('#') {
+            Some((body, expected)) => {
+                if checksum(body)? != expected {
+                    return Err(invalid("checksum mismatch"));
+                }
+                body
+            }
+            None => s,
+        };
+        Descriptor::parse(body)
+    }
+}
+
+// BIP380 descriptor checksum: a BCH code over the descriptor's characters,
+// like bech32's but over 5-bit symbols derived from a 95-character set
+pub fn checksum(descriptor: &str) -> Result<String, BitcoinError> {
+    const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+    const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+    const GENERATOR: [u64; 5] = [
+        0xf5dee51989,
+        0xa9fdca3312,
+        0x1bab10e32d,
+        0x3706b1677a,
+        0x644d626ffd,
+    ];
+
+    let polymod = |chk: u64, value: u64| {
+        let top = chk >> 35;
+        let mut chk = ((chk & 0x7ffffffff) << 5) ^ value;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+        chk
+    };
+
+    // Each character contributes its low 5 bits, and every three characters
+    // their high bits are packed into one extra symbol
+    let mut chk = 1u64;
+    let mut groups = Vec::with_capacity(3);
+    for c in descriptor.chars() {
+        let value = INPUT_CHARSET
+            .find(c)
+            .ok_or_else(|| invalid(&format!("invalid character {:?}", c)))?
+            as u64;
+        chk = polymod(chk, value & 31);
+        groups.push(value >> 5);
+        if groups.len() == 3 {
+            chk = polymod(chk, groups[0] * 9 + groups[1] * 3 + groups[2]);
+            groups.clear();
+        }
+    }
+    match groups.as_slice() {
+        [a] => chk = polymod(chk, *a),
+        [a, b] => chk = polymod(chk, a * 3 + b),
+        _ => {}
+    }
+    for _ in 0..8 {
+        chk = polymod(chk, 0);
+    }
+    chk ^= 1;
+    Ok((0..8)
+        .map(|i| CHECKSUM_CHARSET[((chk >> (5 * (7 - i))) & 31) as usize] as char)
+        .collect())
+}
+
+fn invalid(reason: &str) -> BitcoinError {
+    BitcoinError::InvalidDescriptor(reason.into())
+}
+
+// "name(args)" -> ("name", "args")
+pub(crate) fn call(s: &str) -> Result<(&str, &str), BitcoinError> {
+    let (name, rest) = s
+        .split_once('(')
+        .ok_or_else(|| invalid(&format!("expected a function call, got {:?}", s)))?;
+    let args = rest
+        .strip_suffix(')')
+        .ok_or_else(|| invalid("missing closing parenthesis"))?;
+    Ok((name, args))
+}
+
+// Splits on the commas that aren't nested inside (), {} or []
+pub(crate) fn split_args(s: &str) -> Vec<&str> {
+    let mut args = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' | '{' | '[' => depth += 1,
+            ')' | '}' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(&s[start..]);
+    args
+}
@@ -0,0 +1,282 @@
+use crate::hashes::siphash::siphash24;
+use crate::{BitcoinError, BitcoinTransaction, CompactSize, OutPoint, Script};
+
+// Golomb-coded set, the compact probabilistic filter from BIP158. Each item
+// is hashed into 0..N*M, the hashes are sorted, and the gaps between them
+// are Golomb-Rice coded with P bits of remainder. With BIP158's parameters
+// that's about 20 bits per item and a 1/784931 false-positive rate.
+//
+// For a wallet: put its outpoints and scriptPubKeys in a filter once, then
+// during a rescan check each transaction against the filter first and only
+// do the full comparison on the rare match.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct GcsParams {
+    // SipHash key; BIP158 takes it from the block hash, a wallet can pick
+    // anything as long as it keeps using the same one
+    pub k0: u64,
+    pub k1: u64,
+    pub p: u8,
+    pub m: u64,
+}
+
+impl GcsParams {
+    pub fn bip158(k0: u64, k1: u64) -> Self {
+        GcsParams {
+            k0,
+            k1,
+            p: 19,
+            m: 784_931,
+        }
+    }
+
+    // The fields are public, so anything that codes with them checks first:
+    // a shift by 64 or more bits panics, and n * m has to fit in a u64
+    fn range(&self, n: u64) -> Result<u64, BitcoinError> {
+        let invalid = |reason: String| BitcoinError::InvalidGcsFilter(reason);
+        if !(1..=32).contains(&self.p) {
+            return Err(invalid(format!("P must be 1 to 32, not {}", self.p)));
+        }
+        if self.m == 0 {
+            return Err(invalid("M must not be zero".into()));
+        }
+        n.checked_mul(self.m)
+            .ok_or_else(|| invalid(format!("{} items overflow the hash range", n)))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GcsFilter {
+    params: GcsParams,
+    n: u64,
+    range: u64, // n * m, checked when the filter is made
+    data: Vec<u8>,
+}
+
+impl GcsFilter {
+    pub fn build<I, T>(params: GcsParams, items: I) -> Result<Self, BitcoinError>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        // Duplicate items are dropped before counting, as in BIP158
+        let mut items: Vec<Vec<u8>> = items.into_iter().map(|i| i.as_ref().to_vec()).collect();
+        items.sort_unstable();
+        items.dedup();
+        let n = items.len() as u64;
+        let range = params.range(n)?;
+        let mut hashes: Vec<u64> = items
+            .iter()
+            .map(|item| hash_to_range(&params, range, item))
+            .collect();
+        hashes.sort_unstable();
+
+        let mut writer = BitWriter::default();
+        let mut last = 0;
+        for hash in hashes {
+            golomb_encode(&mut writer, hash - last, params.p);
+            last = hash;
+        }
+        Ok(GcsFilter {
+            params,
+            n,
+            range,
+            data: writer.finish(),
+        })
+    }
+
+    // Outpoints as they're serialized on the wire, and raw script bytes
+    pub fn for_wallet(
+        params: GcsParams,
+        outpoints: &[OutPoint],
+        scripts: &[Script],
+    ) -> Result<Self, BitcoinError> {
+        let items = outpoints
+            .iter()
+            .map(OutPoint::to_bytes)
+            .chain(scripts.iter().map(|s| s.bytes.clone()));
+        Self::build(params, items)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    pub fn params(&self) -> &GcsParams {
+        &self.params
+    }
+
+    pub fn contains(&self, item: &[u8]) -> bool {
+        self.contains_any([item])
+    }
+
+    // Hash the queries, sort them, and walk both sorted lists together, so
+    // checking many items costs one pass over the filter
+    pub fn contains_any<I, T>(&self, items: I) -> bool
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        if self.n == 0 {
+            return false;
+        }
+        let mut queries: Vec<u64> = items
+            .into_iter()
+            .map(|item| hash_to_range(&self.params, self.range, item.as_ref()))
+            .collect();
+        if queries.is_empty() {
+            return false;
+        }
+        queries.sort_unstable();
+
+        let mut reader = BitReader::new(&self.data);
+        let mut value = 0u64;
+        let mut queries = queries.into_iter().peekable();
+        while let Some(delta) = golomb_decode(&mut reader, self.params.p) {
+            // Past the end of the range nothing more can match
+            let Some(next) = value.checked_add(delta) else {
+                return false;
+            };
+            value = next;
+            while let Some(&query) = queries.peek() {
+                if query < value {
+                    queries.next();
+                } else {
+                    break;
+                }
+            }
+            match queries.peek() {
+                Some(&query) if query == value => return true,
+                Some(_) => {}
+                None => return false,
+            }
+        }
+        false
+    }
+
+    // Does this transaction spend one of our outpoints or pay one of our
+    // scripts? False positives are possible, false negatives are not.
+    pub fn matches_transaction(&self, tx: &BitcoinTransaction) -> bool {
+        let items = tx
+            .inputs
+            .iter()
+            .map(|input| input.previous_output.to_bytes())
+            .chain(tx.outputs.iter().map(|o| o.script_pubkey.bytes.clone()));
+        self.contains_any(items)
+    }
+
+    // BIP158 layout: CompactSize item count, then the coded bits. The
+    // parameters aren't included, the reader has to know them.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = CompactSize::new(self.n).to_bytes();
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8], params: GcsParams) -> Result<Self, BitcoinError> {
+        let (n, offset) = CompactSize::decode(bytes)?.into_parts();
+        let n = n.value;
+        let range = params.range(n)?;
+        // Every item takes at least P + 1 bits, so a count the data can't
+        // hold is corrupt
+        let data = &bytes[offset..];
+        let min_bits = n.checked_mul(params.p as u64 + 1);
+        if min_bits.is_none_or(|bits| bits > data.len() as u64 * 8) {
+            return Err(BitcoinError::InvalidGcsFilter(format!(
+                "{} bytes can't hold {} items",
+                data.len(),
+                n
+            )));
+        }
+        Ok(GcsFilter {
+            params,
+            n,
+            range,
+            data: data.to_vec(),
+        })
+    }
+}
+
+// Map an item uniformly onto 0..n*m with a multiply-and-shift instead of a
+// modulo, as BIP158 specifies
+fn hash_to_range(params: &GcsParams, range: u64, item: &[u8]) -> u64 {
+    let hash = siphash24(params.k0, params.k1, item);
+    ((hash as u128 * range as u128) >> 64) as u64
+}
+
+// Quotient in unary (q ones then a zero), then the low p bits
+fn golomb_encode(writer: &mut BitWriter, value: u64, p: u8) {
+    for _ in 0..(value >> p) {
+        writer.write_bit(true);
+    }
+    writer.write_bit(false);
+    writer.write_bits(value, p);
+}
+
+fn golomb_decode(reader: &mut BitReader, p: u8) -> Option<u64> {
+    let mut quotient = 0u64;
+    while reader.read_bit()? {
+        quotient += 1;
+    }
+    let remainder = reader.read_bits(p)?;
+    // A corrupt run of ones could push the quotient past u64
+    Some(quotient.checked_mul(1 << p)? | remainder)
+}
+
+// Bits are packed most-significant first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    used: u8, // bits used in the last byte
+}
+
+impl BitWriter {
+    fn write_bit(&mut self, bit: bool) {
+        if self.used == 0 {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().unwrap() |= 0x80 >> self.used;
+        }
+        self.used = (self.used + 1) % 8;
+    }
+
+    fn write_bits(&mut self, value: u64, count: u8) {
+        for i in (0..count).rev() {
+            self.write_bit((value >> i) & 1 == 1);
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize, // in bits
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, pos: 0 }
+    }
+
+    fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.bytes.get(self.pos / 8)?;
+        let bit = (byte >> (7 - self.pos % 8)) & 1 == 1;
+        self.pos += 1;
+        Some(bit)
+    }
+
+    fn read_bits(&mut self, count: u8) -> Option<u64> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | self.read_bit()? as u64;
+        }
+        Some(value)
+    }
+}
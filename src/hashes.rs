@@ -0,0 +1,92 @@
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256, Sha512};
+
+pub mod siphash;
+
+// Bitcoin almost never uses a single SHA-256. Txids, block hashes and merkle
+// nodes are all SHA-256 applied twice ("SHA256d").
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+pub fn sha256d(data: &[u8]) -> [u8; 32] {
+    sha256(&sha256(data))
+}
+
+// BIP340 tagged hash: SHA256(SHA256(tag) || SHA256(tag) || data). The tag
+// keeps hashes made for one purpose (signatures, tweaks, sighashes, ...)
+// from ever colliding with another's.
+pub fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag = sha256(tag.as_bytes());
+    Sha256::new()
+        .chain_update(tag)
+        .chain_update(tag)
+        .chain_update(data)
+        .finalize()
+        .into()
+}
+
+// HMAC (RFC 2104) over SHA-512, used by BIP32 key derivation and BIP39
+// seed stretching. Keys longer than a block are hashed first; shorter ones
+// are zero-padded.
+pub fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
+    const BLOCK_SIZE: usize = 128;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..64].copy_from_slice(&Sha512::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let pad = |byte: u8| block.map(|b| b ^ byte);
+    let inner = Sha512::new()
+        .chain_update(pad(0x36))
+        .chain_update(data)
+        .finalize();
+    Sha512::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+// RIPEMD160(SHA256(x)): the 20-byte hash inside P2PKH, P2SH and P2WPKH
+// scripts
+pub fn hash160(data: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(sha256(data)).into()
+}
+
+// MurmurHash3 (x86, 32-bit). Not cryptographic - it's what BIP37 bloom
+// filters and Core's rolling bloom filter use to spread items over bits,
+// with the seed picking one of several independent hash functions.
+pub fn murmur3_32(seed: u32, data: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e2d51;
+    const C2: u32 = 0x1b873593;
+
+    let mix = |k: u32| k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+
+    let mut h = seed;
+    let mut blocks = data.chunks_exact(4);
+    for block in &mut blocks {
+        h ^= mix(u32::from_le_bytes(block.try_into().unwrap()));
+        h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        let mut k = 0u32;
+        for (i, &byte) in tail.iter().enumerate() {
+            k ^= (byte as u32) << (8 * i);
+        }
+        h ^= mix(k);
+    }
+
+    // Finalization mix forces all bits to avalanche
+    h ^= data.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85ebca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2ae35);
+    h ^= h >> 16;
+    h
+}
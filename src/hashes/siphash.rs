@@ -0,0 +1,98 @@
+use std::hash::Hasher;
+
+// SipHash-2-4, the keyed hash Core uses wherever an attacker shouldn't be
+// able to predict hash values: BIP152 compact block short IDs, and the
+// salted hash maps for its UTXO cache and mempool. Keys are two u64s, read
+// little-endian from whatever secret they come from.
+//
+// Fed incrementally through `write`, or all at once with `siphash24`.
+#[derive(Debug, Clone)]
+pub struct SipHasher24 {
+    v: [u64; 4],
+    tail: u64,     // bytes not yet making up a full 8-byte word
+    length: usize, // total bytes written
+}
+
+impl SipHasher24 {
+    pub fn new(k0: u64, k1: u64) -> Self {
+        SipHasher24 {
+            v: [
+                0x736f6d6570736575 ^ k0,
+                0x646f72616e646f6d ^ k1,
+                0x6c7967656e657261 ^ k0,
+                0x7465646279746573 ^ k1,
+            ],
+            tail: 0,
+            length: 0,
+        }
+    }
+
+    fn round(v: &mut [u64; 4]) {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13);
+        v[1] ^= v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16);
+        v[3] ^= v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21);
+        v[3] ^= v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17);
+        v[1] ^= v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+
+    fn compress(&mut self, word: u64) {
+        self.v[3] ^= word;
+        Self::round(&mut self.v);
+        Self::round(&mut self.v);
+        self.v[0] ^= word;
+    }
+
+    pub fn finalize(&self) -> u64 {
+        let mut v = self.v;
+        let last = self.tail | ((self.length as u64 & 0xff) << 56);
+        v[3] ^= last;
+        Self::round(&mut v);
+        Self::round(&mut v);
+        v[0] ^= last;
+        v[2] ^= 0xff;
+        for _ in 0..4 {
+            Self::round(&mut v);
+        }
+        v[0] ^ v[1] ^ v[2] ^ v[3]
+    }
+}
+
+impl Hasher for SipHasher24 {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.tail |= (byte as u64) << (8 * (self.length % 8));
+            self.length += 1;
+            if self.length.is_multiple_of(8) {
+                let word = self.tail;
+                self.tail = 0;
+                self.compress(word);
+            }
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.finalize()
+    }
+}
+
+pub fn siphash24(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    let mut hasher = SipHasher24::new(k0, k1);
+    hasher.write(data);
+    hasher.finalize()
+}
+
+// BIP152: a transaction's short ID in a compact block is the SipHash of its
+// wtxid (txid before segwit peers), truncated to 6 bytes. k0/k1 come from
+// SHA256(block header || nonce).
+pub fn short_id(k0: u64, k1: u64, wtxid: &[u8; 32]) -> u64 {
+    siphash24(k0, k1, wtxid) & 0x0000_ffff_ffff_ffff
+}
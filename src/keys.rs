@@ -0,0 +1,181 @@
+use crate::network::Network;
+use crate::{Address, BitcoinError, base58, hashes, sign};
+use std::fmt;
+use std::str::FromStr;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+// Keys as Bitcoin uses them (`secp256k1` feature). libsecp256k1's types are
+// just curve points and scalars; on top of that Bitcoin tracks whether the
+// public key is serialized compressed (33 bytes) or uncompressed (65), which
+// changes its hash160 and so its address. Private keys carry the same flag
+// plus the network, since WIF encodes both.
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct PublicKey {
+    pub inner: secp256k1::PublicKey,
+    pub compressed: bool,
+}
+
+impl PublicKey {
+    pub fn new(inner: secp256k1::PublicKey) -> Self {
+        PublicKey {
+            inner,
+            compressed: true,
+        }
+    }
+
+    pub fn new_uncompressed(inner: secp256k1::PublicKey) -> Self {
+        PublicKey {
+            inner,
+            compressed: false,
+        }
+    }
+
+    // SEC1 encoding: 02/03 + x for compressed, 04 + x + y for uncompressed.
+    // The point must be on the curve.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        let compressed = match bytes.len() {
+            33 => true,
+            65 => false,
+            _ => return Err(BitcoinError::InvalidKey),
+        };
+        let inner =
+            secp256k1::PublicKey::from_slice(bytes).map_err(|_| BitcoinError::InvalidKey)?;
+        Ok(PublicKey { inner, compressed })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.compressed {
+            self.inner.serialize().to_vec()
+        } else {
+            self.inner.serialize_uncompressed().to_vec()
+        }
+    }
+
+    // What P2PKH and P2WPKH scripts commit to
+    pub fn pubkey_hash(&self) -> [u8; 20] {
+        hashes::hash160(&self.to_bytes())
+    }
+
+    pub fn p2pkh(&self, network: Network) -> Address {
+        Address::p2pkh(self.pubkey_hash(), network)
+    }
+
+    // Segwit only allows compressed keys (BIP143 policy), so an uncompressed
+    // one would produce an unspendable output
+    pub fn p2wpkh(&self, network: Network) -> Result<Address, BitcoinError> {
+        if !self.compressed {
+            return Err(BitcoinError::InvalidKey);
+        }
+        Ok(Address::p2wpkh(self.pubkey_hash(), network))
+    }
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.to_bytes()))
+    }
+}
+
+impl FromStr for PublicKey {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PublicKey::from_slice(&hex::decode(s)?)
+    }
+}
+
+// No Display on purpose: printing a private key should take an explicit
+// `to_wif()`. (SecretKey's own Debug doesn't print the secret either.)
+// Equality is constant-time and the secret is overwritten on drop. SecretKey
+// itself is Copy, so `inner` is private: borrow it with `secret_key()`
+// rather than copying it somewhere that's never erased.
+#[derive(Debug, Clone)]
+pub struct PrivateKey {
+    inner: secp256k1::SecretKey,
+    pub compressed: bool,
+    pub network: Network,
+}
+
+impl PrivateKey {
+    pub fn new(inner: secp256k1::SecretKey, network: Network) -> Self {
+        PrivateKey {
+            inner,
+            compressed: true,
+            network,
+        }
+    }
+
+    pub fn secret_key(&self) -> &secp256k1::SecretKey {
+        &self.inner
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            inner: secp256k1::PublicKey::from_secret_key(sign::secp(), &self.inner),
+            compressed: self.compressed,
+        }
+    }
+
+    // Base58check of: network byte, 32-byte key, then 0x01 if the public key
+    // is to be compressed
+    pub fn to_wif(&self) -> String {
+        let mut payload = Zeroizing::new(vec![self.network.wif_prefix()]);
+        payload.extend(self.inner.secret_bytes());
+        if self.compressed {
+            payload.push(0x01);
+        }
+        base58::encode_check_zeroizing(&payload)
+    }
+
+    // Every test network shares one WIF prefix, so those keys come back as
+    // Testnet
+    pub fn from_wif(s: &str) -> Result<Self, BitcoinError> {
+        let payload = base58::decode_check_zeroizing(s)?;
+        let compressed = match payload.len() {
+            33 => false,
+            34 if payload[33] == 0x01 => true,
+            34 => return Err(BitcoinError::InvalidWif("bad compression flag".into())),
+            _ => return Err(BitcoinError::InvalidWif("invalid payload length".into())),
+        };
+        let network = match payload[0] {
+            0x80 => Network::Mainnet,
+            0xef => Network::Testnet,
+            _ => return Err(BitcoinError::InvalidWif("unknown version byte".into())),
+        };
+        let inner = secp256k1::SecretKey::from_slice(&payload[1..33])
+            .map_err(|_| BitcoinError::InvalidKey)?;
+        Ok(PrivateKey {
+            inner,
+            compressed,
+            network,
+        })
+    }
+}
+
+impl PartialEq for PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        let ours = Zeroizing::new(self.inner.secret_bytes());
+        let theirs = Zeroizing::new(other.inner.secret_bytes());
+        bool::from(ours.ct_eq(&*theirs))
+            & (self.compressed == other.compressed)
+            & (self.network == other.network)
+    }
+}
+
+impl Eq for PrivateKey {}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.inner.non_secure_erase();
+    }
+}
+
+impl FromStr for PrivateKey {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PrivateKey::from_wif(s)
+    }
+}
@@ -1,16 +1,435 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
-use std::ops::Deref;
+use std::io;
+use std::ops::{Deref, Index};
+use std::str::FromStr;
+
+pub mod address;
+pub mod amount;
+mod base58;
+pub mod bech32;
+#[cfg(feature = "secp256k1")]
+pub mod bip32;
+#[cfg(feature = "bip39")]
+pub mod bip39;
+pub mod bloom;
+pub mod cluster;
+mod codec;
+pub mod coinjoin;
+#[cfg(feature = "secp256k1")]
+pub mod descriptor;
+pub mod gcs;
+pub mod hashes;
+#[cfg(feature = "secp256k1")]
+pub mod keys;
+pub mod locktime;
+pub mod malleability;
+pub mod merkle;
+#[cfg(feature = "secp256k1")]
+pub mod miniscript;
+pub mod network;
+pub mod op_return;
+pub mod opcodes;
+pub mod script;
+pub mod sighash;
+#[cfg(feature = "secp256k1")]
+pub mod sign;
+pub mod signature;
+pub mod taproot;
+mod trace;
+pub mod utxo;
+pub mod weight;
+
+pub use address::{Address, AddressPayload};
+pub use amount::Amount;
+#[cfg(feature = "secp256k1")]
+pub use bip32::{DerivationPath, Xpriv, Xpub};
+#[cfg(feature = "bip39")]
+pub use bip39::Mnemonic;
+pub use bloom::{RollingBloomFilter, SeenTracker};
+pub use cluster::AddressClusters;
+use codec::impl_consensus_codec;
+pub use codec::{
+    BudgetResource, ConsensusDecodable, ConsensusEncodable, DecodeBudget, DecodeLimits, Decoder,
+    PathSegment,
+};
+pub use coinjoin::{CoinJoinClassification, CoinJoinKind, classify_coinjoin};
+#[cfg(feature = "secp256k1")]
+pub use descriptor::{Descriptor, DescriptorKey};
+pub use gcs::{GcsFilter, GcsParams};
+#[cfg(feature = "secp256k1")]
+pub use keys::{PrivateKey, PublicKey};
+pub use locktime::{LockTime, LockTimeInterpretation, Sequence, SequenceInterpretation};
+pub use malleability::{
+    MalleabilityIssue, MalleabilityReport, MalleabilityVector, analyze_malleability,
+};
+pub use merkle::{MerkleBranch, merkle_root};
+#[cfg(feature = "secp256k1")]
+pub use miniscript::{Miniscript, Policy, ScriptContext};
+pub use network::Network;
+pub use op_return::{OpReturnData, classify_op_return};
+pub use opcodes::Opcode;
+pub use script::{
+    Instruction, Instructions, MAX_SCRIPT_ELEMENT_SIZE, MAX_SCRIPT_SIZE, ScriptBuilder, ScriptType,
+};
+#[cfg(feature = "secp256k1")]
+pub use secp256k1;
+pub use sighash::{SighashCache, SighashType};
+#[cfg(feature = "secp256k1")]
+pub use sign::{
+    sign_input_ecdsa, sign_input_schnorr, taproot_output_key, verify_input_ecdsa,
+    verify_input_schnorr,
+};
+pub use signature::{Signature, SignatureComponent, SignatureError};
+pub use taproot::{ControlBlock, TapTree, TaprootSpendInfo};
+pub use utxo::{DerivationInfo, Utxo, WeightedUtxo};
+pub use weight::{FeeRate, Weight};
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct CompactSize {
     pub value: u64,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum BitcoinError {
-    InsufficientBytes,
+    InsufficientBytes, // ran out of input, but the reader couldn't say by how much
     InvalidFormat,
+    Io(io::ErrorKind), // a reader/writer failed for reasons other than running out of data
+    // Ran out of input with a known shortfall: `needed` bytes for the item
+    // being read, only `available` left
+    UnexpectedEof {
+        needed: usize,
+        available: usize,
+    },
+    // A CompactSize used a longer encoding than its value needs
+    NonMinimalVarInt,
+    // A segwit marker followed by a flag byte other than 0x01 (Core's
+    // "Unknown transaction optional data")
+    UnknownSegwitFlag(u8),
+    // Input that should hold exactly one item had bytes left over after it
+    TrailingBytes(usize),
+    // A length prefix asked for more than we're willing (or able) to allocate
+    OversizedAllocation {
+        requested: u64,
+        limit: u64,
+    },
+    InvalidHex(hex::FromHexError),
+    InvalidChecksum,
+    InvalidCharacter(char),
+    // Well-formed encoding, but not a valid address (bad length, version, ...)
+    InvalidAddress(String),
+    // A private key string that isn't valid WIF
+    InvalidWif(String),
+    // A BIP32 xpub/xprv string that doesn't decode
+    InvalidExtendedKey(String),
+    // A "m/84'/0'/0'" style path that doesn't parse
+    InvalidDerivationPath(String),
+    // Hardened children need the private key; this index can't come from
+    // an xpub
+    HardenedDerivationFromXpub(u32),
+    // A BIP39 phrase with the wrong word count, an unknown word or a bad
+    // checksum
+    InvalidMnemonic(String),
+    // An output descriptor that doesn't parse, uses something we don't
+    // support, or fails its checksum
+    InvalidDescriptor(String),
+    // A miniscript with an unknown fragment, bad arguments, or fragments
+    // whose types don't fit together
+    InvalidMiniscript(String),
+    // Golomb-coded set parameters out of range, or filter bytes that can't
+    // hold the item count they claim
+    InvalidGcsFilter(String),
+    // A token in script ASM that isn't an opcode name, number or hex
+    InvalidAsm {
+        token: String,
+    },
+    // A push opcode at `offset` in a script wants more data than is left
+    MalformedPush {
+        offset: usize,
+        needed: usize,
+        available: usize,
+    },
+    // A script longer than the 10,000 bytes the interpreter will run
+    ScriptTooLarge {
+        len: usize,
+        limit: usize,
+    },
+    // A push at `offset` in a script bigger than the 520-byte stack element limit
+    OversizedPush {
+        offset: usize,
+        len: usize,
+        limit: usize,
+    },
+    // A negative, non-finite or over-MAX_MONEY bitcoin amount
+    InvalidAmount(String),
+    // Asked to sign or check an input the transaction doesn't have
+    InputIndexOutOfRange {
+        index: usize,
+        inputs: usize,
+    },
+    // The input being signed doesn't spend the output it was given
+    PrevoutMismatch {
+        index: usize,
+    },
+    // A signature hash needs one spent output per input
+    PrevoutCount {
+        expected: usize,
+        got: usize,
+    },
+    // A sighash type that isn't defined for this kind of signature, or
+    // SIGHASH_SINGLE without a matching output under taproot
+    InvalidSighashType(u32),
+    // Signing or verifying isn't supported for this kind of output
+    UnsupportedScript(ScriptType),
+    // A signature that doesn't parse or doesn't verify
+    InvalidSignature,
+    // A key that doesn't parse, or doesn't match the output's key hash
+    InvalidKey,
+    // A signature that isn't strict DER + sighash byte (or, where policy is
+    // being checked, isn't low-S)
+    InvalidSignatureEncoding(SignatureError),
+    // Decoding an untrusted message used up its DecodeBudget
+    BudgetExceeded {
+        resource: BudgetResource,
+        limit: u64,
+    },
+    // A taproot script tree with a leaf deeper than the 128 levels a
+    // control block can prove
+    TaprootTreeTooDeep {
+        depth: usize,
+    },
+    // Where a nested decode went wrong: a path like "inputs[0].script_sig",
+    // the absolute byte offset that field started at, and the underlying error
+    AtField {
+        field: String,
+        offset: usize,
+        source: Box<BitcoinError>,
+    },
+}
+
+impl BitcoinError {
+    // The error with any field/offset wrapping peeled off
+    pub fn root_cause(&self) -> &BitcoinError {
+        match self {
+            BitcoinError::AtField { source, .. } => source.root_cause(),
+            other => other,
+        }
+    }
+
+    // Used by the Decoder as an error bubbles up through each enclosing
+    // field. The innermost offset is kept since it's the most precise one;
+    // outer fields just get prepended to the path.
+    pub(crate) fn in_field(self, segment: PathSegment<'_>, start: usize) -> BitcoinError {
+        match self {
+            BitcoinError::AtField {
+                field,
+                offset,
+                source,
+            } => {
+                let field = if field.starts_with('[') {
+                    format!("{}{}", segment, field)
+                } else {
+                    format!("{}.{}", segment, field)
+                };
+                BitcoinError::AtField {
+                    field,
+                    offset,
+                    source,
+                }
+            }
+            other => BitcoinError::AtField {
+                field: segment.to_string(),
+                offset: start,
+                source: Box::new(other),
+            },
+        }
+    }
+}
+
+// FromHexError only derives PartialEq, but its equality is a plain
+// structural one, so BitcoinError can still promise Eq.
+impl Eq for BitcoinError {}
+
+impl fmt::Display for BitcoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BitcoinError::InsufficientBytes => write!(f, "not enough bytes to decode"),
+            BitcoinError::InvalidFormat => write!(f, "invalid format"),
+            BitcoinError::Io(kind) => write!(f, "I/O error: {}", kind),
+            BitcoinError::AtField { field, offset, .. } => {
+                write!(f, "failed to decode {} at byte {}", field, offset)
+            }
+            BitcoinError::UnexpectedEof { needed, available } => write!(
+                f,
+                "unexpected end of input: needed {} bytes, {} available",
+                needed, available
+            ),
+            BitcoinError::NonMinimalVarInt => write!(f, "non-minimal CompactSize encoding"),
+            BitcoinError::UnknownSegwitFlag(flag) => {
+                write!(f, "unknown segwit flag 0x{:02x}", flag)
+            }
+            BitcoinError::TrailingBytes(count) => {
+                write!(f, "{} trailing bytes after the data", count)
+            }
+            BitcoinError::OversizedAllocation { requested, limit } => {
+                write!(f, "length {} exceeds the limit of {}", requested, limit)
+            }
+            BitcoinError::InvalidHex(_) => write!(f, "invalid hex"),
+            BitcoinError::InvalidChecksum => write!(f, "invalid checksum"),
+            BitcoinError::InvalidCharacter(c) => write!(f, "invalid character {:?}", c),
+            BitcoinError::InvalidAddress(reason) => write!(f, "invalid address: {}", reason),
+            BitcoinError::InvalidWif(reason) => write!(f, "invalid WIF: {}", reason),
+            BitcoinError::InvalidExtendedKey(reason) => {
+                write!(f, "invalid extended key: {}", reason)
+            }
+            BitcoinError::InvalidDerivationPath(reason) => {
+                write!(f, "invalid derivation path: {}", reason)
+            }
+            BitcoinError::InvalidMnemonic(reason) => write!(f, "invalid mnemonic: {}", reason),
+            BitcoinError::InvalidDescriptor(reason) => {
+                write!(f, "invalid descriptor: {}", reason)
+            }
+            BitcoinError::InvalidMiniscript(reason) => {
+                write!(f, "invalid miniscript: {}", reason)
+            }
+            BitcoinError::InvalidGcsFilter(reason) => {
+                write!(f, "invalid GCS filter: {}", reason)
+            }
+            BitcoinError::HardenedDerivationFromXpub(index) => write!(
+                f,
+                "can't derive hardened child {}' from a public key",
+                index & 0x7FFF_FFFF
+            ),
+            BitcoinError::InvalidAsm { token } => write!(f, "invalid script ASM token {:?}", token),
+            BitcoinError::MalformedPush {
+                offset,
+                needed,
+                available,
+            } => write!(
+                f,
+                "push at script offset {} needs {} bytes, {} available",
+                offset, needed, available
+            ),
+            BitcoinError::InputIndexOutOfRange { index, inputs } => write!(
+                f,
+                "input index {} out of range for a transaction with {} inputs",
+                index, inputs
+            ),
+            BitcoinError::PrevoutMismatch { index } => {
+                write!(f, "input {} doesn't spend the given output", index)
+            }
+            BitcoinError::PrevoutCount { expected, got } => {
+                write!(f, "expected {} spent outputs, got {}", expected, got)
+            }
+            BitcoinError::InvalidSighashType(t) => write!(f, "invalid sighash type {:#x}", t),
+            BitcoinError::UnsupportedScript(kind) => {
+                write!(f, "unsupported script type: {}", kind)
+            }
+            BitcoinError::InvalidSignature => write!(f, "invalid signature"),
+            BitcoinError::InvalidKey => write!(f, "invalid key"),
+            BitcoinError::InvalidSignatureEncoding(err) => {
+                write!(f, "invalid signature encoding: {}", err)
+            }
+            BitcoinError::TaprootTreeTooDeep { depth } => {
+                write!(f, "taproot tree too deep: leaf at depth {}", depth)
+            }
+            BitcoinError::BudgetExceeded { resource, limit } => {
+                write!(
+                    f,
+                    "decode budget exceeded: more than {} {}",
+                    limit, resource
+                )
+            }
+            BitcoinError::InvalidAmount(reason) => write!(f, "invalid amount: {}", reason),
+            BitcoinError::ScriptTooLarge { len, limit } => {
+                write!(f, "script is {} bytes, the limit is {}", len, limit)
+            }
+            BitcoinError::OversizedPush { offset, len, limit } => write!(
+                f,
+                "push at script offset {} is {} bytes, the limit is {}",
+                offset, len, limit
+            ),
+        }
+    }
+}
+
+// Wrapped errors are exposed through source() rather than repeated in the
+// Display text, so error reporters that walk the chain don't print them twice
+impl std::error::Error for BitcoinError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BitcoinError::AtField { source, .. } => Some(source.as_ref()),
+            BitcoinError::InvalidHex(err) => Some(err),
+            BitcoinError::InvalidSignatureEncoding(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<SignatureError> for BitcoinError {
+    fn from(err: SignatureError) -> Self {
+        BitcoinError::InvalidSignatureEncoding(err)
+    }
+}
+
+impl From<hex::FromHexError> for BitcoinError {
+    fn from(err: hex::FromHexError) -> Self {
+        BitcoinError::InvalidHex(err)
+    }
+}
+
+// Running out of input on a reader is the streaming version of not having
+// enough bytes in a slice. io::Error doesn't say how short we were, so this
+// is the plain InsufficientBytes; the decoders report UnexpectedEof where
+// they do know.
+impl From<io::Error> for BitcoinError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => BitcoinError::InsufficientBytes,
+            kind => BitcoinError::Io(kind),
+        }
+    }
+}
+
+// Every decoder returns the parsed value together with how many bytes it
+// used up, so the caller knows where the next field starts. Wrapping both in
+// a named struct reads much better than juggling `(value, usize)` tuples, and
+// Deref means you can call methods on the decoded value directly.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Decoded<T> {
+    pub value: T,
+    pub consumed: usize,
+}
+
+impl<T> Decoded<T> {
+    pub fn new(value: T, consumed: usize) -> Self {
+        Decoded { value, consumed }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    pub fn into_parts(self) -> (T, usize) {
+        (self.value, self.consumed)
+    }
+}
+
+impl<T> Deref for Decoded<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+// Length prefixes come straight from untrusted input. On a 32-bit target a
+// u64 length might not even fit in usize, so decoders convert through this
+// instead of a truncating `as usize`.
+fn length_to_usize(value: u64) -> Result<usize, BitcoinError> {
+    usize::try_from(value).map_err(|_| BitcoinError::OversizedAllocation {
+        requested: value,
+        limit: usize::MAX as u64,
+    })
 }
 
 impl CompactSize {
@@ -20,6 +439,16 @@ impl CompactSize {
         CompactSize { value }
     }
 
+    // How many bytes to_bytes() will produce, i.e. the minimal encoding
+    pub fn encoded_len(&self) -> usize {
+        match self.value {
+            0..=0xFC => 1,
+            0xFD..=0xFFFF => 3,
+            0x1_0000..=0xFFFF_FFFF => 5,
+            _ => 9,
+        }
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         // OK so Bitcoin has this weird encoding called CompactSize
         // The idea is to save space by using fewer bytes for small numbers
@@ -55,13 +484,16 @@ impl CompactSize {
         }
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+    pub fn decode(bytes: &[u8]) -> Result<Decoded<Self>, BitcoinError> {
         // This is the reverse of to_bytes()
         // We need to figure out what format was used and decode it
 
         // First, basic safety check - do we have any bytes at all?
         if bytes.is_empty() {
-            return Err(BitcoinError::InsufficientBytes);
+            return Err(BitcoinError::UnexpectedEof {
+                needed: 1,
+                available: 0,
+            });
         }
 
         // Look at the first byte to determine the format
@@ -71,47 +503,92 @@ impl CompactSize {
             // Case 1: First byte is 0-252, so the value IS the first byte
             0x00..=0xFC => {
                 // Super simple - just convert the byte to u64
-                Ok((CompactSize::new(first_byte as u64), 1))
+                Ok(Decoded::new(CompactSize::new(first_byte as u64), 1))
             }
             // Case 2: First byte is 0xFD, so next 2 bytes are the value
             0xFD => {
                 // Check if we have enough bytes (need 3 total: prefix + 2 data)
                 if bytes.len() < 3 {
-                    return Err(BitcoinError::InsufficientBytes);
+                    return Err(BitcoinError::UnexpectedEof {
+                        needed: 3,
+                        available: bytes.len(),
+                    });
                 }
                 // Extract bytes 1 and 2, convert from little-endian
                 let value = u16::from_le_bytes([bytes[1], bytes[2]]) as u64;
-                Ok((CompactSize::new(value), 3)) // consumed 3 bytes total
+                Ok(Decoded::new(CompactSize::new(value), 3)) // consumed 3 bytes total
             }
             // Case 3: First byte is 0xFE, so next 4 bytes are the value
             0xFE => {
                 if bytes.len() < 5 {
                     // need 5 total: prefix + 4 data
-                    return Err(BitcoinError::InsufficientBytes);
+                    return Err(BitcoinError::UnexpectedEof {
+                        needed: 5,
+                        available: bytes.len(),
+                    });
                 }
                 // Extract 4 bytes and convert from little-endian
                 let value = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]) as u64;
-                Ok((CompactSize::new(value), 5))
+                Ok(Decoded::new(CompactSize::new(value), 5))
             }
             // Case 4: First byte is 0xFF, so next 8 bytes are the value
             0xFF => {
                 if bytes.len() < 9 {
                     // need 9 total: prefix + 8 data
-                    return Err(BitcoinError::InsufficientBytes);
+                    return Err(BitcoinError::UnexpectedEof {
+                        needed: 9,
+                        available: bytes.len(),
+                    });
                 }
                 // Extract all 8 bytes for the full u64
                 let value = u64::from_le_bytes([
                     bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7], bytes[8],
                 ]);
-                Ok((CompactSize::new(value), 9))
+                Ok(Decoded::new(CompactSize::new(value), 9))
             }
         }
     }
+
+    #[deprecated(note = "use decode")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        Self::decode(bytes).map(Decoded::into_parts)
+    }
+
+    // Like `decode`, but rejects encodings longer than the value needs, e.g.
+    // FD 05 00 for 5. Bitcoin Core refuses those when reading consensus
+    // data; DecodeLimits::minimal() does the same for whole transactions.
+    // `decode` stays lenient for reading old or non-standard data.
+    pub fn decode_minimal(bytes: &[u8]) -> Result<Decoded<Self>, BitcoinError> {
+        let decoded = Self::decode(bytes)?;
+        if decoded.consumed != decoded.encoded_len() {
+            return Err(BitcoinError::NonMinimalVarInt);
+        }
+        Ok(decoded)
+    }
+
+    #[deprecated(note = "use decode_minimal")]
+    pub fn from_bytes_minimal(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        Self::decode_minimal(bytes).map(Decoded::into_parts)
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct Txid(pub [u8; 32]);
 
+// Compares the displayed (byte-reversed) form, so txids sort the way
+// explorers and RPC output list them
+impl Ord for Txid {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for Txid {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Serialize for Txid {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -125,6 +602,40 @@ impl Serialize for Txid {
     }
 }
 
+// Txids are stored in the byte order the hash function produced them (which
+// is also the order they appear in serialized transactions), but every
+// explorer and RPC displays them byte-reversed. Display and FromStr use that
+// reversed, human-facing form.
+impl fmt::Display for Txid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut reversed = self.0;
+        reversed.reverse();
+        write!(f, "{}", hex::encode(reversed))
+    }
+}
+
+// The witness-inclusive counterpart of Txid (BIP141). Same byte-order rules.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Wtxid(pub [u8; 32]);
+
+impl fmt::Display for Wtxid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut reversed = self.0;
+        reversed.reverse();
+        write!(f, "{}", hex::encode(reversed))
+    }
+}
+
+impl FromStr for Txid {
+    type Err = BitcoinError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s)?;
+        let mut txid: [u8; 32] = bytes.try_into().map_err(|_| BitcoinError::InvalidFormat)?;
+        txid.reverse();
+        Ok(Txid(txid))
+    }
+}
+
 impl<'de> Deserialize<'de> for Txid {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -149,7 +660,7 @@ impl<'de> Deserialize<'de> for Txid {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
 pub struct OutPoint {
     pub txid: Txid,
     pub vout: u32, // vout = "vector out" = output index
@@ -165,38 +676,31 @@ impl OutPoint {
         }
     }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        // Bitcoin format: txid (32 bytes) + vout (4 bytes little-endian)
-        // Total: 36 bytes
-        let mut bytes = Vec::with_capacity(36); // pre-allocate for efficiency
+    // The outpoint a coinbase input "spends": all-zero txid, vout 0xFFFFFFFF
+    pub fn null() -> Self {
+        OutPoint::new([0u8; 32], u32::MAX)
+    }
 
-        // First 32 bytes: the transaction ID
-        bytes.extend_from_slice(&self.txid.0);
+    pub fn is_null(&self) -> bool {
+        *self == OutPoint::null()
+    }
 
-        // Next 4 bytes: the output index in little-endian
-        bytes.extend_from_slice(&self.vout.to_le_bytes());
+    // 32-byte txid, then vout as 4 bytes little-endian (see codec.rs)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::encode_to_vec(self)
+    }
 
-        bytes
+    pub fn decode(bytes: &[u8]) -> Result<Decoded<Self>, BitcoinError> {
+        codec::decode_slice(bytes)
     }
 
+    #[deprecated(note = "use decode")]
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        // Need exactly 36 bytes for an OutPoint
-        if bytes.len() < 36 {
-            return Err(BitcoinError::InsufficientBytes);
-        }
-
-        // Extract txid from first 32 bytes
-        let mut txid = [0u8; 32];
-        txid.copy_from_slice(&bytes[0..32]);
-
-        // Extract vout from next 4 bytes (little-endian)
-        let vout = u32::from_le_bytes([bytes[32], bytes[33], bytes[34], bytes[35]]);
-
-        Ok((OutPoint::new(txid, vout), 36)) // consumed 36 bytes
+        Self::decode(bytes).map(Decoded::into_parts)
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Default, Hash, Serialize, Deserialize)]
 pub struct Script {
     pub bytes: Vec<u8>,
 }
@@ -206,25 +710,33 @@ impl Script {
         Script { bytes } // Basic constructor to create a Script from raw bytes
     }
 
+    // CompactSize length prefix, then the script bytes
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut result = Vec::new();
-        let len = CompactSize::new(self.bytes.len() as u64); // Use CompactSize to encode the length of the script
-        // First serialize the length using CompactSize
-        result.extend(len.to_bytes());
-        result.extend(&self.bytes);
-        result // Combine CompactSize length prefix with the actual script bytes
+        codec::encode_to_vec(self)
+    }
+
+    // Same rules as a script inside a transaction (see codec.rs)
+    pub fn decode(bytes: &[u8]) -> Result<Decoded<Self>, BitcoinError> {
+        codec::decode_slice(bytes)
     }
 
+    #[deprecated(note = "use decode")]
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        let (len_prefix, offset) = CompactSize::from_bytes(bytes)?;
-        let len = len_prefix.value as usize; // Get the length of the script from CompactSize
+        Self::decode(bytes).map(Decoded::into_parts)
+    }
 
-        if bytes.len() < offset + len {
-            return Err(BitcoinError::InsufficientBytes);
-        } // Ensure i have enough bytes for the script
+    // Like `decode`, but also runs `validate` on the result, so a script
+    // that could never execute is rejected here instead of much later.
+    // `decode` stays lenient since scriptPubKeys on chain can be anything.
+    pub fn decode_strict(bytes: &[u8]) -> Result<Decoded<Self>, BitcoinError> {
+        let decoded = Self::decode(bytes)?;
+        decoded.validate()?;
+        Ok(decoded)
+    }
 
-        let script_bytes = bytes[offset..offset + len].to_vec();
-        Ok((Script::new(script_bytes), offset + len)) // Return the script and how many bytes i consumed
+    #[deprecated(note = "use decode_strict")]
+    pub fn from_bytes_strict(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        Self::decode_strict(bytes).map(Decoded::into_parts)
     }
 }
 
@@ -235,11 +747,177 @@ impl Deref for Script {
     }
 }
 
+// A segwit witness is a stack of byte strings (signatures, pubkeys, scripts,
+// ...) that proves an input may be spent. On the wire it's a CompactSize item
+// count followed by each item as CompactSize length + bytes (see codec.rs).
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Witness {
+    pub items: Vec<Vec<u8>>,
+}
+
+impl Witness {
+    pub fn new() -> Self {
+        Witness { items: Vec::new() }
+    }
+
+    pub fn from_items(items: Vec<Vec<u8>>) -> Self {
+        Witness { items }
+    }
+
+    pub fn push(&mut self, item: impl Into<Vec<u8>>) -> &mut Self {
+        self.items.push(item.into());
+        self
+    }
+
+    // An ECDSA signature goes on the stack as its DER encoding with the
+    // sighash type byte appended
+    pub fn push_ecdsa_signature(
+        &mut self,
+        der_sig: impl AsRef<[u8]>,
+        sighash_type: u8,
+    ) -> &mut Self {
+        let mut item = der_sig.as_ref().to_vec();
+        item.push(sighash_type);
+        self.push(item)
+    }
+
+    // P2WPKH spend: <signature> <pubkey>, where `sig` already has its
+    // sighash byte (see push_ecdsa_signature)
+    pub fn p2wpkh(sig: impl AsRef<[u8]>, pubkey: impl AsRef<[u8]>) -> Self {
+        Witness::from_items(vec![sig.as_ref().to_vec(), pubkey.as_ref().to_vec()])
+    }
+
+    // Taproot script-path spend: the script's inputs, then the leaf script,
+    // then the control block proving the leaf is in the output key's tree
+    pub fn tapscript<I, T>(script: &Script, control_block: impl AsRef<[u8]>, args: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<Vec<u8>>,
+    {
+        let mut witness = Witness::from_items(args.into_iter().map(Into::into).collect());
+        witness.push(script.bytes.clone());
+        witness.push(control_block.as_ref());
+        witness
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        self.items.get(index).map(Vec::as_slice)
+    }
+
+    // The top of the stack, e.g. the pubkey in a P2WPKH spend or the witness
+    // script in a P2WSH spend
+    pub fn last(&self) -> Option<&[u8]> {
+        self.items.last().map(Vec::as_slice)
+    }
+
+    // The signature in a P2WPKH spend
+    pub fn second_to_last(&self) -> Option<&[u8]> {
+        self.nth_from_end(1)
+    }
+
+    pub fn third_to_last(&self) -> Option<&[u8]> {
+        self.nth_from_end(2)
+    }
+
+    fn nth_from_end(&self, n: usize) -> Option<&[u8]> {
+        let index = self.items.len().checked_sub(n + 1)?;
+        self.get(index)
+    }
+
+    // BIP341: with two or more items, a last item starting with 0x50 is the
+    // annex, which is ignored by script execution but covered by signatures
+    pub fn taproot_annex(&self) -> Option<&[u8]> {
+        if self.items.len() < 2 {
+            return None;
+        }
+        self.last().filter(|item| item.first() == Some(&0x50))
+    }
+
+    // For a taproot script-path spend, the leaf script and control block
+    // (the two items below the annex, if there is one). None for anything
+    // shorter, which would be a key-path spend.
+    pub fn tapscript_parts(&self) -> Option<(&[u8], &[u8])> {
+        let skip = usize::from(self.taproot_annex().is_some());
+        let control_block = self.nth_from_end(skip)?;
+        let script = self.nth_from_end(skip + 1)?;
+        Some((script, control_block))
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Vec<u8>> {
+        self.items.iter()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::encode_to_vec(self)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Decoded<Self>, BitcoinError> {
+        codec::decode_slice(bytes)
+    }
+
+    #[deprecated(note = "use decode")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        Self::decode(bytes).map(Decoded::into_parts)
+    }
+}
+
+impl Index<usize> for Witness {
+    type Output = [u8];
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.items[index]
+    }
+}
+
+impl<'a> IntoIterator for &'a Witness {
+    type Item = &'a Vec<u8>;
+    type IntoIter = std::slice::Iter<'a, Vec<u8>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+// In JSON a witness is shown the way Core's RPCs show it: a list of hex strings
+impl Serialize for Witness {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_seq(self.items.iter().map(hex::encode))
+    }
+}
+
+impl<'de> Deserialize<'de> for Witness {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let hex_items = Vec::<String>::deserialize(deserializer)?;
+        let items = hex_items
+            .iter()
+            .map(hex::decode)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(serde::de::Error::custom)?;
+        Ok(Witness::from_items(items))
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct TransactionInput {
     pub previous_output: OutPoint,
     pub script_sig: Script,
     pub sequence: u32,
+    // Segwit inputs prove ownership with a stack of byte strings instead of
+    // (or as well as) the scriptSig. Empty for legacy inputs.
+    #[serde(default, skip_serializing_if = "Witness::is_empty")]
+    pub witness: Witness,
 }
 
 impl TransactionInput {
@@ -248,50 +926,56 @@ impl TransactionInput {
             previous_output,
             script_sig,
             sequence,
+            witness: Witness::new(),
         } // Basic constructor to create a TransactionInput
     }
+}
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new(); // Start with an empty vector to hold the serialized bytes
-        // Serialize the previous output (OutPoint)
-        // This is the transaction ID and output index
-        // i use the OutPoint's to_bytes() method to get its byte representation
-        // Then i serialize the scriptSig (Script) and sequence number
-        // The scriptSig is the script that proves ownership of the previous output
-        // Finally, i add the sequence number (4 bytes little-endian)
-        bytes.extend(self.previous_output.to_bytes());
-        bytes.extend(self.script_sig.to_bytes());
-        bytes.extend(&self.sequence.to_le_bytes());
-        bytes
-    }
-
-    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        let (outpoint, offset1) = OutPoint::from_bytes(bytes)?;
-        let (script_sig, offset2) = Script::from_bytes(&bytes[offset1..])?;
-        let total_offset = offset1 + offset2;
+// Wire format: the previous output (txid + vout), then the scriptSig that
+// proves ownership of it (CompactSize length + bytes), then the 4-byte
+// little-endian sequence number. The witness is not part of the input's own
+// encoding - BIP144 puts all witnesses after the outputs - so to_bytes()
+// leaves it out and decode() returns an input with an empty witness.
+impl_consensus_codec!(
+    TransactionInput {
+        previous_output: OutPoint,
+        script_sig: Script,
+        sequence: u32,
+    },
+    default { witness }
+);
 
-        if bytes.len() < total_offset + 4 {
-            return Err(BitcoinError::InsufficientBytes);
-        } // Ensure i have enough bytes for the sequence
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct TransactionOutput {
+    pub value: Amount,
+    pub script_pubkey: Script,
+}
 
-        let sequence = u32::from_le_bytes([
-            bytes[total_offset],
-            bytes[total_offset + 1],
-            bytes[total_offset + 2],
-            bytes[total_offset + 3],
-        ]);
+// Bitcoin Core and most libraries call this a TxOut
+pub type TxOut = TransactionOutput;
 
-        Ok((
-            TransactionInput::new(outpoint, script_sig, sequence),
-            total_offset + 4,
-        )) // Return the TransactionInput and how many bytes were consumed
+impl TransactionOutput {
+    pub fn new(value: impl Into<Amount>, script_pubkey: Script) -> Self {
+        Self {
+            value: value.into(),
+            script_pubkey,
+        } // The script_pubkey is the "lock" that whoever spends this output has to satisfy
     }
 }
 
+// Wire format: 8-byte little-endian value in satoshis, then the scriptPubKey
+// (CompactSize length + bytes).
+impl_consensus_codec!(TransactionOutput {
+    value: Amount,
+    script_pubkey: Script,
+});
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct BitcoinTransaction {
     pub version: u32,
     pub inputs: Vec<TransactionInput>,
+    #[serde(default)] // JSON written before outputs existed has no "outputs" key
+    pub outputs: Vec<TransactionOutput>,
     pub lock_time: u32,
 }
 
@@ -300,63 +984,285 @@ impl BitcoinTransaction {
         Self {
             version,
             inputs,
+            outputs: Vec::new(), // add them with add_output() or by setting the field
             lock_time,
         }
     }
 
+    // Small mutators for construct-and-tweak flows. add_input returns &mut Self
+    // so calls can be chained: tx.add_input(a).add_input(b);
+    pub fn add_input(&mut self, input: TransactionInput) -> &mut Self {
+        self.inputs.push(input);
+        self
+    }
+
+    pub fn input_mut(&mut self, index: usize) -> Option<&mut TransactionInput> {
+        self.inputs.get_mut(index)
+    }
+
+    pub fn add_output(&mut self, output: TransactionOutput) -> &mut Self {
+        self.outputs.push(output);
+        self
+    }
+
+    pub fn output_mut(&mut self, index: usize) -> Option<&mut TransactionOutput> {
+        self.outputs.get_mut(index)
+    }
+
+    // The transaction id is SHA256d of the legacy serialization. Leaving the
+    // witness out is what makes segwit txids immune to signature malleability.
+    pub fn txid(&self) -> Txid {
+        Txid(hashes::sha256d(&self.to_bytes_without_witness()))
+    }
+
+    // The wtxid hashes the full serialization, witness included. For a tx
+    // without witness data that's the same bytes as the txid. The coinbase's
+    // wtxid is defined as all zeros, since the witness commitment that the
+    // wtxids feed into lives inside the coinbase itself.
+    pub fn wtxid(&self) -> Wtxid {
+        if self.is_coinbase() {
+            return Wtxid([0u8; 32]);
+        }
+        Wtxid(hashes::sha256d(&self.to_bytes()))
+    }
+
+    // A coinbase has exactly one input, spending the "null" outpoint
+    pub fn is_coinbase(&self) -> bool {
+        self.inputs.len() == 1 && self.inputs[0].previous_output.is_null()
+    }
+
+    pub fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|input| !input.witness.is_empty())
+    }
+
+    // Picks the format automatically, like Core: segwit (BIP144) if any input
+    // has witness data, otherwise the legacy format.
     pub fn to_bytes(&self) -> Vec<u8> {
+        codec::encode_to_vec(self)
+    }
+
+    // The legacy ("stripped") serialization: version, inputs, outputs, lock
+    // time, with no witness data. This is what the txid commits to, what the
+    // legacy sighash is computed over, and what the merkle tree of txids hashes.
+    pub fn to_bytes_without_witness(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
+        self.encode_to(&mut bytes, false)
+            .expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    // Always uses the BIP144 layout, even if every witness is empty (each
+    // empty stack is written as a single 0x00 count).
+    pub fn to_bytes_with_witness(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.encode_to(&mut bytes, true)
+            .expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    // Size of the serialization `to_bytes` produces: with witness data if
+    // there is any
+    pub fn total_size(&self) -> usize {
+        self.encode_to(&mut io::sink(), self.has_witness())
+            .expect("writing to a sink can't fail")
+    }
+
+    // Size without any witness data, i.e. of `to_bytes_without_witness`
+    pub fn base_size(&self) -> usize {
+        self.encode_to(&mut io::sink(), false)
+            .expect("writing to a sink can't fail")
+    }
+
+    // BIP141: base size x 3 + total size, which is the same as 4 wu per
+    // non-witness byte and 1 wu per witness byte (marker and flag count as
+    // witness data)
+    pub fn weight(&self) -> Weight {
+        let base = self.base_size() as u64;
+        let total = self.total_size() as u64;
+        Weight::from_wu(base * (Weight::WITNESS_SCALE_FACTOR - 1) + total)
+    }
+
+    pub fn vsize(&self) -> u64 {
+        self.weight().to_vbytes_ceil()
+    }
 
+    pub(crate) fn encode_to<W: io::Write + ?Sized>(
+        &self,
+        writer: &mut W,
+        with_witness: bool,
+    ) -> io::Result<usize> {
         // Version
-        bytes.extend(&self.version.to_le_bytes());
+        let mut written = self.version.consensus_encode(writer)?;
 
-        // Input count
-        let count = CompactSize::new(self.inputs.len() as u64);
-        bytes.extend(count.to_bytes());
+        // Segwit marker (0x00) and flag (0x01). A legacy parser would read the
+        // 0x00 as "zero inputs", which is how old nodes skip over this data.
+        if with_witness {
+            writer.write_all(&[0x00, 0x01])?;
+            written += 2;
+        }
 
-        // Inputs
-        for input in &self.inputs {
-            bytes.extend(input.to_bytes());
+        // Inputs and outputs, each a CompactSize count followed by the items
+        written += self.inputs.consensus_encode(writer)?;
+        written += self.outputs.consensus_encode(writer)?;
+
+        // One witness stack per input, in input order
+        if with_witness {
+            for input in &self.inputs {
+                written += input.witness.consensus_encode(writer)?;
+            }
         }
 
         // Lock time
-        bytes.extend(&self.lock_time.to_le_bytes());
+        written += self.lock_time.consensus_encode(writer)?;
 
-        bytes
+        Ok(written)
+    }
+
+    // The segwit/legacy detection lives in the ConsensusDecodable impl
+    pub fn decode(bytes: &[u8]) -> Result<Decoded<Self>, BitcoinError> {
+        codec::decode_slice(bytes)
+    }
+
+    // `decode` applies DecodeLimits::default(); use this to tighten them,
+    // e.g. a relay-policy sized max_total_size for data straight off the wire
+    pub fn decode_with_limits(
+        bytes: &[u8],
+        limits: DecodeLimits,
+    ) -> Result<Decoded<Self>, BitcoinError> {
+        codec::decode_slice_with_limits(bytes, limits)
     }
 
+    // For data from an untrusted peer: default limits, plus a DecodeBudget
+    // for the whole transaction
+    pub fn decode_with_budget(
+        bytes: &[u8],
+        budget: DecodeBudget,
+    ) -> Result<Decoded<Self>, BitcoinError> {
+        codec::decode_slice_with_budget(bytes, DecodeLimits::default(), budget)
+    }
+
+    #[deprecated(note = "use decode")]
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        if bytes.len() < 4 {
-            return Err(BitcoinError::InsufficientBytes);
+        Self::decode(bytes).map(Decoded::into_parts)
+    }
+
+    // Decodes and re-serializes, so encodings that differ only in ways the
+    // decoder accepts (padded CompactSizes, a segwit marker with every
+    // witness empty) come out as the same bytes. With `keep_witness` false
+    // the result is the stripped serialization the txid commits to. The
+    // input must be exactly one transaction.
+    pub fn normalize(bytes: &[u8], keep_witness: bool) -> Result<Vec<u8>, BitcoinError> {
+        let (tx, consumed) = Self::decode(bytes)?.into_parts();
+        if consumed != bytes.len() {
+            return Err(BitcoinError::TrailingBytes(bytes.len() - consumed));
         }
+        Ok(if keep_witness {
+            tx.to_bytes()
+        } else {
+            tx.to_bytes_without_witness()
+        })
+    }
 
-        let version = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-        let (count_cs, offset1) = CompactSize::from_bytes(&bytes[4..])?;
-        let count = count_cs.value as usize;
-        let mut inputs = Vec::with_capacity(count);
+    // Same, for hex as it comes out of RPCs and explorers: surrounding
+    // whitespace and upper case are fine, the result is lowercase
+    pub fn normalize_hex(hex: &str, keep_witness: bool) -> Result<String, BitcoinError> {
+        let bytes = hex::decode(hex.trim())?;
+        Ok(hex::encode(Self::normalize(&bytes, keep_witness)?))
+    }
 
-        let mut offset = 4 + offset1;
-        for _ in 0..count {
-            let (input, used) = TransactionInput::from_bytes(&bytes[offset..])?;
-            inputs.push(input);
-            offset += used;
-        }
+    // True if both serialize to the same bytes, witness included. Compare
+    // txids instead to treat a transaction and its re-signed (malleated)
+    // version as the same.
+    pub fn canonical_eq(&self, other: &BitcoinTransaction) -> bool {
+        self.to_bytes() == other.to_bytes()
+    }
 
-        if bytes.len() < offset + 4 {
-            return Err(BitcoinError::InsufficientBytes);
-        }
+    // Same order as Ord, but each key is serialized and hashed once per
+    // transaction instead of on every comparison. Equivalent to
+    // `txs.sort_by_cached_key(|tx| (tx.txid(), tx.to_bytes()))`.
+    pub fn sort_by_txid(txs: &mut [BitcoinTransaction]) {
+        txs.sort_by_cached_key(|tx| (tx.txid(), tx.to_bytes()));
+    }
+}
+
+// Ordered by txid (see Txid's Ord), so sorted lists match what explorers
+// show. Transactions sharing a txid but not a witness are ordered by their
+// full serialization, to stay consistent with Eq. Every comparison hashes
+// both sides, so sort big lists with sort_by_txid instead of sort().
+impl Ord for BitcoinTransaction {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.txid()
+            .cmp(&other.txid())
+            .then_with(|| self.to_bytes().cmp(&other.to_bytes()))
+    }
+}
+
+impl PartialOrd for BitcoinTransaction {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Version 2 is what every modern wallet produces (it enables BIP68 relative
+// locktimes), and a zero locktime means "valid immediately". No inputs or
+// outputs.
+impl Default for BitcoinTransaction {
+    fn default() -> Self {
+        BitcoinTransaction::new(2, Vec::new(), 0)
+    }
+}
+
+// Collection-style access for analysis code. A transaction holds both
+// inputs and outputs, so nothing picks one silently: index with
+// `tx[InputIndex(0)]` or `tx[OutputIndex(1)]`, iterate `tx.inputs()` or
+// `tx.outputs()`, and `extend` with either kind of item.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct InputIndex(pub usize);
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct OutputIndex(pub usize);
+
+impl BitcoinTransaction {
+    pub fn inputs(&self) -> &[TransactionInput] {
+        &self.inputs
+    }
 
-        let lock_time = u32::from_le_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-        ]);
+    pub fn outputs(&self) -> &[TransactionOutput] {
+        &self.outputs
+    }
+
+    pub fn iter_inputs(&self) -> std::slice::Iter<'_, TransactionInput> {
+        self.inputs.iter()
+    }
+
+    pub fn iter_outputs(&self) -> std::slice::Iter<'_, TransactionOutput> {
+        self.outputs.iter()
+    }
+}
+
+impl Index<InputIndex> for BitcoinTransaction {
+    type Output = TransactionInput;
+    fn index(&self, index: InputIndex) -> &Self::Output {
+        &self.inputs[index.0] // panics on out-of-range, same as Vec
+    }
+}
 
-        Ok((
-            BitcoinTransaction::new(version, inputs, lock_time),
-            offset + 4,
-        ))
+impl Index<OutputIndex> for BitcoinTransaction {
+    type Output = TransactionOutput;
+    fn index(&self, index: OutputIndex) -> &Self::Output {
+        &self.outputs[index.0]
+    }
+}
+
+impl Extend<TransactionInput> for BitcoinTransaction {
+    fn extend<I: IntoIterator<Item = TransactionInput>>(&mut self, iter: I) {
+        self.inputs.extend(iter);
+    }
+}
+
+impl Extend<TransactionOutput> for BitcoinTransaction {
+    fn extend<I: IntoIterator<Item = TransactionOutput>>(&mut self, iter: I) {
+        self.outputs.extend(iter);
     }
 }
 
@@ -365,7 +1271,24 @@ impl fmt::Display for BitcoinTransaction {
         writeln!(f, "Version: {}", self.version)?;
         for input in &self.inputs {
             writeln!(f, "Previous Output Vout: {}", input.previous_output.vout)?;
+            writeln!(
+                f,
+                "Sequence: {:#010x} ({})",
+                input.sequence,
+                Sequence(input.sequence).describe()
+            )?;
+            if !input.witness.is_empty() {
+                writeln!(f, "Witness Items: {}", input.witness.len())?;
+            }
+        }
+        for output in &self.outputs {
+            writeln!(f, "Output Value: {} sats", output.value.to_sat())?;
         }
-        writeln!(f, "Lock Time: {}", self.lock_time)
+        writeln!(
+            f,
+            "Lock Time: {} ({})",
+            self.lock_time,
+            LockTime(self.lock_time).describe()
+        )
     }
 }
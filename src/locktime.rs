@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// nSequence and nLockTime are stored as plain u32 on the transaction types,
+// but the same number means very different things depending on which bits
+// are set. These newtypes wrap the raw value and know how to interpret it.
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Sequence(pub u32);
+
+impl Sequence {
+    // 0xFFFFFFFF: input is final, no RBF, no relative locktime, and nLockTime
+    // is ignored if every input is final
+    pub const MAX: Sequence = Sequence(0xFFFFFFFF);
+    // 0xFFFFFFFE: nLockTime is enforced but RBF is not signalled
+    pub const ENABLE_LOCKTIME_NO_RBF: Sequence = Sequence(0xFFFFFFFE);
+    // 0xFFFFFFFD: what modern wallets use by default - RBF on, no relative lock
+    pub const ENABLE_RBF_NO_LOCKTIME: Sequence = Sequence(0xFFFFFFFD);
+
+    // BIP68 bits: bit 31 disables the relative lock, bit 22 picks time vs
+    // blocks, and the low 16 bits carry the value
+    const DISABLE_FLAG: u32 = 1 << 31;
+    const TYPE_FLAG: u32 = 1 << 22;
+    const VALUE_MASK: u32 = 0x0000FFFF;
+
+    pub fn is_final(&self) -> bool {
+        *self == Sequence::MAX
+    }
+
+    // BIP125: any input with nSequence below 0xFFFFFFFE opts the tx into RBF
+    pub fn signals_rbf(&self) -> bool {
+        self.0 < Sequence::ENABLE_LOCKTIME_NO_RBF.0
+    }
+
+    pub fn describe(&self) -> SequenceInterpretation {
+        if self.is_final() {
+            SequenceInterpretation::Final
+        } else if self.0 & Self::DISABLE_FLAG != 0 {
+            SequenceInterpretation::NoRelativeLockTime {
+                rbf: self.signals_rbf(),
+            }
+        } else if self.0 & Self::TYPE_FLAG != 0 {
+            // Time-based relative locks count in units of 512 seconds
+            SequenceInterpretation::RelativeTime {
+                seconds: (self.0 & Self::VALUE_MASK) * 512,
+            }
+        } else {
+            SequenceInterpretation::RelativeBlocks {
+                blocks: (self.0 & Self::VALUE_MASK) as u16,
+            }
+        }
+    }
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Sequence::ENABLE_RBF_NO_LOCKTIME
+    }
+}
+
+impl From<u32> for Sequence {
+    fn from(value: u32) -> Self {
+        Sequence(value)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SequenceInterpretation {
+    Final,
+    NoRelativeLockTime { rbf: bool },
+    RelativeBlocks { blocks: u16 },
+    RelativeTime { seconds: u32 },
+}
+
+impl fmt::Display for SequenceInterpretation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SequenceInterpretation::Final => write!(f, "final"),
+            SequenceInterpretation::NoRelativeLockTime { rbf: true } => {
+                write!(f, "no relative locktime (RBF)")
+            }
+            SequenceInterpretation::NoRelativeLockTime { rbf: false } => {
+                write!(f, "no relative locktime")
+            }
+            SequenceInterpretation::RelativeBlocks { blocks } => {
+                write!(f, "relative: {} blocks", blocks)
+            }
+            SequenceInterpretation::RelativeTime { seconds } => {
+                write!(f, "relative: {} seconds", seconds)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LockTime(pub u32);
+
+impl LockTime {
+    // Values below this are block heights, values at or above are unix times
+    pub const THRESHOLD: u32 = 500_000_000;
+
+    pub fn is_block_height(&self) -> bool {
+        self.0 < Self::THRESHOLD
+    }
+
+    pub fn describe(&self) -> LockTimeInterpretation {
+        match self.0 {
+            0 => LockTimeInterpretation::None,
+            h if h < Self::THRESHOLD => LockTimeInterpretation::BlockHeight { height: h },
+            t => LockTimeInterpretation::UnixTime { timestamp: t },
+        }
+    }
+}
+
+impl From<u32> for LockTime {
+    fn from(value: u32) -> Self {
+        LockTime(value)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LockTimeInterpretation {
+    None,
+    BlockHeight { height: u32 },
+    UnixTime { timestamp: u32 },
+}
+
+impl fmt::Display for LockTimeInterpretation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockTimeInterpretation::None => write!(f, "none"),
+            LockTimeInterpretation::BlockHeight { height } => {
+                write!(f, "absolute: block height {}", height)
+            }
+            LockTimeInterpretation::UnixTime { timestamp } => {
+                write!(f, "absolute: unix time {}", format_utc(*timestamp))
+            }
+        }
+    }
+}
+
+// Format a unix timestamp as an ISO-8601 UTC string without pulling in a date
+// crate. The day -> (year, month, day) step is the usual civil-from-days
+// algorithm (Howard Hinnant's), which is exact for the whole u32 range.
+fn format_utc(timestamp: u32) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let secs = timestamp % 86_400;
+
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
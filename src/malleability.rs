@@ -0,0 +1,135 @@
+use crate::script::Instruction;
+use crate::{BitcoinTransaction, Script, Signature, TransactionInput};
+use serde::{Deserialize, Serialize};
+
+// Ways a third party could change a transaction's txid without invalidating
+// it, e.g. while relaying it. Anything tracking unconfirmed transactions by
+// txid (exchanges watching withdrawals, chains of unconfirmed spends) cares,
+// since the version that confirms may have a different txid.
+//
+// Only the scriptSig is covered by the txid, so segwit inputs (empty
+// scriptSig, or exactly one push of the redeem script for P2SH-wrapped ones)
+// are safe. For everything else the classic vectors are:
+//
+// - NonSegwitInput: the input is legacy at all. Always reported for a legacy
+//   input, even if nothing more specific was found
+// - HighS: ECDSA signatures stay valid with S replaced by n - S
+// - NonMinimalPush: the same data can be re-pushed with a longer opcode
+// - NonPushOpcode: extra no-op opcodes can be added or removed
+// - NonStandardSighash: an undefined sighash type byte, which Core won't
+//   relay but miners can still mine
+//
+// These are the policy rules Core added (LOW_S, MINIMALDATA, SIGPUSHONLY,
+// STRICTENC) - a transaction that passes them can still be malleated by a
+// miner, just not by an ordinary relay node.
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MalleabilityVector {
+    NonSegwitInput,
+    HighS,
+    NonMinimalPush { offset: usize },
+    NonPushOpcode { offset: usize },
+    NonStandardSighash { sighash_type: u8 },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct MalleabilityIssue {
+    pub input: usize,
+    pub vector: MalleabilityVector,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+pub struct MalleabilityReport {
+    pub issues: Vec<MalleabilityIssue>,
+}
+
+impl MalleabilityReport {
+    pub fn is_malleable(&self) -> bool {
+        !self.issues.is_empty()
+    }
+
+    pub fn for_input(&self, input: usize) -> impl Iterator<Item = &MalleabilityVector> {
+        self.issues
+            .iter()
+            .filter(move |issue| issue.input == input)
+            .map(|issue| &issue.vector)
+    }
+}
+
+pub fn analyze_malleability(tx: &BitcoinTransaction) -> MalleabilityReport {
+    let mut report = MalleabilityReport::default();
+    // The coinbase scriptSig is free-form and nobody else can change it
+    if tx.is_coinbase() {
+        return report;
+    }
+    for (index, input) in tx.inputs.iter().enumerate() {
+        if is_segwit_spend(input) {
+            continue;
+        }
+        let mut add = |vector| {
+            report.issues.push(MalleabilityIssue {
+                input: index,
+                vector,
+            })
+        };
+        add(MalleabilityVector::NonSegwitInput);
+
+        let mut instructions = input.script_sig.instructions();
+        loop {
+            let offset = instructions.position();
+            let data = match instructions.next() {
+                None => break,
+                // A truncated push can't be valid anyway, nothing to malleate
+                Some(Err(_)) => break,
+                Some(Ok(Instruction::Op(op))) => {
+                    // OP_0 .. OP_16 (incl. OP_1NEGATE and OP_RESERVED) count as
+                    // pushes, as in Core's IsPushOnly
+                    if op.to_byte() > 0x60 {
+                        add(MalleabilityVector::NonPushOpcode { offset });
+                    }
+                    continue;
+                }
+                Some(Ok(Instruction::PushBytes(data))) => data,
+            };
+
+            let raw = &input.script_sig.bytes[offset..instructions.position()];
+            if raw != Script::builder().push_slice(data).into_script().bytes {
+                add(MalleabilityVector::NonMinimalPush { offset });
+            }
+
+            // Anything that isn't strict DER isn't a signature at all
+            if let Ok(signature) = Signature::from_bytes(data) {
+                if !signature.is_low_s() {
+                    add(MalleabilityVector::HighS);
+                }
+                if !signature.sighash_type.is_standard() {
+                    add(MalleabilityVector::NonStandardSighash {
+                        sighash_type: signature.sighash_type.0 as u8,
+                    });
+                }
+            }
+        }
+    }
+    report
+}
+
+// Native segwit spends have an empty scriptSig. P2SH-wrapped ones carry
+// exactly one push of the redeem script, and consensus requires it to be
+// exactly that push, so it can't be re-encoded either.
+fn is_segwit_spend(input: &TransactionInput) -> bool {
+    if input.witness.is_empty() {
+        return false;
+    }
+    let mut instructions = input.script_sig.instructions();
+    match instructions.next() {
+        None => true,
+        Some(Ok(Instruction::PushBytes(redeem_script))) => {
+            instructions.next().is_none()
+                && Script::new(redeem_script.to_vec())
+                    .witness_program()
+                    .is_some()
+        }
+        _ => false,
+    }
+}
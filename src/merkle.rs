@@ -0,0 +1,86 @@
+use crate::hashes::sha256d;
+
+// A merkle branch is the list of sibling hashes needed to climb from one leaf
+// up to the root, plus the leaf's position. The position tells us, level by
+// level, whether our running hash is the left or the right child.
+//
+// The same structure shows up in two places: the coinbase branch in stratum
+// jobs (index is always 0) and SPV inclusion proofs for any transaction.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MerkleBranch {
+    pub hashes: Vec<[u8; 32]>,
+    pub index: u32,
+}
+
+impl MerkleBranch {
+    pub fn new(hashes: Vec<[u8; 32]>, index: u32) -> Self {
+        MerkleBranch { hashes, index }
+    }
+
+    // Build the branch for `leaves[index]`. Returns None if the index is out
+    // of range. Like Core, an odd node at the end of a level is paired with
+    // itself.
+    pub fn from_leaves(leaves: &[[u8; 32]], index: usize) -> Option<Self> {
+        if index >= leaves.len() {
+            return None;
+        }
+
+        let mut hashes = Vec::new();
+        let mut level = leaves.to_vec();
+        let mut pos = index;
+        while level.len() > 1 {
+            // Sibling is the neighbour in the pair, or ourselves if we're the odd one out
+            let sibling = if pos % 2 == 1 {
+                level[pos - 1]
+            } else {
+                *level.get(pos + 1).unwrap_or(&level[pos])
+            };
+            hashes.push(sibling);
+            level = next_level(&level);
+            pos /= 2;
+        }
+
+        Some(MerkleBranch::new(hashes, index as u32))
+    }
+
+    pub fn derive_root(&self, leaf: [u8; 32]) -> [u8; 32] {
+        let mut current = leaf;
+        let mut index = self.index;
+        for sibling in &self.hashes {
+            // Lowest bit set means we're the right child at this level
+            current = if index & 1 == 1 {
+                hash_pair(sibling, &current)
+            } else {
+                hash_pair(&current, sibling)
+            };
+            index >>= 1;
+        }
+        current
+    }
+}
+
+// Merkle root over a full list of leaves (e.g. all txids of a block).
+pub fn merkle_root(leaves: &[[u8; 32]]) -> Option<[u8; 32]> {
+    if leaves.is_empty() {
+        return None;
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    Some(level[0])
+}
+
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+        .collect()
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut buf = [0u8; 64];
+    buf[..32].copy_from_slice(left);
+    buf[32..].copy_from_slice(right);
+    sha256d(&buf)
+}
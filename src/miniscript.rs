@@ -0,0 +1,787 @@
+use crate::descriptor::{self, Context, DescriptorKey};
+use crate::opcodes::Opcode;
+use crate::{BitcoinError, CompactSize, Script, Weight, hashes};
+use std::fmt;
+use std::str::FromStr;
+
+// A practical subset of miniscript (`secp256k1` feature): the fragments you
+// need for key, timelock and threshold policies, with the wrappers that make
+// them compose. Every expression maps to exactly one script, so a wallet can
+// reason about what's needed to spend it without running a script
+// interpreter.
+//
+// Supported fragments: pk_k, pk_h, older, after, and_v, and_b, or_b, or_c,
+// or_d, or_i, thresh, multi, plus the a: s: c: d: v: n: wrappers and the
+// pk()/pkh() shorthands for c:pk_k / c:pk_h.
+//
+// Type checking covers the base types (B, V, K, W) and the z/o/n/d/u
+// properties they depend on, which is enough to reject scripts that don't
+// work. It doesn't check non-malleability or resource limits; stick to
+// policies a compiler produced if that matters.
+
+// Which script version the miniscript runs under. Tapscript uses x-only
+// keys and Schnorr signatures, and has no CHECKMULTISIG.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScriptContext {
+    Segwitv0,
+    Tapscript,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Miniscript {
+    pub fragment: Fragment,
+    pub context: ScriptContext,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Fragment {
+    PkK(DescriptorKey),
+    PkH(DescriptorKey),
+    Older(u32),
+    After(u32),
+    AndV(Box<Fragment>, Box<Fragment>),
+    AndB(Box<Fragment>, Box<Fragment>),
+    OrB(Box<Fragment>, Box<Fragment>),
+    OrC(Box<Fragment>, Box<Fragment>),
+    OrD(Box<Fragment>, Box<Fragment>),
+    OrI(Box<Fragment>, Box<Fragment>),
+    Thresh(usize, Vec<Fragment>),
+    Multi(usize, Vec<DescriptorKey>),
+    Alt(Box<Fragment>),
+    Swap(Box<Fragment>),
+    Check(Box<Fragment>),
+    DupIf(Box<Fragment>),
+    Verify(Box<Fragment>),
+    NonZero(Box<Fragment>),
+}
+
+// The spending conditions with the script details stripped away
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Policy {
+    Key(DescriptorKey),
+    Older(u32),
+    After(u32),
+    // k of the sub-policies; and/or are thresh(n,...) / thresh(1,...)
+    Threshold(usize, Vec<Policy>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Base {
+    B,
+    V,
+    K,
+    W,
+}
+
+// A base type plus the properties from the miniscript spec: z (consumes no
+// stack items), o (exactly one), n (top item is never zero), d (has a
+// dissatisfaction), u (leaves exactly 1 on success)
+#[derive(Debug, Clone, Copy)]
+struct Type {
+    base: Base,
+    z: bool,
+    o: bool,
+    n: bool,
+    d: bool,
+    u: bool,
+}
+
+const MAX_MULTISIG_KEYS: usize = 20;
+// Timelocks are script numbers with the disable bit clear
+const MAX_TIMELOCK: u32 = 0x7FFF_FFFF;
+
+impl Miniscript {
+    // Parses and type checks; the result must be of type B
+    pub fn parse(s: &str, context: ScriptContext) -> Result<Self, BitcoinError> {
+        let fragment = Fragment::parse(s, context)?;
+        match fragment.type_check(context)?.base {
+            Base::B => Ok(Miniscript { fragment, context }),
+            _ => Err(invalid("top level must be of type B")),
+        }
+    }
+
+    pub fn is_ranged(&self) -> bool {
+        self.fragment.keys().iter().any(|key| key.is_ranged())
+    }
+
+    // The script, with any ranged keys derived at `index`
+    pub fn to_script(&self, index: u32) -> Result<Script, BitcoinError> {
+        let mut bytes = Vec::new();
+        self.fragment.compile(&mut bytes, self.context, index)?;
+        Ok(Script::new(bytes))
+    }
+
+    // Largest witness stack (item lengths and data, no item count) any
+    // satisfaction needs. Assumes worst-case signatures: 72-byte DER plus
+    // sighash byte, or 64-byte Schnorr plus sighash byte.
+    pub fn max_satisfaction_size(&self) -> usize {
+        self.fragment
+            .sizes(self.context)
+            .sat
+            .expect("type B fragments always have a satisfaction")
+    }
+
+    // Weight of the worst-case wsh() spend, in the units WeightedUtxo wants:
+    // the empty scriptSig's length byte, then the witness item count, the
+    // satisfaction and the witness script. Tapscript spends also carry a
+    // control block, which depends on the tree, so they're not covered here.
+    pub fn max_satisfaction_weight(&self) -> Result<Weight, BitcoinError> {
+        if self.context == ScriptContext::Tapscript {
+            return Err(invalid("tapscript weight depends on the control block"));
+        }
+        let script_len = self.to_script(0)?.len();
+        let witness = 1
+            + self.max_satisfaction_size()
+            + CompactSize::new(script_len as u64).encoded_len()
+            + script_len;
+        Ok(Weight::from_wu(
+            Weight::from_non_witness_data_size(1).to_wu() + witness as u64,
+        ))
+    }
+
+    pub fn lift(&self) -> Policy {
+        self.fragment.lift()
+    }
+
+    pub(crate) fn keys(&self) -> Vec<&DescriptorKey> {
+        self.fragment.keys()
+    }
+}
+
+// Max witness size to satisfy / dissatisfy; None if there's no way to
+struct Sizes {
+    sat: Option<usize>,
+    dsat: Option<usize>,
+}
+
+fn add(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    Some(a? + b?)
+}
+
+fn max(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    a.max(b)
+}
+
+impl Fragment {
+    fn parse(s: &str, context: ScriptContext) -> Result<Self, BitcoinError> {
+        // Wrapper letters before a colon apply outermost first
+        let (name, args) =
+            descriptor::call(s).map_err(|_| invalid(&format!("can't parse {:?}", s)))?;
+        if let Some((wrappers, name)) = name.split_once(':') {
+            let inner = Fragment::parse(&format!("{}({})", name, args), context)?;
+            return wrappers.chars().rev().try_fold(inner, |inner, wrapper| {
+                let inner = Box::new(inner);
+                Ok(match wrapper {
+                    'a' => Fragment::Alt(inner),
+                    's' => Fragment::Swap(inner),
+                    'c' => Fragment::Check(inner),
+                    'd' => Fragment::DupIf(inner),
+                    'v' => Fragment::Verify(inner),
+                    'n' => Fragment::NonZero(inner),
+                    other => return Err(invalid(&format!("unsupported wrapper {}:", other))),
+                })
+            });
+        }
+
+        let args = descriptor::split_args(args);
+        let key = |s: &str| DescriptorKey::parse(s, key_context(context));
+        let sub = |s: &str| Fragment::parse(s, context).map(Box::new);
+        let two = |args: &[&str]| match args {
+            [x, y] => Ok((sub(x)?, sub(y)?)),
+            _ => Err(invalid(&format!("{}() takes two arguments", name))),
+        };
+        Ok(match (name, args.as_slice()) {
+            ("pk_k", [k]) => Fragment::PkK(key(k)?),
+            ("pk_h", [k]) => Fragment::PkH(key(k)?),
+            ("pk", [k]) => Fragment::Check(Box::new(Fragment::PkK(key(k)?))),
+            ("pkh", [k]) => Fragment::Check(Box::new(Fragment::PkH(key(k)?))),
+            ("older", [n]) => Fragment::Older(timelock(n)?),
+            ("after", [n]) => Fragment::After(timelock(n)?),
+            ("and_v", args) => two(args).map(|(x, y)| Fragment::AndV(x, y))?,
+            ("and_b", args) => two(args).map(|(x, y)| Fragment::AndB(x, y))?,
+            ("or_b", args) => two(args).map(|(x, z)| Fragment::OrB(x, z))?,
+            ("or_c", args) => two(args).map(|(x, z)| Fragment::OrC(x, z))?,
+            ("or_d", args) => two(args).map(|(x, z)| Fragment::OrD(x, z))?,
+            ("or_i", args) => two(args).map(|(x, z)| Fragment::OrI(x, z))?,
+            ("thresh", [k, subs @ ..]) => Fragment::Thresh(
+                threshold(k, subs.len())?,
+                subs.iter()
+                    .map(|s| Fragment::parse(s, context))
+                    .collect::<Result<_, _>>()?,
+            ),
+            ("multi", [k, keys @ ..]) => {
+                if context == ScriptContext::Tapscript {
+                    return Err(invalid("multi() isn't available in tapscript"));
+                }
+                if keys.len() > MAX_MULTISIG_KEYS {
+                    return Err(invalid("multi() takes at most 20 keys"));
+                }
+                Fragment::Multi(
+                    threshold(k, keys.len())?,
+                    keys.iter().map(|k| key(k)).collect::<Result<_, _>>()?,
+                )
+            }
+            (name, _) => return Err(invalid(&format!("unsupported fragment {}()", name))),
+        })
+    }
+
+    fn type_check(&self, context: ScriptContext) -> Result<Type, BitcoinError> {
+        use Base::*;
+        let ty = |base, z, o, n, d, u| Type {
+            base,
+            z,
+            o,
+            n,
+            d,
+            u,
+        };
+        let expect = |t: Type, ok: bool, what: &str| {
+            if ok {
+                Ok(t)
+            } else {
+                Err(invalid(&format!("type error: {}", what)))
+            }
+        };
+        Ok(match self {
+            Fragment::PkK(_) => ty(K, false, true, true, true, true),
+            Fragment::PkH(_) => ty(K, false, false, true, true, true),
+            Fragment::Older(_) | Fragment::After(_) => ty(B, true, false, false, false, false),
+            Fragment::Multi(..) => ty(B, false, false, true, true, true),
+            Fragment::AndV(x, y) => {
+                let (x, y) = (x.type_check(context)?, y.type_check(context)?);
+                let t = ty(
+                    y.base,
+                    x.z && y.z,
+                    (x.z && y.o) || (x.o && y.z),
+                    x.n || (x.z && y.n),
+                    false,
+                    y.u,
+                );
+                expect(t, x.base == V && y.base != W, "and_v needs V and B/K/V")?
+            }
+            Fragment::AndB(x, y) => {
+                let (x, y) = (x.type_check(context)?, y.type_check(context)?);
+                let t = ty(
+                    B,
+                    x.z && y.z,
+                    (x.z && y.o) || (x.o && y.z),
+                    x.n || (x.z && y.n),
+                    x.d && y.d,
+                    true,
+                );
+                expect(t, x.base == B && y.base == W, "and_b needs B and W")?
+            }
+            Fragment::OrB(x, z) => {
+                let (x, z) = (x.type_check(context)?, z.type_check(context)?);
+                let t = ty(
+                    B,
+                    x.z && z.z,
+                    (x.z && z.o) || (x.o && z.z),
+                    false,
+                    true,
+                    true,
+                );
+                let ok = x.base == B && x.d && z.base == W && z.d;
+                expect(t, ok, "or_b needs Bd and Wd")?
+            }
+            Fragment::OrC(x, z) => {
+                let (x, z) = (x.type_check(context)?, z.type_check(context)?);
+                let t = ty(V, x.z && z.z, x.o && z.z, false, false, false);
+                let ok = x.base == B && x.d && x.u && z.base == V;
+                expect(t, ok, "or_c needs Bdu and V")?
+            }
+            Fragment::OrD(x, z) => {
+                let (x, z) = (x.type_check(context)?, z.type_check(context)?);
+                let t = ty(B, x.z && z.z, x.o && z.z, false, z.d, z.u);
+                let ok = x.base == B && x.d && x.u && z.base == B;
+                expect(t, ok, "or_d needs Bdu and B")?
+            }
+            Fragment::OrI(x, z) => {
+                let (x, z) = (x.type_check(context)?, z.type_check(context)?);
+                let t = ty(x.base, false, x.z && z.z, false, x.d || z.d, x.u && z.u);
+                let ok = x.base == z.base && x.base != W;
+                expect(t, ok, "or_i needs two B, K or V")?
+            }
+            Fragment::Thresh(_, subs) => {
+                let types = subs
+                    .iter()
+                    .map(|sub| sub.type_check(context))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let ok = types
+                    .iter()
+                    .enumerate()
+                    .all(|(i, t)| t.base == if i == 0 { B } else { W } && t.d && t.u);
+                let zs = types.iter().filter(|t| t.z).count();
+                let os = types.iter().filter(|t| t.o).count();
+                let t = ty(
+                    B,
+                    zs == types.len(),
+                    zs + 1 == types.len() && os == 1,
+                    false,
+                    true,
+                    true,
+                );
+                expect(t, ok, "thresh needs Bdu then Wdu")?
+            }
+            Fragment::Alt(x) => {
+                let x = x.type_check(context)?;
+                expect(
+                    ty(W, false, false, false, x.d, x.u),
+                    x.base == B,
+                    "a: needs B",
+                )?
+            }
+            Fragment::Swap(x) => {
+                let x = x.type_check(context)?;
+                let t = ty(W, false, false, false, x.d, x.u);
+                expect(t, x.base == B && x.o, "s: needs Bo")?
+            }
+            Fragment::Check(x) => {
+                let x = x.type_check(context)?;
+                expect(ty(B, false, x.o, x.n, x.d, true), x.base == K, "c: needs K")?
+            }
+            Fragment::DupIf(x) => {
+                let x = x.type_check(context)?;
+                // Only unit under tapscript, where MINIMALIF is consensus
+                let u = context == ScriptContext::Tapscript;
+                let t = ty(B, false, true, true, true, u);
+                expect(t, x.base == V && x.z, "d: needs Vz")?
+            }
+            Fragment::Verify(x) => {
+                let x = x.type_check(context)?;
+                expect(
+                    ty(V, x.z, x.o, x.n, false, false),
+                    x.base == B,
+                    "v: needs B",
+                )?
+            }
+            Fragment::NonZero(x) => {
+                let x = x.type_check(context)?;
+                expect(ty(B, x.z, x.o, x.n, x.d, true), x.base == B, "n: needs B")?
+            }
+        })
+    }
+
+    fn compile(
+        &self,
+        out: &mut Vec<u8>,
+        context: ScriptContext,
+        index: u32,
+    ) -> Result<(), BitcoinError> {
+        let push = |out: &mut Vec<u8>, data: &[u8]| {
+            out.extend(Script::builder().push_slice(data).into_script().bytes)
+        };
+        let push_int = |out: &mut Vec<u8>, n: i64| {
+            out.extend(Script::builder().push_int(n).into_script().bytes)
+        };
+        let op = |out: &mut Vec<u8>, op: Opcode| out.push(op.to_byte());
+
+        match self {
+            Fragment::PkK(key) => push(out, &serialize_key(key, context, index)?),
+            Fragment::PkH(key) => {
+                op(out, Opcode::Dup);
+                op(out, Opcode::Hash160);
+                push(out, &hashes::hash160(&serialize_key(key, context, index)?));
+                op(out, Opcode::EqualVerify);
+            }
+            Fragment::Older(n) => {
+                push_int(out, *n as i64);
+                op(out, Opcode::CheckSequenceVerify);
+            }
+            Fragment::After(n) => {
+                push_int(out, *n as i64);
+                op(out, Opcode::CheckLockTimeVerify);
+            }
+            Fragment::AndV(x, y) => {
+                x.compile(out, context, index)?;
+                y.compile(out, context, index)?;
+            }
+            Fragment::AndB(x, y) => {
+                x.compile(out, context, index)?;
+                y.compile(out, context, index)?;
+                op(out, Opcode::BoolAnd);
+            }
+            Fragment::OrB(x, z) => {
+                x.compile(out, context, index)?;
+                z.compile(out, context, index)?;
+                op(out, Opcode::BoolOr);
+            }
+            Fragment::OrC(x, z) => {
+                x.compile(out, context, index)?;
+                op(out, Opcode::NotIf);
+                z.compile(out, context, index)?;
+                op(out, Opcode::EndIf);
+            }
+            Fragment::OrD(x, z) => {
+                x.compile(out, context, index)?;
+                op(out, Opcode::IfDup);
+                op(out, Opcode::NotIf);
+                z.compile(out, context, index)?;
+                op(out, Opcode::EndIf);
+            }
+            Fragment::OrI(x, z) => {
+                op(out, Opcode::If);
+                x.compile(out, context, index)?;
+                op(out, Opcode::Else);
+                z.compile(out, context, index)?;
+                op(out, Opcode::EndIf);
+            }
+            Fragment::Thresh(k, subs) => {
+                for (i, sub) in subs.iter().enumerate() {
+                    sub.compile(out, context, index)?;
+                    if i > 0 {
+                        op(out, Opcode::Add);
+                    }
+                }
+                push_int(out, *k as i64);
+                op(out, Opcode::Equal);
+            }
+            Fragment::Multi(k, keys) => {
+                push_int(out, *k as i64);
+                for key in keys {
+                    push(out, &serialize_key(key, context, index)?);
+                }
+                push_int(out, keys.len() as i64);
+                op(out, Opcode::CheckMultiSig);
+            }
+            Fragment::Alt(x) => {
+                op(out, Opcode::ToAltStack);
+                x.compile(out, context, index)?;
+                op(out, Opcode::FromAltStack);
+            }
+            Fragment::Swap(x) => {
+                op(out, Opcode::Swap);
+                x.compile(out, context, index)?;
+            }
+            Fragment::Check(x) => {
+                x.compile(out, context, index)?;
+                op(out, Opcode::CheckSig);
+            }
+            Fragment::DupIf(x) => {
+                op(out, Opcode::Dup);
+                op(out, Opcode::If);
+                x.compile(out, context, index)?;
+                op(out, Opcode::EndIf);
+            }
+            Fragment::Verify(x) => {
+                x.compile(out, context, index)?;
+                // Fold into the -VERIFY form of the last opcode if it has one
+                match x.last_opcode() {
+                    Some(Opcode::CheckSig) => {
+                        *out.last_mut().unwrap() = Opcode::CheckSigVerify.to_byte()
+                    }
+                    Some(Opcode::Equal) => *out.last_mut().unwrap() = Opcode::EqualVerify.to_byte(),
+                    Some(Opcode::CheckMultiSig) => {
+                        *out.last_mut().unwrap() = Opcode::CheckMultiSigVerify.to_byte()
+                    }
+                    _ => op(out, Opcode::Verify),
+                }
+            }
+            Fragment::NonZero(x) => {
+                x.compile(out, context, index)?;
+                op(out, Opcode::ZeroNotEqual);
+            }
+        }
+        Ok(())
+    }
+
+    // The opcode this fragment's script ends with, where v: cares
+    fn last_opcode(&self) -> Option<Opcode> {
+        match self {
+            Fragment::Check(_) => Some(Opcode::CheckSig),
+            Fragment::Thresh(..) => Some(Opcode::Equal),
+            Fragment::Multi(..) => Some(Opcode::CheckMultiSig),
+            Fragment::AndV(_, y) | Fragment::Swap(y) => y.last_opcode(),
+            _ => None,
+        }
+    }
+
+    fn sizes(&self, context: ScriptContext) -> Sizes {
+        // Each stack item costs its length byte plus its data
+        let (sig, key) = match context {
+            ScriptContext::Segwitv0 => (1 + 73, 1 + 33),
+            ScriptContext::Tapscript => (1 + 65, 1 + 32),
+        };
+        // OP_IF branch selectors: 0x01 to take it, empty to skip
+        let (one, zero) = (2, 1);
+        let both = |x: &Fragment, y: &Fragment| (x.sizes(context), y.sizes(context));
+
+        match self {
+            Fragment::PkK(_) => Sizes {
+                sat: Some(sig),
+                dsat: Some(zero),
+            },
+            Fragment::PkH(_) => Sizes {
+                sat: Some(sig + key),
+                dsat: Some(zero + key),
+            },
+            Fragment::Older(_) | Fragment::After(_) => Sizes {
+                sat: Some(0),
+                dsat: None,
+            },
+            // CHECKMULTISIG's extra dummy item, then the signatures
+            Fragment::Multi(k, _) => Sizes {
+                sat: Some(zero + k * sig),
+                dsat: Some(zero + k * zero),
+            },
+            Fragment::AndV(x, y) => {
+                let (x, y) = both(x, y);
+                Sizes {
+                    sat: add(x.sat, y.sat),
+                    dsat: None,
+                }
+            }
+            Fragment::AndB(x, y) => {
+                let (x, y) = both(x, y);
+                Sizes {
+                    sat: add(x.sat, y.sat),
+                    dsat: add(x.dsat, y.dsat),
+                }
+            }
+            Fragment::OrB(x, z) => {
+                let (x, z) = both(x, z);
+                Sizes {
+                    sat: max(add(x.sat, z.dsat), add(x.dsat, z.sat)),
+                    dsat: add(x.dsat, z.dsat),
+                }
+            }
+            Fragment::OrC(x, z) => {
+                let (x, z) = both(x, z);
+                Sizes {
+                    sat: max(x.sat, add(x.dsat, z.sat)),
+                    dsat: None,
+                }
+            }
+            Fragment::OrD(x, z) => {
+                let (x, z) = both(x, z);
+                Sizes {
+                    sat: max(x.sat, add(x.dsat, z.sat)),
+                    dsat: add(x.dsat, z.dsat),
+                }
+            }
+            Fragment::OrI(x, z) => {
+                let (x, z) = both(x, z);
+                Sizes {
+                    sat: max(add(x.sat, Some(one)), add(z.sat, Some(zero))),
+                    dsat: max(add(x.dsat, Some(one)), add(z.dsat, Some(zero))),
+                }
+            }
+            Fragment::Thresh(k, subs) => {
+                // Dissatisfy everything, then satisfy the k where that
+                // costs the most extra
+                let sizes: Vec<Sizes> = subs.iter().map(|sub| sub.sizes(context)).collect();
+                let dsat: Option<usize> = sizes.iter().map(|s| s.dsat).sum();
+                let mut extra: Vec<Option<usize>> = sizes
+                    .iter()
+                    .map(|s| Some(s.sat?.saturating_sub(s.dsat?)))
+                    .collect();
+                extra.sort_unstable_by(|a, b| b.cmp(a));
+                let sat = dsat.and_then(|dsat| {
+                    extra[..*k]
+                        .iter()
+                        .try_fold(dsat, |acc, e| Some(acc + (*e)?))
+                });
+                Sizes { sat, dsat }
+            }
+            Fragment::Alt(x) | Fragment::Swap(x) | Fragment::Check(x) | Fragment::NonZero(x) => {
+                x.sizes(context)
+            }
+            Fragment::DupIf(x) => Sizes {
+                sat: add(x.sizes(context).sat, Some(one)),
+                dsat: Some(zero),
+            },
+            Fragment::Verify(x) => Sizes {
+                sat: x.sizes(context).sat,
+                dsat: None,
+            },
+        }
+    }
+
+    fn lift(&self) -> Policy {
+        let pair = |k, x: &Fragment, y: &Fragment| Policy::Threshold(k, vec![x.lift(), y.lift()]);
+        match self {
+            Fragment::PkK(key) | Fragment::PkH(key) => Policy::Key(key.clone()),
+            Fragment::Older(n) => Policy::Older(*n),
+            Fragment::After(n) => Policy::After(*n),
+            Fragment::AndV(x, y) | Fragment::AndB(x, y) => pair(2, x, y),
+            Fragment::OrB(x, z)
+            | Fragment::OrC(x, z)
+            | Fragment::OrD(x, z)
+            | Fragment::OrI(x, z) => pair(1, x, z),
+            Fragment::Thresh(k, subs) => {
+                Policy::Threshold(*k, subs.iter().map(Fragment::lift).collect())
+            }
+            Fragment::Multi(k, keys) => {
+                Policy::Threshold(*k, keys.iter().cloned().map(Policy::Key).collect())
+            }
+            Fragment::Alt(x)
+            | Fragment::Swap(x)
+            | Fragment::Check(x)
+            | Fragment::DupIf(x)
+            | Fragment::Verify(x)
+            | Fragment::NonZero(x) => x.lift(),
+        }
+    }
+
+    fn keys(&self) -> Vec<&DescriptorKey> {
+        match self {
+            Fragment::PkK(key) | Fragment::PkH(key) => vec![key],
+            Fragment::Older(_) | Fragment::After(_) => vec![],
+            Fragment::Multi(_, keys) => keys.iter().collect(),
+            Fragment::AndV(x, y)
+            | Fragment::AndB(x, y)
+            | Fragment::OrB(x, y)
+            | Fragment::OrC(x, y)
+            | Fragment::OrD(x, y)
+            | Fragment::OrI(x, y) => {
+                let mut keys = x.keys();
+                keys.extend(y.keys());
+                keys
+            }
+            Fragment::Thresh(_, subs) => subs.iter().flat_map(Fragment::keys).collect(),
+            Fragment::Alt(x)
+            | Fragment::Swap(x)
+            | Fragment::Check(x)
+            | Fragment::DupIf(x)
+            | Fragment::Verify(x)
+            | Fragment::NonZero(x) => x.keys(),
+        }
+    }
+
+    fn wrapper(&self) -> Option<(char, &Fragment)> {
+        match self {
+            Fragment::Alt(x) => Some(('a', x)),
+            Fragment::Swap(x) => Some(('s', x)),
+            Fragment::Check(x) if !matches!(**x, Fragment::PkK(_) | Fragment::PkH(_)) => {
+                Some(('c', x))
+            }
+            Fragment::DupIf(x) => Some(('d', x)),
+            Fragment::Verify(x) => Some(('v', x)),
+            Fragment::NonZero(x) => Some(('n', x)),
+            _ => None,
+        }
+    }
+}
+
+fn key_context(context: ScriptContext) -> Context {
+    match context {
+        ScriptContext::Segwitv0 => Context::Segwit,
+        ScriptContext::Tapscript => Context::Taproot,
+    }
+}
+
+fn serialize_key(
+    key: &DescriptorKey,
+    context: ScriptContext,
+    index: u32,
+) -> Result<Vec<u8>, BitcoinError> {
+    match context {
+        ScriptContext::Segwitv0 => Ok(key.derive(index)?.to_bytes()),
+        ScriptContext::Tapscript => Ok(key.derive_x_only(index)?.serialize().to_vec()),
+    }
+}
+
+fn timelock(s: &str) -> Result<u32, BitcoinError> {
+    match s.parse() {
+        Ok(n @ 1..=MAX_TIMELOCK) => Ok(n),
+        _ => Err(invalid(&format!("bad timelock {:?}", s))),
+    }
+}
+
+fn threshold(s: &str, n: usize) -> Result<usize, BitcoinError> {
+    match s.parse() {
+        Ok(k) if k >= 1 && k <= n => Ok(k),
+        _ => Err(invalid(&format!(
+            "threshold {:?} out of range for {} items",
+            s, n
+        ))),
+    }
+}
+
+fn invalid(reason: &str) -> BitcoinError {
+    BitcoinError::InvalidMiniscript(reason.into())
+}
+
+impl fmt::Display for Fragment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Collapse chains of wrappers into one prefix, e.g. "sv:"
+        if let Some((letter, mut inner)) = self.wrapper() {
+            let mut letters = letter.to_string();
+            while let Some((letter, next)) = inner.wrapper() {
+                letters.push(letter);
+                inner = next;
+            }
+            return write!(f, "{}:{}", letters, inner);
+        }
+        let join = |items: Vec<String>| items.join(",");
+        match self {
+            Fragment::PkK(key) => write!(f, "pk_k({})", key),
+            Fragment::PkH(key) => write!(f, "pk_h({})", key),
+            Fragment::Older(n) => write!(f, "older({})", n),
+            Fragment::After(n) => write!(f, "after({})", n),
+            Fragment::AndV(x, y) => write!(f, "and_v({},{})", x, y),
+            Fragment::AndB(x, y) => write!(f, "and_b({},{})", x, y),
+            Fragment::OrB(x, z) => write!(f, "or_b({},{})", x, z),
+            Fragment::OrC(x, z) => write!(f, "or_c({},{})", x, z),
+            Fragment::OrD(x, z) => write!(f, "or_d({},{})", x, z),
+            Fragment::OrI(x, z) => write!(f, "or_i({},{})", x, z),
+            Fragment::Thresh(k, subs) => write!(
+                f,
+                "thresh({},{})",
+                k,
+                join(subs.iter().map(ToString::to_string).collect())
+            ),
+            Fragment::Multi(k, keys) => write!(
+                f,
+                "multi({},{})",
+                k,
+                join(keys.iter().map(ToString::to_string).collect())
+            ),
+            // c:pk_k / c:pk_h print as their shorthands
+            Fragment::Check(x) => match &**x {
+                Fragment::PkK(key) => write!(f, "pk({})", key),
+                Fragment::PkH(key) => write!(f, "pkh({})", key),
+                _ => unreachable!("handled as a wrapper"),
+            },
+            _ => unreachable!("handled as a wrapper"),
+        }
+    }
+}
+
+impl fmt::Display for Miniscript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.fragment)
+    }
+}
+
+// Standalone miniscripts parse as segwit v0; use `parse` for tapscript
+impl FromStr for Miniscript {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Miniscript::parse(s, ScriptContext::Segwitv0)
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Policy::Key(key) => write!(f, "pk({})", key),
+            Policy::Older(n) => write!(f, "older({})", n),
+            Policy::After(n) => write!(f, "after({})", n),
+            Policy::Threshold(k, subs) => {
+                let name = match *k {
+                    1 if subs.len() == 2 => "or",
+                    k if k == subs.len() && k == 2 => "and",
+                    _ => "",
+                };
+                let subs: Vec<String> = subs.iter().map(ToString::to_string).collect();
+                if name.is_empty() {
+                    write!(f, "thresh({},{})", k, subs.join(","))
+                } else {
+                    write!(f, "{}({})", name, subs.join(","))
+                }
+            }
+        }
+    }
+}
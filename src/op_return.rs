@@ -0,0 +1,90 @@
+use crate::Script;
+use crate::opcodes::Opcode;
+use crate::script::Instruction;
+use serde::{Deserialize, Serialize};
+
+// Best-effort guess at what an OP_RETURN output carries. Nothing on chain
+// says which protocol wrote the data, so this goes by the markers the
+// common ones start with:
+//
+// - Omni Layer (USDT's original home): a single push starting with "omni",
+//   followed by a 2-byte version and 2-byte message type
+// - Runes: OP_RETURN OP_13, then pushes whose concatenation is the
+//   runestone's varint-encoded fields. Any other opcode after the marker
+//   (even OP_1..OP_16) makes it Unknown, as ord treats those as invalid
+// - Text: pushes that decode as printable UTF-8, e.g. notes and messages
+//
+// Anything else comes back as Unknown. Every variant keeps the raw payload:
+// the pushed bytes joined together, with any protocol marker removed.
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(tag = "protocol", rename_all = "snake_case")]
+pub enum OpReturnData {
+    Omni { payload: Vec<u8> },
+    Runestone { payload: Vec<u8> },
+    Text { text: String, payload: Vec<u8> },
+    // No recognised marker, or not push-only (then `payload` is every byte
+    // after the OP_RETURN)
+    Unknown { payload: Vec<u8> },
+}
+
+const OMNI_MARKER: &[u8] = b"omni";
+
+impl OpReturnData {
+    pub fn payload(&self) -> &[u8] {
+        match self {
+            OpReturnData::Omni { payload }
+            | OpReturnData::Runestone { payload }
+            | OpReturnData::Text { payload, .. }
+            | OpReturnData::Unknown { payload } => payload,
+        }
+    }
+}
+
+// None if the script isn't an OP_RETURN output at all
+pub fn classify_op_return(script: &Script) -> Option<OpReturnData> {
+    if !script.is_op_return() {
+        return None;
+    }
+    let unknown = || OpReturnData::Unknown {
+        payload: script.bytes[1..].to_vec(),
+    };
+    let Ok(instructions) = script.instructions().skip(1).collect::<Result<Vec<_>, _>>() else {
+        return Some(unknown());
+    };
+
+    // OP_13 is the runestone marker; everything after it must be data
+    let (is_runestone, data) = match instructions.split_first() {
+        Some((Instruction::Op(Opcode::Op13), rest)) => (true, rest),
+        _ => (false, instructions.as_slice()),
+    };
+    let mut payload = Vec::new();
+    for instruction in data {
+        match instruction {
+            Instruction::PushBytes(bytes) => payload.extend(*bytes),
+            Instruction::Op(_) => return Some(unknown()),
+        }
+    }
+
+    if is_runestone {
+        return Some(OpReturnData::Runestone { payload });
+    }
+    if let [Instruction::PushBytes(bytes)] = data
+        && let Some(rest) = bytes.strip_prefix(OMNI_MARKER)
+    {
+        return Some(OpReturnData::Omni {
+            payload: rest.to_vec(),
+        });
+    }
+    match std::str::from_utf8(&payload) {
+        Ok(text)
+            if !text.is_empty() && text.chars().all(|c| !c.is_control() || c.is_whitespace()) =>
+        {
+            Some(OpReturnData::Text {
+                text: text.to_string(),
+                payload,
+            })
+        }
+        _ => Some(OpReturnData::Unknown { payload }),
+    }
+}
@@ -0,0 +1,499 @@
+use crate::opcodes::Opcode;
+use crate::{BitcoinError, Script};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// A script is a flat byte string, but it's really a sequence of
+// instructions: either an opcode, or a push of some data onto the stack.
+// Pushes come in four forms - 0x01-0x4b push that many following bytes, and
+// OP_PUSHDATA1/2/4 give the length in the next 1/2/4 bytes (little-endian).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Instruction<'a> {
+    PushBytes(&'a [u8]),
+    Op(Opcode),
+}
+
+// Walks a script one instruction at a time. A push that claims more bytes
+// than the script has left yields a MalformedPush error, after which the
+// iterator stops.
+#[derive(Debug, Clone)]
+pub struct Instructions<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Script {
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions {
+            bytes: &self.bytes,
+            pos: 0,
+        }
+    }
+}
+
+impl<'a> Instructions<'a> {
+    // Offset of the next instruction in the script
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn take(&mut self, start: usize, len: usize) -> Result<&'a [u8], BitcoinError> {
+        let available = self.bytes.len() - self.pos;
+        if len > available {
+            // Don't hand out anything after a bad push
+            self.pos = self.bytes.len();
+            return Err(BitcoinError::MalformedPush {
+                offset: start,
+                needed: len,
+                available,
+            });
+        }
+        let data = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(data)
+    }
+
+    fn read_push(&mut self, start: usize, width: usize) -> Result<&'a [u8], BitcoinError> {
+        let len_bytes = self.take(start, width)?;
+        let mut buf = [0u8; 4];
+        buf[..width].copy_from_slice(len_bytes);
+        let len = u32::from_le_bytes(buf) as usize;
+        self.take(start, len)
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<Instruction<'a>, BitcoinError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.pos;
+        let byte = *self.bytes.get(start)?;
+        self.pos += 1;
+
+        let push = match Opcode::from_byte(byte) {
+            Opcode::PushBytes(n) => self.take(start, n as usize),
+            Opcode::PushData1 => self.read_push(start, 1),
+            Opcode::PushData2 => self.read_push(start, 2),
+            Opcode::PushData4 => self.read_push(start, 4),
+            op => return Some(Ok(Instruction::Op(op))),
+        };
+        Some(push.map(Instruction::PushBytes))
+    }
+}
+
+// Consensus limits from Core's script.h: the interpreter refuses scripts
+// longer than MAX_SCRIPT_SIZE, and any push bigger than
+// MAX_SCRIPT_ELEMENT_SIZE fails as soon as it's executed
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+
+impl Script {
+    // Checks the script could actually be run: it tokenizes all the way to
+    // the end, fits in MAX_SCRIPT_SIZE, and no push exceeds
+    // MAX_SCRIPT_ELEMENT_SIZE. Core only hits these during execution, so an
+    // output script that breaks them is still valid on chain - it's just
+    // unspendable.
+    pub fn validate(&self) -> Result<(), BitcoinError> {
+        if self.bytes.len() > MAX_SCRIPT_SIZE {
+            return Err(BitcoinError::ScriptTooLarge {
+                len: self.bytes.len(),
+                limit: MAX_SCRIPT_SIZE,
+            });
+        }
+        let mut instructions = self.instructions();
+        loop {
+            let offset = instructions.position();
+            match instructions.next() {
+                None => return Ok(()),
+                Some(Err(err)) => return Err(err),
+                Some(Ok(Instruction::PushBytes(data))) if data.len() > MAX_SCRIPT_ELEMENT_SIZE => {
+                    return Err(BitcoinError::OversizedPush {
+                        offset,
+                        len: data.len(),
+                        limit: MAX_SCRIPT_ELEMENT_SIZE,
+                    });
+                }
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+}
+
+// The standard scriptPubKey templates, named as Core's RPCs name them
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptType {
+    #[serde(rename = "pubkey")]
+    P2pk,
+    #[serde(rename = "pubkeyhash")]
+    P2pkh,
+    #[serde(rename = "scripthash")]
+    P2sh,
+    #[serde(rename = "witness_v0_keyhash")]
+    P2wpkh,
+    #[serde(rename = "witness_v0_scripthash")]
+    P2wsh,
+    #[serde(rename = "witness_v1_taproot")]
+    P2tr,
+    // Bare m-of-n CHECKMULTISIG
+    Multisig {
+        required: u8,
+        keys: u8,
+    },
+    #[serde(rename = "nulldata")]
+    OpReturn,
+    // A valid witness program for a version/length nothing defines yet
+    WitnessUnknown {
+        version: u8,
+    },
+    #[serde(rename = "nonstandard")]
+    NonStandard,
+}
+
+impl fmt::Display for ScriptType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ScriptType::P2pk => "pubkey",
+            ScriptType::P2pkh => "pubkeyhash",
+            ScriptType::P2sh => "scripthash",
+            ScriptType::P2wpkh => "witness_v0_keyhash",
+            ScriptType::P2wsh => "witness_v0_scripthash",
+            ScriptType::P2tr => "witness_v1_taproot",
+            ScriptType::Multisig { .. } => "multisig",
+            ScriptType::OpReturn => "nulldata",
+            ScriptType::WitnessUnknown { .. } => "witness_unknown",
+            ScriptType::NonStandard => "nonstandard",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Template checks work on the raw bytes, since every template has a fixed
+// layout and this is how Core matches them too
+impl Script {
+    // OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+    pub fn is_p2pkh(&self) -> bool {
+        let b = &self.bytes;
+        b.len() == 25
+            && b[0] == 0x76
+            && b[1] == 0xa9
+            && b[2] == 0x14
+            && b[23] == 0x88
+            && b[24] == 0xac
+    }
+
+    // OP_HASH160 <20 bytes> OP_EQUAL
+    pub fn is_p2sh(&self) -> bool {
+        let b = &self.bytes;
+        b.len() == 23 && b[0] == 0xa9 && b[1] == 0x14 && b[22] == 0x87
+    }
+
+    // <33 or 65 byte pubkey> OP_CHECKSIG
+    pub fn is_p2pk(&self) -> bool {
+        let b = &self.bytes;
+        match b.len() {
+            35 => b[0] == 0x21 && b[34] == 0xac,
+            67 => b[0] == 0x41 && b[66] == 0xac,
+            _ => false,
+        }
+    }
+
+    // OP_0 <20 bytes>
+    pub fn is_p2wpkh(&self) -> bool {
+        matches!(self.witness_program(), Some((0, program)) if program.len() == 20)
+    }
+
+    // OP_0 <32 bytes>
+    pub fn is_p2wsh(&self) -> bool {
+        matches!(self.witness_program(), Some((0, program)) if program.len() == 32)
+    }
+
+    // OP_1 <32 byte x-only key>
+    pub fn is_p2tr(&self) -> bool {
+        matches!(self.witness_program(), Some((1, program)) if program.len() == 32)
+    }
+
+    // Provably unspendable data carrier
+    pub fn is_op_return(&self) -> bool {
+        self.bytes.first() == Some(&0x6a)
+    }
+
+    // BIP141: a version opcode (OP_0..OP_16) followed by a single direct push
+    // of 2 to 40 bytes, and nothing else
+    pub fn witness_program(&self) -> Option<(u8, &[u8])> {
+        let b = &self.bytes;
+        if b.len() < 4 || b.len() > 42 || b[1] as usize != b.len() - 2 {
+            return None;
+        }
+        let version = Opcode::from_byte(b[0]).small_int()?;
+        Some((version, &b[2..]))
+    }
+
+    pub fn classify(&self) -> ScriptType {
+        if self.is_p2pkh() {
+            ScriptType::P2pkh
+        } else if self.is_p2sh() {
+            ScriptType::P2sh
+        } else if self.is_op_return() {
+            ScriptType::OpReturn
+        } else if let Some((version, program)) = self.witness_program() {
+            match (version, program.len()) {
+                (0, 20) => ScriptType::P2wpkh,
+                (0, 32) => ScriptType::P2wsh,
+                (1, 32) => ScriptType::P2tr,
+                // v0 only allows 20 or 32 bytes
+                (0, _) => ScriptType::NonStandard,
+                (version, _) => ScriptType::WitnessUnknown { version },
+            }
+        } else if self.is_p2pk() {
+            ScriptType::P2pk
+        } else if let Some((required, keys)) = self.multisig_params() {
+            ScriptType::Multisig { required, keys }
+        } else {
+            ScriptType::NonStandard
+        }
+    }
+
+    // OP_m <pubkey>... OP_n OP_CHECKMULTISIG with 1 <= m <= n <= 16
+    fn multisig_params(&self) -> Option<(u8, u8)> {
+        let instructions: Vec<Instruction> = self.instructions().collect::<Result<_, _>>().ok()?;
+        let (first, rest) = instructions.split_first()?;
+        let (last, rest) = rest.split_last()?;
+        let (n_op, keys) = rest.split_last()?;
+
+        let required = match first {
+            Instruction::Op(op) => op.small_int()?,
+            _ => return None,
+        };
+        let total = match n_op {
+            Instruction::Op(op) => op.small_int()?,
+            _ => return None,
+        };
+        let keys_ok = keys.iter().all(
+            |k| matches!(k, Instruction::PushBytes(key) if key.len() == 33 || key.len() == 65),
+        );
+        if *last != Instruction::Op(Opcode::CheckMultiSig)
+            || !keys_ok
+            || keys.len() != total as usize
+            || required == 0
+            || required > total
+        {
+            return None;
+        }
+        Some((required, total))
+    }
+}
+
+// Builds a script instruction by instruction, always picking the shortest
+// encoding for each push - the same rule Core enforces for standardness
+// (SCRIPT_VERIFY_MINIMALDATA), so scripts built here won't be rejected for
+// using a bigger push than necessary.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ScriptBuilder {
+    bytes: Vec<u8>,
+}
+
+impl Script {
+    pub fn builder() -> ScriptBuilder {
+        ScriptBuilder::new()
+    }
+}
+
+impl ScriptBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_opcode(mut self, op: Opcode) -> Self {
+        self.bytes.push(op.to_byte());
+        self
+    }
+
+    // Pushes `data`, choosing OP_0 / OP_1..OP_16 / OP_1NEGATE where one
+    // exists for it, otherwise a direct push or OP_PUSHDATA1/2/4 by length
+    pub fn push_slice(mut self, data: &[u8]) -> Self {
+        match data {
+            [] => self.bytes.push(Opcode::Op0.to_byte()),
+            [n @ 1..=16] => self.bytes.push(0x50 + n),
+            [0x81] => self.bytes.push(Opcode::Op1Negate.to_byte()),
+            _ => {
+                let len = data.len();
+                if len <= 0x4b {
+                    self.bytes.push(len as u8);
+                } else if len <= 0xff {
+                    self.bytes.push(Opcode::PushData1.to_byte());
+                    self.bytes.push(len as u8);
+                } else if len <= 0xffff {
+                    self.bytes.push(Opcode::PushData2.to_byte());
+                    self.bytes.extend_from_slice(&(len as u16).to_le_bytes());
+                } else {
+                    self.bytes.push(Opcode::PushData4.to_byte());
+                    self.bytes.extend_from_slice(&(len as u32).to_le_bytes());
+                }
+                self.bytes.extend_from_slice(data);
+            }
+        }
+        self
+    }
+
+    // Script numbers: small ones have their own opcodes, anything else is
+    // pushed in the minimal sign-magnitude little-endian form
+    pub fn push_int(self, n: i64) -> Self {
+        match n {
+            -1 => self.push_opcode(Opcode::Op1Negate),
+            0..=16 => self.push_opcode(Opcode::from_small_int(n as u8).unwrap()),
+            _ => self.push_slice(&script_num(n)),
+        }
+    }
+
+    // A serialized public key (33 bytes compressed, 65 uncompressed, or 32
+    // x-only for tapscript) is just a data push
+    pub fn push_key(self, key: impl AsRef<[u8]>) -> Self {
+        self.push_slice(key.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn into_script(self) -> Script {
+        Script::new(self.bytes)
+    }
+}
+
+// CScriptNum serialization: magnitude little-endian, with the sign in the
+// top bit of the last byte (adding a byte if the magnitude already uses it)
+pub(crate) fn script_num(n: i64) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut abs = n.unsigned_abs();
+    while abs > 0 {
+        out.push((abs & 0xff) as u8);
+        abs >>= 8;
+    }
+    if let Some(last) = out.last_mut() {
+        if *last & 0x80 != 0 {
+            out.push(if n < 0 { 0x80 } else { 0x00 });
+        } else if n < 0 {
+            *last |= 0x80;
+        }
+    }
+    out
+}
+
+// Core-style ASM, as printed by `decodescript`: opcodes by name, small
+// numbers (OP_0, OP_1..OP_16, OP_1NEGATE and pushes of up to 4 bytes) as
+// decimal, and larger pushes as bare hex. A push that runs off the end is
+// shown as "[error]" and nothing after it is printed.
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, instruction) in self.instructions().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            match instruction {
+                Ok(Instruction::PushBytes(data)) if data.len() <= 4 => {
+                    write!(f, "{}", script_num_decode(data))?
+                }
+                Ok(Instruction::PushBytes(data)) => write!(f, "{}", hex::encode(data))?,
+                Ok(Instruction::Op(Opcode::Op1Negate)) => write!(f, "-1")?,
+                Ok(Instruction::Op(op)) => match op.small_int() {
+                    Some(n) => write!(f, "{}", n)?,
+                    None => write!(f, "{}", op)?,
+                },
+                Err(_) => return write!(f, "[error]"),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Script {
+    pub fn to_asm(&self) -> String {
+        self.to_string()
+    }
+
+    // Parse the ASM form back into a script. Accepts opcode names with or
+    // without the OP_ prefix (including OP_0..OP_16, OP_FALSE and OP_TRUE),
+    // decimal numbers (pushed as script numbers) and hex (pushed as data). Because the ASM form prints 4-byte-or-smaller
+    // pushes as numbers and doesn't record which push opcode was used,
+    // to_asm -> from_asm gives back the minimally-encoded version of a
+    // script, not necessarily the exact original bytes.
+    pub fn from_asm(asm: &str) -> Result<Script, BitcoinError> {
+        let mut builder = ScriptBuilder::new();
+        for token in asm.split_whitespace() {
+            builder = if let Some(op) = parse_opcode_name(token) {
+                builder.push_opcode(op)
+            } else if let Some(n) = parse_asm_number(token) {
+                builder.push_int(n)
+            } else if let Ok(data) = hex::decode(token) {
+                builder.push_slice(&data)
+            } else {
+                return Err(BitcoinError::InvalidAsm {
+                    token: token.to_string(),
+                });
+            };
+        }
+        Ok(builder.into_script())
+    }
+}
+
+fn parse_opcode_name(token: &str) -> Option<Opcode> {
+    if let Some(byte) = token.strip_prefix("OP_UNKNOWN_") {
+        return match byte.parse::<u8>().ok()? {
+            b @ 0xbb..=0xfe => Some(Opcode::Unknown(b)),
+            _ => None,
+        };
+    }
+    let op = match token {
+        "OP_FALSE" => Opcode::Op0,
+        "OP_TRUE" => Opcode::Op1,
+        _ if token.starts_with("OP_") => Opcode::from_name(token)?,
+        // Without the prefix, "0".."16" are numbers (to_asm's form), which
+        // parse_asm_number pushes the same way
+        _ => Opcode::from_name(&format!("OP_{}", token)).filter(|op| op.small_int().is_none())?,
+    };
+    if matches!(
+        op,
+        Opcode::PushData1 | Opcode::PushData2 | Opcode::PushData4
+    ) {
+        // A bare PUSHDATA has no data attached; hex tokens handle pushes
+        return None;
+    }
+    Some(op)
+}
+
+// Decimal tokens as Core prints them: no leading zeros, and within the
+// 4-byte script number range. Anything else that looks numeric (e.g.
+// "0011") is a hex push.
+fn parse_asm_number(token: &str) -> Option<i64> {
+    let digits = token.strip_prefix('-').unwrap_or(token);
+    if digits.is_empty()
+        || !digits.bytes().all(|b| b.is_ascii_digit())
+        || (digits.len() > 1 && digits.starts_with('0'))
+    {
+        return None;
+    }
+    let n: i64 = token.parse().ok()?;
+    (n.abs() <= 0x7fff_ffff).then_some(n)
+}
+
+// Inverse of script_num, for the up-to-4-byte pushes shown in ASM
+fn script_num_decode(data: &[u8]) -> i64 {
+    let Some((&last, _)) = data.split_last() else {
+        return 0;
+    };
+    let mut n: i64 = 0;
+    for (i, &byte) in data.iter().enumerate() {
+        n |= (byte as i64) << (8 * i);
+    }
+    if last & 0x80 != 0 {
+        // Clear the sign bit and negate
+        -(n & !(0x80i64 << (8 * (data.len() - 1))))
+    } else {
+        n
+    }
+}
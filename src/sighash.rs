@@ -0,0 +1,465 @@
+use crate::opcodes::Opcode;
+use crate::script::Instruction;
+use crate::{
+    Amount, BitcoinError, BitcoinTransaction, ConsensusEncodable, Script, TransactionInput, TxOut,
+    hashes,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// The sighash type says which parts of the transaction a signature commits
+// to. It's appended to every ECDSA signature as one byte, and to the data
+// being hashed as four.
+//
+// - ALL: every input and output
+// - NONE: every input, no outputs (anyone can redirect the funds)
+// - SINGLE: every input, and only the output at the same index as this input
+// - ANYONECANPAY (flag, or'd with one of the above): only this input, so
+//   others can add their own
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
+pub struct SighashType(pub u32);
+
+impl SighashType {
+    // Taproot only: commits to the same data as ALL, and the signature
+    // goes without a type byte
+    pub const DEFAULT: SighashType = SighashType(0x00);
+    pub const ALL: SighashType = SighashType(0x01);
+    pub const NONE: SighashType = SighashType(0x02);
+    pub const SINGLE: SighashType = SighashType(0x03);
+    pub const ANYONECANPAY: u32 = 0x80;
+    pub const ALL_ANYONECANPAY: SighashType = SighashType(0x81);
+    pub const NONE_ANYONECANPAY: SighashType = SighashType(0x82);
+    pub const SINGLE_ANYONECANPAY: SighashType = SighashType(0x83);
+
+    pub fn anyone_can_pay(self) -> bool {
+        self.0 & Self::ANYONECANPAY != 0
+    }
+
+    // The type with ANYONECANPAY masked off. Legacy sighash only looks at the
+    // low 5 bits, so anything that isn't NONE or SINGLE there acts as ALL.
+    pub fn base_type(self) -> SighashType {
+        match self.0 & 0x1f {
+            0x02 => SighashType::NONE,
+            0x03 => SighashType::SINGLE,
+            _ => SighashType::ALL,
+        }
+    }
+
+    // One of the six defined values (Core's IsDefinedHashtypeSignature)
+    pub fn is_standard(self) -> bool {
+        matches!(self.0 & !Self::ANYONECANPAY, 0x01..=0x03)
+    }
+
+    // Taproot rejects anything undefined outright, but adds DEFAULT
+    pub fn is_valid_taproot(self) -> bool {
+        self == Self::DEFAULT || self.is_standard()
+    }
+}
+
+impl From<u8> for SighashType {
+    fn from(value: u8) -> Self {
+        SighashType(value as u32)
+    }
+}
+
+impl fmt::Display for SighashType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let base = match self.0 & !Self::ANYONECANPAY {
+            0x00 if *self == Self::DEFAULT => "DEFAULT",
+            0x01 => "ALL",
+            0x02 => "NONE",
+            0x03 => "SINGLE",
+            _ => return write!(f, "{:#04x}", self.0),
+        };
+        if self.anyone_can_pay() {
+            write!(f, "{}|ANYONECANPAY", base)
+        } else {
+            write!(f, "{}", base)
+        }
+    }
+}
+
+// What the original algorithm returns when asked to sign something it
+// can't: an input index past the end, or SIGHASH_SINGLE without a matching
+// output. It's the number 1 as a little-endian uint256, and because nothing
+// checked, a signature over it is valid - the well-known SIGHASH_SINGLE bug,
+// which is now consensus and has to be reproduced exactly.
+const SIGHASH_ONE: [u8; 32] = {
+    let mut one = [0u8; 32];
+    one[0] = 1;
+    one
+};
+
+impl BitcoinTransaction {
+    // The pre-segwit signature hash (Core's SignatureHash with
+    // SigVersion::BASE), for signing or checking input `input_index`.
+    //
+    // `script_code` is the script being executed: the previous output's
+    // scriptPubKey, or the redeem script for P2SH. The caller is expected to
+    // have already removed the signature itself from it (FindAndDelete) if
+    // it's in there; OP_CODESEPARATORs are stripped here. Pass the part after
+    // the last executed OP_CODESEPARATOR if there is one.
+    pub fn legacy_signature_hash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        sighash_type: impl Into<SighashType>,
+    ) -> [u8; 32] {
+        let sighash_type = sighash_type.into();
+        let base = sighash_type.base_type();
+        if input_index >= self.inputs.len()
+            || (base == SighashType::SINGLE && input_index >= self.outputs.len())
+        {
+            return SIGHASH_ONE;
+        }
+
+        let script_code = strip_code_separators(script_code);
+        let inputs: Vec<TransactionInput> = self
+            .inputs
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| !sighash_type.anyone_can_pay() || i == input_index)
+            .map(|(i, input)| {
+                let mut copy = TransactionInput::new(
+                    input.previous_output.clone(),
+                    Script::default(),
+                    input.sequence,
+                );
+                if i == input_index {
+                    copy.script_sig = script_code.clone();
+                } else if base != SighashType::ALL {
+                    // With NONE or SINGLE, other inputs' sequences aren't
+                    // committed to, so their owners can still update them
+                    copy.sequence = 0;
+                }
+                copy
+            })
+            .collect();
+
+        let outputs: Vec<TxOut> = match base {
+            SighashType::NONE => Vec::new(),
+            // Outputs before ours are blanked: value -1, empty script
+            SighashType::SINGLE => (0..=input_index)
+                .map(|i| {
+                    if i == input_index {
+                        self.outputs[i].clone()
+                    } else {
+                        TxOut::new(u64::MAX, Script::default())
+                    }
+                })
+                .collect(),
+            _ => self.outputs.clone(),
+        };
+
+        let copy = BitcoinTransaction {
+            version: self.version,
+            inputs,
+            outputs,
+            lock_time: self.lock_time,
+        };
+        let mut preimage = copy.to_bytes_without_witness();
+        write(&mut preimage, &sighash_type.0);
+        hashes::sha256d(&preimage)
+    }
+}
+
+// Signs several inputs of one transaction without redoing the shared work.
+// The BIP143 (segwit v0) digest hashes all prevouts, all sequences and all
+// outputs; those are the same for every input, so they're computed on first
+// use and reused - otherwise signing n inputs would hash the whole
+// transaction n times.
+#[derive(Debug, Clone)]
+pub struct SighashCache<'tx> {
+    tx: &'tx BitcoinTransaction,
+    segwit_v0: Option<SegwitV0Midstates>,
+    taproot: Option<TaprootMidstates>,
+    spent: Option<SpentOutputsMidstates>,
+}
+
+// sha256d of the concatenated outpoints, sequences and outputs
+#[derive(Debug, Clone, Copy)]
+struct SegwitV0Midstates {
+    prevouts: [u8; 32],
+    sequences: [u8; 32],
+    outputs: [u8; 32],
+}
+
+// BIP341 hashes the same lists, but with a single SHA256
+#[derive(Debug, Clone, Copy)]
+struct TaprootMidstates {
+    prevouts: [u8; 32],
+    sequences: [u8; 32],
+    outputs: [u8; 32],
+}
+
+// BIP341 also commits to every spent output's amount and scriptPubKey.
+// Those come from the caller, not the transaction, so the outputs they were
+// hashed from are kept to check later calls against.
+#[derive(Debug, Clone)]
+struct SpentOutputsMidstates {
+    spent: Vec<TxOut>,
+    amounts: [u8; 32],
+    script_pubkeys: [u8; 32],
+}
+
+impl<'tx> SighashCache<'tx> {
+    pub fn new(tx: &'tx BitcoinTransaction) -> Self {
+        SighashCache {
+            tx,
+            segwit_v0: None,
+            taproot: None,
+            spent: None,
+        }
+    }
+
+    pub fn transaction(&self) -> &'tx BitcoinTransaction {
+        self.tx
+    }
+
+    // Nothing to cache for legacy inputs, this is here so callers can go
+    // through the cache for every input type
+    pub fn legacy_signature_hash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        sighash_type: impl Into<SighashType>,
+    ) -> [u8; 32] {
+        self.tx
+            .legacy_signature_hash(input_index, script_code, sighash_type)
+    }
+
+    // BIP143 digest for a segwit v0 input spending `value`. `script_code` is
+    // the witness script for P2WSH (again minus anything before the last
+    // executed OP_CODESEPARATOR); for P2WPKH use p2wpkh_signature_hash.
+    pub fn segwit_v0_signature_hash(
+        &mut self,
+        input_index: usize,
+        script_code: &Script,
+        value: Amount,
+        sighash_type: impl Into<SighashType>,
+    ) -> Result<[u8; 32], BitcoinError> {
+        let sighash_type = sighash_type.into();
+        let input = self
+            .tx
+            .inputs
+            .get(input_index)
+            .ok_or(BitcoinError::InputIndexOutOfRange {
+                index: input_index,
+                inputs: self.tx.inputs.len(),
+            })?;
+        let midstates = self.segwit_v0_midstates();
+        let base = sighash_type.base_type();
+        let acp = sighash_type.anyone_can_pay();
+        let zero = [0u8; 32];
+
+        let hash_prevouts = if acp { zero } else { midstates.prevouts };
+        let hash_sequence = if acp || base != SighashType::ALL {
+            zero
+        } else {
+            midstates.sequences
+        };
+        let hash_outputs = match base {
+            SighashType::ALL => midstates.outputs,
+            // Unlike legacy, a missing output just means zeros - no bug here
+            SighashType::SINGLE => match self.tx.outputs.get(input_index) {
+                Some(output) => hashes::sha256d(&output.to_bytes()),
+                None => zero,
+            },
+            _ => zero,
+        };
+
+        let mut preimage = Vec::with_capacity(156 + script_code.len());
+        write(&mut preimage, &self.tx.version);
+        preimage.extend_from_slice(&hash_prevouts);
+        preimage.extend_from_slice(&hash_sequence);
+        write(&mut preimage, &input.previous_output);
+        write(&mut preimage, script_code);
+        write(&mut preimage, &value);
+        write(&mut preimage, &input.sequence);
+        preimage.extend_from_slice(&hash_outputs);
+        write(&mut preimage, &self.tx.lock_time);
+        write(&mut preimage, &sighash_type.0);
+        Ok(hashes::sha256d(&preimage))
+    }
+
+    // P2WPKH signs with the P2PKH script for the same key hash as its
+    // script code. Takes the output being spent, e.g. from a Utxo.
+    pub fn p2wpkh_signature_hash(
+        &mut self,
+        input_index: usize,
+        script_pubkey: &Script,
+        value: Amount,
+        sighash_type: impl Into<SighashType>,
+    ) -> Result<[u8; 32], BitcoinError> {
+        if !script_pubkey.is_p2wpkh() {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        let script_code = Script::builder()
+            .push_opcode(Opcode::Dup)
+            .push_opcode(Opcode::Hash160)
+            .push_slice(&script_pubkey[2..22])
+            .push_opcode(Opcode::EqualVerify)
+            .push_opcode(Opcode::CheckSig)
+            .into_script();
+        self.segwit_v0_signature_hash(input_index, &script_code, value, sighash_type)
+    }
+
+    // BIP341 digest for a taproot key-path spend (no annex). `prevouts` are
+    // the outputs spent by every input, in input order - taproot signatures
+    // commit to all of them, so fee and script information can't be lied
+    // about to a signer. Their hashes are cached, and redone if a later call
+    // passes different ones.
+    //
+    // SighashType(0x00), "DEFAULT", means ALL with a 64-byte signature.
+    pub fn taproot_key_spend_signature_hash(
+        &mut self,
+        input_index: usize,
+        prevouts: &[TxOut],
+        sighash_type: impl Into<SighashType>,
+    ) -> Result<[u8; 32], BitcoinError> {
+        let sighash_type = sighash_type.into();
+        if !sighash_type.is_valid_taproot() {
+            return Err(BitcoinError::InvalidSighashType(sighash_type.0));
+        }
+        let input = self
+            .tx
+            .inputs
+            .get(input_index)
+            .ok_or(BitcoinError::InputIndexOutOfRange {
+                index: input_index,
+                inputs: self.tx.inputs.len(),
+            })?;
+        if prevouts.len() != self.tx.inputs.len() {
+            return Err(BitcoinError::PrevoutCount {
+                expected: self.tx.inputs.len(),
+                got: prevouts.len(),
+            });
+        }
+        let midstates = self.taproot_midstates();
+        let (amounts, script_pubkeys) = self.spent_outputs_midstates(prevouts);
+        let base = sighash_type.base_type();
+        let acp = sighash_type.anyone_can_pay();
+
+        // Epoch 0, then SigMsg
+        let mut msg = vec![0x00, sighash_type.0 as u8];
+        write(&mut msg, &self.tx.version);
+        write(&mut msg, &self.tx.lock_time);
+        if !acp {
+            msg.extend_from_slice(&midstates.prevouts);
+            msg.extend_from_slice(&amounts);
+            msg.extend_from_slice(&script_pubkeys);
+            msg.extend_from_slice(&midstates.sequences);
+        }
+        if base == SighashType::ALL {
+            msg.extend_from_slice(&midstates.outputs);
+        }
+        // spend_type: key path (ext_flag 0), no annex
+        msg.push(0x00);
+        if acp {
+            write(&mut msg, &input.previous_output);
+            write(&mut msg, &prevouts[input_index]);
+            write(&mut msg, &input.sequence);
+        } else {
+            write(&mut msg, &(input_index as u32));
+        }
+        if base == SighashType::SINGLE {
+            // No SIGHASH_SINGLE bug in taproot: a missing output is an error
+            let output = self
+                .tx
+                .outputs
+                .get(input_index)
+                .ok_or(BitcoinError::InvalidSighashType(sighash_type.0))?;
+            msg.extend_from_slice(&hashes::sha256(&output.to_bytes()));
+        }
+        Ok(hashes::tagged_hash("TapSighash", &msg))
+    }
+
+    fn taproot_midstates(&mut self) -> TaprootMidstates {
+        let tx = self.tx;
+        *self.taproot.get_or_insert_with(|| {
+            let (mut outpoints, mut sequences) = (Vec::new(), Vec::new());
+            for input in &tx.inputs {
+                write(&mut outpoints, &input.previous_output);
+                write(&mut sequences, &input.sequence);
+            }
+            let mut outputs = Vec::new();
+            for output in &tx.outputs {
+                write(&mut outputs, output);
+            }
+            TaprootMidstates {
+                prevouts: hashes::sha256(&outpoints),
+                sequences: hashes::sha256(&sequences),
+                outputs: hashes::sha256(&outputs),
+            }
+        })
+    }
+
+    // Comparing against the cached outputs is a lot cheaper than hashing
+    // them again, and means a stale cache can never sign the wrong amounts
+    fn spent_outputs_midstates(&mut self, prevouts: &[TxOut]) -> ([u8; 32], [u8; 32]) {
+        if let Some(cached) = &self.spent
+            && cached.spent == prevouts
+        {
+            return (cached.amounts, cached.script_pubkeys);
+        }
+        let (mut amounts, mut script_pubkeys) = (Vec::new(), Vec::new());
+        for prevout in prevouts {
+            write(&mut amounts, &prevout.value);
+            write(&mut script_pubkeys, &prevout.script_pubkey);
+        }
+        let cached = self.spent.insert(SpentOutputsMidstates {
+            spent: prevouts.to_vec(),
+            amounts: hashes::sha256(&amounts),
+            script_pubkeys: hashes::sha256(&script_pubkeys),
+        });
+        (cached.amounts, cached.script_pubkeys)
+    }
+
+    fn segwit_v0_midstates(&mut self) -> SegwitV0Midstates {
+        let tx = self.tx;
+        *self.segwit_v0.get_or_insert_with(|| {
+            let mut prevouts = Vec::with_capacity(36 * tx.inputs.len());
+            let mut sequences = Vec::with_capacity(4 * tx.inputs.len());
+            for input in &tx.inputs {
+                write(&mut prevouts, &input.previous_output);
+                write(&mut sequences, &input.sequence);
+            }
+            let mut outputs = Vec::new();
+            for output in &tx.outputs {
+                write(&mut outputs, output);
+            }
+            SegwitV0Midstates {
+                prevouts: hashes::sha256d(&prevouts),
+                sequences: hashes::sha256d(&sequences),
+                outputs: hashes::sha256d(&outputs),
+            }
+        })
+    }
+}
+
+fn write<T: ConsensusEncodable + ?Sized>(buf: &mut Vec<u8>, value: &T) {
+    value
+        .consensus_encode(buf)
+        .expect("writing to a Vec can't fail");
+}
+
+// Drops every OP_CODESEPARATOR, leaving the rest byte-for-byte (pushes keep
+// their original, possibly non-minimal, encoding). A malformed tail is kept
+// as it is, like Core does.
+fn strip_code_separators(script: &Script) -> Script {
+    let mut out = Vec::with_capacity(script.bytes.len());
+    let mut instructions = script.instructions();
+    loop {
+        let start = instructions.position();
+        match instructions.next() {
+            None => break,
+            Some(Ok(Instruction::Op(Opcode::CodeSeparator))) => {}
+            Some(Ok(_)) => out.extend_from_slice(&script.bytes[start..instructions.position()]),
+            Some(Err(_)) => {
+                out.extend_from_slice(&script.bytes[start..]);
+                break;
+            }
+        }
+    }
+    Script::new(out)
+}
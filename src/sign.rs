@@ -0,0 +1,283 @@
+use crate::script::Instruction;
+use crate::{
+    BitcoinError, Script, ScriptType, SighashCache, SighashType, Signature, TransactionInput,
+    TxOut, Utxo, Witness, hashes,
+};
+use secp256k1::{
+    All, Keypair, Message, Parity, PublicKey, Scalar, Secp256k1, SecretKey, XOnlyPublicKey,
+};
+use std::sync::OnceLock;
+
+// Signing and checking single-key inputs with libsecp256k1 (`secp256k1`
+// feature). These tie the sighash code to the keys: work out the digest for
+// the kind of output being spent, sign it, and lay the result out the way
+// that output type expects.
+//
+// Supported spends:
+// - P2PKH: scriptSig <sig> <pubkey>, legacy sighash
+// - P2WPKH: empty scriptSig, witness [<sig>, <pubkey>], BIP143 sighash
+// - P2TR key path: empty scriptSig, witness [<schnorr sig>], BIP341 sighash
+//
+// ECDSA keys are always used compressed. The spent output comes in as a
+// Utxo and must be the one the input actually references.
+
+// Context creation is expensive (it precomputes tables), so share one
+pub(crate) fn secp() -> &'static Secp256k1<All> {
+    static SECP: OnceLock<Secp256k1<All>> = OnceLock::new();
+    SECP.get_or_init(Secp256k1::new)
+}
+
+// Signs input `input_index` of the cache's transaction and returns the
+// scriptSig and witness to put on it. Taking the cache (rather than the
+// transaction) means signing every input shares one set of BIP143
+// midstates; apply the results once all inputs are signed.
+//
+// Signatures are RFC6979 deterministic and low-S.
+pub fn sign_input_ecdsa(
+    cache: &mut SighashCache<'_>,
+    input_index: usize,
+    utxo: &Utxo,
+    secret_key: &SecretKey,
+    sighash_type: SighashType,
+) -> Result<(Script, Witness), BitcoinError> {
+    let pubkey = PublicKey::from_secret_key(secp(), secret_key).serialize();
+    let (kind, key_hash) = spend_kind(&utxo.txout.script_pubkey)?;
+    if hashes::hash160(&pubkey) != key_hash {
+        return Err(BitcoinError::InvalidKey);
+    }
+
+    let sighash = signature_hash(cache, input_index, utxo, kind, sighash_type)?;
+    let signature = secp().sign_ecdsa(&Message::from_digest(sighash), secret_key);
+    let sig = Signature::from_secp(&signature, sighash_type).to_bytes();
+
+    Ok(match kind {
+        ScriptType::P2pkh => {
+            let script_sig = Script::builder()
+                .push_slice(&sig)
+                .push_key(pubkey)
+                .into_script();
+            (script_sig, Witness::new())
+        }
+        _ => (Script::default(), Witness::p2wpkh(sig, pubkey)),
+    })
+}
+
+// Checks the signature already on input `input_index` against the output it
+// spends. Like consensus, high-S signatures are accepted (policy rejects
+// them, see the malleability analyzer).
+pub fn verify_input_ecdsa(
+    cache: &mut SighashCache<'_>,
+    input_index: usize,
+    utxo: &Utxo,
+) -> Result<(), BitcoinError> {
+    let (kind, key_hash) = spend_kind(&utxo.txout.script_pubkey)?;
+    let input = input(cache, input_index, utxo)?;
+
+    // Both forms carry exactly <sig> <pubkey>
+    let (sig, pubkey) = match kind {
+        ScriptType::P2pkh => {
+            let pushes = input
+                .script_sig
+                .instructions()
+                .map(|ins| match ins {
+                    Ok(Instruction::PushBytes(data)) => Ok(data.to_vec()),
+                    _ => Err(BitcoinError::InvalidSignature),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            match <[Vec<u8>; 2]>::try_from(pushes) {
+                Ok([sig, pubkey]) => (sig, pubkey),
+                Err(_) => return Err(BitcoinError::InvalidSignature),
+            }
+        }
+        _ => {
+            if !input.script_sig.is_empty() || input.witness.len() != 2 {
+                return Err(BitcoinError::InvalidSignature);
+            }
+            (
+                input.witness.items[0].clone(),
+                input.witness.items[1].clone(),
+            )
+        }
+    };
+
+    if hashes::hash160(&pubkey) != key_hash {
+        return Err(BitcoinError::InvalidKey);
+    }
+    let pubkey = PublicKey::from_slice(&pubkey).map_err(|_| BitcoinError::InvalidKey)?;
+    let signature = Signature::from_bytes(&sig)?;
+    let sighash = signature_hash(cache, input_index, utxo, kind, signature.sighash_type)?;
+    // libsecp256k1 only verifies low-S; consensus takes either
+    let signature = signature.normalize_s().to_secp()?;
+    secp()
+        .verify_ecdsa(&Message::from_digest(sighash), &signature, &pubkey)
+        .map_err(|_| BitcoinError::InvalidSignature)
+}
+
+// BIP341 output key: the internal key tweaked by
+// tagged_hash("TapTweak", internal_key || merkle_root). With no script tree
+// (merkle_root None) the tweak is over the key alone, per BIP86, so the
+// output key commits to there being no hidden script path. Feed the result
+// to Address::p2tr.
+pub fn taproot_output_key(
+    internal_key: &XOnlyPublicKey,
+    merkle_root: Option<[u8; 32]>,
+) -> Result<XOnlyPublicKey, BitcoinError> {
+    tweak_internal_key(internal_key, merkle_root).map(|(output_key, _parity)| output_key)
+}
+
+// Same as taproot_output_key, but keeps the parity of the tweaked point,
+// which script-path control blocks have to carry
+pub(crate) fn tweak_internal_key(
+    internal_key: &XOnlyPublicKey,
+    merkle_root: Option<[u8; 32]>,
+) -> Result<(XOnlyPublicKey, Parity), BitcoinError> {
+    internal_key
+        .add_tweak(secp(), &taproot_tweak(internal_key, merkle_root)?)
+        .map_err(|_| BitcoinError::InvalidKey)
+}
+
+// Key-path spend of a P2TR input: tweaks the key, signs the BIP341 sighash
+// and returns the one-item witness (64 bytes for DEFAULT, 65 with the type
+// byte otherwise). `prevouts` are the outputs spent by every input, in order.
+pub fn sign_input_schnorr(
+    cache: &mut SighashCache<'_>,
+    input_index: usize,
+    prevouts: &[Utxo],
+    secret_key: &SecretKey,
+    merkle_root: Option<[u8; 32]>,
+    sighash_type: SighashType,
+) -> Result<Witness, BitcoinError> {
+    let spent = taproot_prevouts(cache, input_index, prevouts)?;
+    let keypair = Keypair::from_secret_key(secp(), secret_key);
+    let (internal_key, _parity) = keypair.x_only_public_key();
+    let tweaked = keypair
+        .add_xonly_tweak(secp(), &taproot_tweak(&internal_key, merkle_root)?)
+        .map_err(|_| BitcoinError::InvalidKey)?;
+    if tweaked.x_only_public_key().0 != output_key(&spent[input_index])? {
+        return Err(BitcoinError::InvalidKey);
+    }
+
+    let sighash = cache.taproot_key_spend_signature_hash(input_index, &spent, sighash_type)?;
+    let signature = secp().sign_schnorr_no_aux_rand(&Message::from_digest(sighash), &tweaked);
+    let mut sig = signature.serialize().to_vec();
+    if sighash_type != SighashType::DEFAULT {
+        sig.push(sighash_type.0 as u8);
+    }
+    Ok(Witness::from_items(vec![sig]))
+}
+
+pub fn verify_input_schnorr(
+    cache: &mut SighashCache<'_>,
+    input_index: usize,
+    prevouts: &[Utxo],
+) -> Result<(), BitcoinError> {
+    let spent = taproot_prevouts(cache, input_index, prevouts)?;
+    let output_key = output_key(&spent[input_index])?;
+    let input = &cache.transaction().inputs[input_index];
+    if !input.script_sig.is_empty() || input.witness.len() != 1 {
+        return Err(BitcoinError::InvalidSignature);
+    }
+
+    // A 65th byte is the sighash type, and may not be DEFAULT spelled out
+    let sig = &input.witness.items[0];
+    let sighash_type = match sig.len() {
+        64 => SighashType::DEFAULT,
+        65 if sig[64] != 0x00 => SighashType::from(sig[64]),
+        _ => return Err(BitcoinError::InvalidSignature),
+    };
+    let signature = secp256k1::schnorr::Signature::from_slice(&sig[..64])
+        .map_err(|_| BitcoinError::InvalidSignature)?;
+    let sighash = cache.taproot_key_spend_signature_hash(input_index, &spent, sighash_type)?;
+    secp()
+        .verify_schnorr(&signature, &Message::from_digest(sighash), &output_key)
+        .map_err(|_| BitcoinError::InvalidSignature)
+}
+
+fn taproot_tweak(
+    internal_key: &XOnlyPublicKey,
+    merkle_root: Option<[u8; 32]>,
+) -> Result<Scalar, BitcoinError> {
+    let mut data = internal_key.serialize().to_vec();
+    data.extend(merkle_root.iter().flatten());
+    Scalar::from_be_bytes(hashes::tagged_hash("TapTweak", &data))
+        .map_err(|_| BitcoinError::InvalidKey)
+}
+
+// Checks every prevout lines up with its input, and hands back the TxOuts
+// the sighash wants
+fn taproot_prevouts(
+    cache: &SighashCache<'_>,
+    input_index: usize,
+    prevouts: &[Utxo],
+) -> Result<Vec<TxOut>, BitcoinError> {
+    let inputs = &cache.transaction().inputs;
+    if prevouts.len() != inputs.len() {
+        return Err(BitcoinError::PrevoutCount {
+            expected: inputs.len(),
+            got: prevouts.len(),
+        });
+    }
+    input(cache, input_index, &prevouts[input_index])?;
+    for (index, (input, utxo)) in inputs.iter().zip(prevouts).enumerate() {
+        if input.previous_output != utxo.outpoint {
+            return Err(BitcoinError::PrevoutMismatch { index });
+        }
+    }
+    Ok(prevouts.iter().map(|utxo| utxo.txout.clone()).collect())
+}
+
+fn output_key(prevout: &TxOut) -> Result<XOnlyPublicKey, BitcoinError> {
+    let script = &prevout.script_pubkey;
+    if !script.is_p2tr() {
+        return Err(BitcoinError::UnsupportedScript(script.classify()));
+    }
+    XOnlyPublicKey::from_slice(&script[2..34]).map_err(|_| BitcoinError::InvalidKey)
+}
+
+fn input<'a>(
+    cache: &SighashCache<'a>,
+    input_index: usize,
+    utxo: &Utxo,
+) -> Result<&'a TransactionInput, BitcoinError> {
+    let tx = cache.transaction();
+    let input = tx
+        .inputs
+        .get(input_index)
+        .ok_or(BitcoinError::InputIndexOutOfRange {
+            index: input_index,
+            inputs: tx.inputs.len(),
+        })?;
+    if input.previous_output != utxo.outpoint {
+        return Err(BitcoinError::PrevoutMismatch { index: input_index });
+    }
+    Ok(input)
+}
+
+// Which supported template the output is, and the key hash in it
+fn spend_kind(script_pubkey: &Script) -> Result<(ScriptType, [u8; 20]), BitcoinError> {
+    let bytes = &script_pubkey.bytes;
+    match script_pubkey.classify() {
+        ScriptType::P2pkh => Ok((ScriptType::P2pkh, bytes[3..23].try_into().unwrap())),
+        ScriptType::P2wpkh => Ok((ScriptType::P2wpkh, bytes[2..22].try_into().unwrap())),
+        other => Err(BitcoinError::UnsupportedScript(other)),
+    }
+}
+
+fn signature_hash(
+    cache: &mut SighashCache<'_>,
+    input_index: usize,
+    utxo: &Utxo,
+    kind: ScriptType,
+    sighash_type: SighashType,
+) -> Result<[u8; 32], BitcoinError> {
+    input(cache, input_index, utxo)?;
+    let script_pubkey = &utxo.txout.script_pubkey;
+    match kind {
+        ScriptType::P2pkh => {
+            Ok(cache.legacy_signature_hash(input_index, script_pubkey, sighash_type))
+        }
+        _ => {
+            cache.p2wpkh_signature_hash(input_index, script_pubkey, utxo.txout.value, sighash_type)
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use crate::SighashType;
+use std::fmt;
+
+// An ECDSA signature as it appears in a scriptSig or witness: a DER-encoded
+// (r, s) pair with the sighash type byte tacked on the end.
+//
+// Parsing follows BIP66 strict DER, which has been consensus since 2015, so
+// anything `from_bytes` accepts is a signature some node could have checked.
+// Low-S (BIP146) is policy only: a high-S signature is valid in a block but
+// won't be relayed, so it's a separate check.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Signature {
+    pub r: [u8; 32],
+    pub s: [u8; 32],
+    pub sighash_type: SighashType,
+}
+
+// Which of the two integers a DER problem is in
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SignatureComponent {
+    R,
+    S,
+}
+
+// Everything that can be wrong with a signature's encoding, in the order
+// BIP66's IsValidSignatureEncoding checks for it
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SignatureError {
+    // Not even a sighash byte
+    Empty,
+    // The DER part must be 8..=72 bytes
+    InvalidLength(usize),
+    // DER doesn't start with the 0x30 compound tag
+    NotASequence,
+    // The sequence length byte doesn't cover exactly the rest of the DER
+    SequenceLength { declared: usize, actual: usize },
+    // An integer is missing its 0x02 tag, or runs past the end
+    NotAnInteger(SignatureComponent),
+    ZeroLength(SignatureComponent),
+    Negative(SignatureComponent),
+    // Padded with a zero byte it didn't need
+    NonMinimal(SignatureComponent),
+    // More than 32 bytes once the sign padding is dropped
+    TooLarge(SignatureComponent),
+    // Valid DER, but S is above n/2 (policy)
+    HighS,
+}
+
+// secp256k1's group order n, and n / 2. An S above the latter is "high".
+const ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+const HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+impl Signature {
+    // DER + sighash byte, the way it's pushed on the stack
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SignatureError> {
+        let (&sighash_type, der) = bytes.split_last().ok_or(SignatureError::Empty)?;
+        Self::from_der(der, SighashType::from(sighash_type))
+    }
+
+    pub fn from_der(der: &[u8], sighash_type: SighashType) -> Result<Self, SignatureError> {
+        use SignatureComponent::{R, S};
+
+        if der.len() < 8 || der.len() > 72 {
+            return Err(SignatureError::InvalidLength(der.len()));
+        }
+        if der[0] != 0x30 {
+            return Err(SignatureError::NotASequence);
+        }
+        if der[1] as usize != der.len() - 2 {
+            return Err(SignatureError::SequenceLength {
+                declared: der[1] as usize,
+                actual: der.len() - 2,
+            });
+        }
+        let (r, rest) = read_integer(&der[2..], R)?;
+        let (s, rest) = read_integer(rest, S)?;
+        // S must run exactly to the end
+        if !rest.is_empty() {
+            return Err(SignatureError::NotAnInteger(S));
+        }
+        Ok(Signature {
+            r: to_scalar(r, R)?,
+            s: to_scalar(s, S)?,
+            sighash_type,
+        })
+    }
+
+    // Strict DER and low-S: what a node will relay
+    pub fn from_bytes_standard(bytes: &[u8]) -> Result<Self, SignatureError> {
+        let signature = Self::from_bytes(bytes)?;
+        if !signature.is_low_s() {
+            return Err(SignatureError::HighS);
+        }
+        Ok(signature)
+    }
+
+    pub fn is_low_s(&self) -> bool {
+        self.s <= HALF_ORDER
+    }
+
+    // (r, n - s) verifies against the same message and key, which is exactly
+    // the malleability low-S rules out. Flip a high S to get the canonical
+    // form.
+    pub fn normalize_s(mut self) -> Self {
+        if !self.is_low_s() {
+            self.s = sub_from_order(&self.s);
+        }
+        self
+    }
+
+    pub fn to_der(&self) -> Vec<u8> {
+        let r = der_integer(&self.r);
+        let s = der_integer(&self.s);
+        let mut der = vec![0x30, (r.len() + s.len()) as u8];
+        der.extend(r);
+        der.extend(s);
+        der
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.to_der();
+        bytes.push(self.sighash_type.0 as u8);
+        bytes
+    }
+}
+
+// Conversions to and from libsecp256k1's signature, for signing and checking
+#[cfg(feature = "secp256k1")]
+impl Signature {
+    pub fn from_secp(signature: &secp256k1::ecdsa::Signature, sighash_type: SighashType) -> Self {
+        let compact = signature.serialize_compact();
+        Signature {
+            r: compact[..32].try_into().unwrap(),
+            s: compact[32..].try_into().unwrap(),
+            sighash_type,
+        }
+    }
+
+    // Fails for an r or s that isn't below the group order
+    pub fn to_secp(&self) -> Result<secp256k1::ecdsa::Signature, crate::BitcoinError> {
+        let mut compact = [0u8; 64];
+        compact[..32].copy_from_slice(&self.r);
+        compact[32..].copy_from_slice(&self.s);
+        secp256k1::ecdsa::Signature::from_compact(&compact)
+            .map_err(|_| crate::BitcoinError::InvalidSignature)
+    }
+}
+
+impl fmt::Display for SignatureComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureComponent::R => write!(f, "R"),
+            SignatureComponent::S => write!(f, "S"),
+        }
+    }
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::Empty => write!(f, "empty signature"),
+            SignatureError::InvalidLength(len) => {
+                write!(f, "DER signature is {} bytes, expected 8 to 72", len)
+            }
+            SignatureError::NotASequence => write!(f, "DER signature is not a sequence"),
+            SignatureError::SequenceLength { declared, actual } => write!(
+                f,
+                "DER sequence length is {} but {} bytes follow",
+                declared, actual
+            ),
+            SignatureError::NotAnInteger(c) => write!(f, "{} is not a DER integer", c),
+            SignatureError::ZeroLength(c) => write!(f, "{} has zero length", c),
+            SignatureError::Negative(c) => write!(f, "{} is negative", c),
+            SignatureError::NonMinimal(c) => write!(f, "{} has excess zero padding", c),
+            SignatureError::TooLarge(c) => write!(f, "{} is larger than 32 bytes", c),
+            SignatureError::HighS => write!(f, "S is above half the curve order"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+// Reads one `02 <len> <bytes>` integer, returning its bytes and what follows
+fn read_integer(
+    bytes: &[u8],
+    component: SignatureComponent,
+) -> Result<(&[u8], &[u8]), SignatureError> {
+    let (&tag, rest) = bytes
+        .split_first()
+        .ok_or(SignatureError::NotAnInteger(component))?;
+    let (&len, rest) = rest
+        .split_first()
+        .ok_or(SignatureError::NotAnInteger(component))?;
+    if tag != 0x02 || len as usize > rest.len() {
+        return Err(SignatureError::NotAnInteger(component));
+    }
+    let (int, rest) = rest.split_at(len as usize);
+    match int {
+        [] => Err(SignatureError::ZeroLength(component)),
+        [first, ..] if first & 0x80 != 0 => Err(SignatureError::Negative(component)),
+        [0, second, ..] if second & 0x80 == 0 => Err(SignatureError::NonMinimal(component)),
+        _ => Ok((int, rest)),
+    }
+}
+
+fn to_scalar(int: &[u8], component: SignatureComponent) -> Result<[u8; 32], SignatureError> {
+    // The only leading zero left is sign padding
+    let int = int.strip_prefix(&[0]).unwrap_or(int);
+    if int.len() > 32 {
+        return Err(SignatureError::TooLarge(component));
+    }
+    let mut scalar = [0u8; 32];
+    scalar[32 - int.len()..].copy_from_slice(int);
+    Ok(scalar)
+}
+
+// Shortest positive encoding: drop leading zeros, then add one back if the
+// top bit would read as a sign
+fn der_integer(scalar: &[u8; 32]) -> Vec<u8> {
+    let start = scalar.iter().position(|&b| b != 0).unwrap_or(31);
+    let mut int = vec![0x02, 0];
+    if scalar[start] & 0x80 != 0 {
+        int.push(0);
+    }
+    int.extend(&scalar[start..]);
+    int[1] = (int.len() - 2) as u8;
+    int
+}
+
+// n - s, big-endian with borrow
+fn sub_from_order(s: &[u8; 32]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let mut diff = ORDER[i] as i16 - s[i] as i16 - borrow;
+        borrow = i16::from(diff < 0);
+        if diff < 0 {
+            diff += 256;
+        }
+        out[i] = diff as u8;
+    }
+    out
+}
@@ -0,0 +1,216 @@
+use crate::address::Address;
+use crate::network::Network;
+use crate::{BitcoinError, CompactSize, Script, hashes};
+
+// Taproot script trees (BIP341). A P2TR output commits to an internal key
+// plus, optionally, the merkle root of a binary tree of scripts. Spending
+// through a script means revealing the leaf and a control block: the
+// internal key, the output key's parity and the sibling hashes needed to
+// climb from the leaf back to the root.
+//
+// Unlike the transaction merkle tree, each pair of children is hashed in
+// sorted order, so a path only needs the siblings and not left/right flags.
+
+// The only leaf version defined so far: BIP342 tapscript
+pub const TAPSCRIPT_LEAF_VERSION: u8 = 0xC0;
+// Consensus limit on the depth of the tree (and so the length of a path)
+pub const TAPROOT_CONTROL_MAX_NODE_COUNT: usize = 128;
+// Control block size with an empty path: leaf version/parity byte + internal key
+const CONTROL_BLOCK_BASE_SIZE: usize = 33;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TapTree {
+    Leaf { version: u8, script: Script },
+    Branch(Box<TapTree>, Box<TapTree>),
+}
+
+impl TapTree {
+    // A tapscript leaf
+    pub fn leaf(script: Script) -> Self {
+        TapTree::Leaf {
+            version: TAPSCRIPT_LEAF_VERSION,
+            script,
+        }
+    }
+
+    pub fn branch(left: TapTree, right: TapTree) -> Self {
+        TapTree::Branch(Box::new(left), Box::new(right))
+    }
+
+    // The hash committed to in the output key
+    pub fn merkle_root(&self) -> [u8; 32] {
+        match self {
+            TapTree::Leaf { version, script } => tap_leaf_hash(*version, script),
+            TapTree::Branch(left, right) => {
+                tap_branch_hash(left.merkle_root(), right.merkle_root())
+            }
+        }
+    }
+
+    // Every leaf, left to right, with its merkle path (siblings from the leaf
+    // upwards). Fails if any leaf is deeper than consensus allows.
+    pub fn leaves(&self) -> Result<Vec<LeafInfo>, BitcoinError> {
+        let mut leaves = Vec::new();
+        self.collect_leaves(&mut Vec::new(), &mut leaves);
+        match leaves.iter().map(|leaf| leaf.merkle_branch.len()).max() {
+            Some(depth) if depth > TAPROOT_CONTROL_MAX_NODE_COUNT => {
+                Err(BitcoinError::TaprootTreeTooDeep { depth })
+            }
+            _ => Ok(leaves),
+        }
+    }
+
+    // `path` holds the siblings from the root down to `self`
+    fn collect_leaves(&self, path: &mut Vec<[u8; 32]>, out: &mut Vec<LeafInfo>) {
+        match self {
+            TapTree::Leaf { version, script } => out.push(LeafInfo {
+                version: *version,
+                script: script.clone(),
+                merkle_branch: path.iter().rev().copied().collect(),
+            }),
+            TapTree::Branch(left, right) => {
+                path.push(right.merkle_root());
+                left.collect_leaves(path, out);
+                path.pop();
+                path.push(left.merkle_root());
+                right.collect_leaves(path, out);
+                path.pop();
+            }
+        }
+    }
+}
+
+// A leaf script and the path proving it's in the tree
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LeafInfo {
+    pub version: u8,
+    pub script: Script,
+    pub merkle_branch: Vec<[u8; 32]>,
+}
+
+impl LeafInfo {
+    pub fn leaf_hash(&self) -> [u8; 32] {
+        tap_leaf_hash(self.version, &self.script)
+    }
+}
+
+// tagged_hash("TapLeaf", version || compact_size(len) || script)
+pub fn tap_leaf_hash(version: u8, script: &Script) -> [u8; 32] {
+    let mut data = vec![version];
+    data.extend(CompactSize::new(script.len() as u64).to_bytes());
+    data.extend(&script.bytes);
+    hashes::tagged_hash("TapLeaf", &data)
+}
+
+// tagged_hash("TapBranch", min(a, b) || max(a, b))
+pub fn tap_branch_hash(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+    let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+    let mut data = lo.to_vec();
+    data.extend(hi);
+    hashes::tagged_hash("TapBranch", &data)
+}
+
+// The last witness item of a script-path spend. The first byte packs the
+// leaf version (high 7 bits) with the parity of the output key (low bit).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ControlBlock {
+    pub leaf_version: u8,
+    pub output_key_parity: u8,
+    pub internal_key: [u8; 32],
+    pub merkle_branch: Vec<[u8; 32]>,
+}
+
+impl ControlBlock {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(CONTROL_BLOCK_BASE_SIZE + 32 * self.merkle_branch.len());
+        bytes.push(self.leaf_version | self.output_key_parity);
+        bytes.extend(self.internal_key);
+        bytes.extend(self.merkle_branch.iter().flatten());
+        bytes
+    }
+
+    // Checks only the shape (33 + 32m bytes, m <= 128); the key itself is
+    // validated when it's tweaked
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        let nodes = bytes.len().saturating_sub(CONTROL_BLOCK_BASE_SIZE) / 32;
+        if bytes.len() < CONTROL_BLOCK_BASE_SIZE
+            || bytes.len() != CONTROL_BLOCK_BASE_SIZE + 32 * nodes
+            || nodes > TAPROOT_CONTROL_MAX_NODE_COUNT
+        {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Ok(ControlBlock {
+            leaf_version: bytes[0] & 0xFE,
+            output_key_parity: bytes[0] & 1,
+            internal_key: bytes[1..33].try_into().unwrap(),
+            merkle_branch: bytes[33..]
+                .chunks_exact(32)
+                .map(|node| node.try_into().unwrap())
+                .collect(),
+        })
+    }
+
+    // The root this block claims `script` hangs from. A spend is valid when
+    // the internal key tweaked by this root gives the output key.
+    pub fn merkle_root(&self, script: &Script) -> [u8; 32] {
+        self.merkle_branch.iter().fold(
+            tap_leaf_hash(self.leaf_version, script),
+            |node, &sibling| tap_branch_hash(node, sibling),
+        )
+    }
+}
+
+// Everything needed to pay to and spend from a P2TR output: the tweaked
+// output key and a control block for each leaf of the tree
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TaprootSpendInfo {
+    pub internal_key: [u8; 32],
+    pub merkle_root: Option<[u8; 32]>,
+    pub output_key: [u8; 32],
+    pub output_key_parity: u8,
+    pub leaves: Vec<LeafInfo>,
+}
+
+impl TaprootSpendInfo {
+    // Tweaks `internal_key` by the tree's root. With no tree the output is
+    // key-path only (BIP86).
+    #[cfg(feature = "secp256k1")]
+    pub fn new(
+        internal_key: &secp256k1::XOnlyPublicKey,
+        tree: Option<&TapTree>,
+    ) -> Result<Self, BitcoinError> {
+        let leaves = match tree {
+            Some(tree) => tree.leaves()?,
+            None => Vec::new(),
+        };
+        let merkle_root = tree.map(TapTree::merkle_root);
+        let (output_key, parity) = crate::sign::tweak_internal_key(internal_key, merkle_root)?;
+        Ok(TaprootSpendInfo {
+            internal_key: internal_key.serialize(),
+            merkle_root,
+            output_key: output_key.serialize(),
+            output_key_parity: parity.to_u8(),
+            leaves,
+        })
+    }
+
+    pub fn address(&self, network: Network) -> Address {
+        Address::p2tr(self.output_key, network)
+    }
+
+    // Control block for spending through `script`, or None if it isn't a
+    // leaf of this tree. If the same script appears more than once, the
+    // shallowest copy gives the smallest witness.
+    pub fn control_block(&self, script: &Script) -> Option<ControlBlock> {
+        self.leaves
+            .iter()
+            .filter(|leaf| leaf.script == *script)
+            .min_by_key(|leaf| leaf.merkle_branch.len())
+            .map(|leaf| ControlBlock {
+                leaf_version: leaf.version,
+                output_key_parity: self.output_key_parity,
+                internal_key: self.internal_key,
+                merkle_branch: leaf.merkle_branch.clone(),
+            })
+    }
+}
@@ -0,0 +1,20 @@
+// Logging hooks for the optional `tracing` feature. Call sites use these
+// instead of the tracing macros directly so they don't each need a #[cfg]:
+// with the feature on they forward to tracing, with it off they expand to
+// nothing and their arguments aren't evaluated.
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+
+pub(crate) use {debug, trace};
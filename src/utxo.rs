@@ -0,0 +1,174 @@
+use crate::{
+    Amount, BitcoinTransaction, FeeRate, OutPoint, Script, ScriptType, Sequence, TransactionInput,
+    TxOut, Weight,
+};
+use serde::{Deserialize, Serialize};
+
+// Everything you need to know about a coin in one place: where it is (the
+// outpoint), what it's worth and how it's locked (the output), and, for
+// coins our own wallet owns, which key it was derived from. Passing these
+// around beats keeping parallel `Vec<OutPoint>` / `Vec<TxOut>` slices that
+// can drift out of order.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Utxo {
+    pub outpoint: OutPoint,
+    pub txout: TxOut,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivation: Option<DerivationInfo>,
+}
+
+// Where the key for a coin came from, as PSBTs record it: the fingerprint of
+// the master key and the BIP32 path from it (hardened steps have the top bit
+// set, e.g. 0x8000002C for 44').
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+pub struct DerivationInfo {
+    pub fingerprint: [u8; 4],
+    pub path: Vec<u32>,
+}
+
+impl Utxo {
+    pub fn new(outpoint: OutPoint, txout: TxOut) -> Self {
+        Utxo {
+            outpoint,
+            txout,
+            derivation: None,
+        }
+    }
+
+    pub fn with_derivation(mut self, derivation: DerivationInfo) -> Self {
+        self.derivation = Some(derivation);
+        self
+    }
+
+    // The coin created by output `vout` of `tx`, if it has that many outputs
+    pub fn from_transaction(tx: &BitcoinTransaction, vout: u32) -> Option<Self> {
+        let txout = tx.outputs.get(vout as usize)?.clone();
+        Some(Utxo::new(
+            OutPoint {
+                txid: tx.txid(),
+                vout,
+            },
+            txout,
+        ))
+    }
+
+    pub fn value(&self) -> Amount {
+        self.txout.value
+    }
+
+    pub fn script_pubkey(&self) -> &Script {
+        &self.txout.script_pubkey
+    }
+
+    // An unsigned input spending this coin, with the default (RBF) sequence
+    pub fn to_input(&self) -> TransactionInput {
+        TransactionInput::new(
+            self.outpoint.clone(),
+            Script::default(),
+            Sequence::default().0,
+        )
+    }
+}
+
+// A coin together with how much weight it takes to spend: the scriptSig
+// (including its length byte) plus the witness (including its item count).
+// That's what coin selection needs to tell whether a coin is worth adding
+// at a given fee rate.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct WeightedUtxo {
+    pub utxo: Utxo,
+    pub satisfaction_weight: Weight,
+}
+
+impl WeightedUtxo {
+    // Outpoint (36 bytes) + nSequence (4), the part every input has
+    pub const BASE_INPUT_WEIGHT: Weight = Weight::from_non_witness_data_size(40);
+
+    pub fn new(utxo: Utxo, satisfaction_weight: Weight) -> Self {
+        WeightedUtxo {
+            utxo,
+            satisfaction_weight,
+        }
+    }
+
+    // Worst-case satisfaction weight for the single-key templates: 72-byte
+    // DER signatures, compressed keys, and a 65-byte Schnorr signature (with
+    // a sighash byte). None for anything else, whose cost depends on the
+    // script.
+    pub fn single_key(utxo: Utxo) -> Option<Self> {
+        let satisfaction_weight = match utxo.script_pubkey().classify() {
+            // scriptSig: len, push(sig), push(key)
+            ScriptType::P2pkh => Weight::from_non_witness_data_size(1 + 73 + 34),
+            // empty scriptSig; witness: count, sig, key
+            ScriptType::P2wpkh => Weight::from_wu(4 + 1 + 73 + 34),
+            // empty scriptSig; witness: count, sig
+            ScriptType::P2tr => Weight::from_wu(4 + 1 + 66),
+            _ => return None,
+        };
+        Some(WeightedUtxo::new(utxo, satisfaction_weight))
+    }
+
+    pub fn input_weight(&self) -> Weight {
+        Self::BASE_INPUT_WEIGHT
+            .checked_add(self.satisfaction_weight)
+            .unwrap_or(Weight::from_wu(u64::MAX))
+    }
+
+    // What adding this input costs at `fee_rate`
+    pub fn input_fee(&self, fee_rate: FeeRate) -> Option<Amount> {
+        fee_rate.fee_wu(self.input_weight())
+    }
+
+    // Value minus the fee to spend it, in satoshis. Negative means the coin
+    // costs more to spend than it brings in, so selecting it only makes the
+    // transaction more expensive.
+    pub fn effective_value(&self, fee_rate: FeeRate) -> i64 {
+        let value = i64::try_from(self.utxo.value().to_sat()).unwrap_or(i64::MAX);
+        let fee = self
+            .input_fee(fee_rate)
+            .and_then(|fee| i64::try_from(fee.to_sat()).ok())
+            .unwrap_or(i64::MAX);
+        value.saturating_sub(fee)
+    }
+
+    // Worth selecting at all at this fee rate
+    pub fn is_economical(&self, fee_rate: FeeRate) -> bool {
+        self.effective_value(fee_rate) > 0
+    }
+}
+
+impl DerivationInfo {
+    pub fn new(fingerprint: [u8; 4], path: Vec<u32>) -> Self {
+        DerivationInfo { fingerprint, path }
+    }
+}
+
+impl From<(OutPoint, TxOut)> for Utxo {
+    fn from((outpoint, txout): (OutPoint, TxOut)) -> Self {
+        Utxo::new(outpoint, txout)
+    }
+}
+
+impl From<Utxo> for (OutPoint, TxOut) {
+    fn from(utxo: Utxo) -> Self {
+        (utxo.outpoint, utxo.txout)
+    }
+}
+
+impl From<Utxo> for OutPoint {
+    fn from(utxo: Utxo) -> Self {
+        utxo.outpoint
+    }
+}
+
+impl From<Utxo> for TxOut {
+    fn from(utxo: Utxo) -> Self {
+        utxo.txout
+    }
+}
+
+impl From<&Utxo> for TransactionInput {
+    fn from(utxo: &Utxo) -> Self {
+        utxo.to_input()
+    }
+}
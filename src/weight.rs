@@ -0,0 +1,140 @@
+use crate::Amount;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// BIP141 transaction weight. Non-witness bytes cost 4 weight units (wu) each
+// and witness bytes cost 1, which is the "witness discount". Blocks are
+// capped at 4M wu, and fees are quoted per virtual byte (vB), a quarter of a
+// weight unit rounded up.
+#[derive(
+    Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Weight(u64);
+
+impl Weight {
+    pub const ZERO: Weight = Weight(0);
+    pub const WITNESS_SCALE_FACTOR: u64 = 4;
+    pub const MAX_BLOCK: Weight = Weight(4_000_000);
+    // Core won't relay anything heavier (MAX_STANDARD_TX_WEIGHT)
+    pub const MAX_STANDARD_TX: Weight = Weight(400_000);
+
+    pub const fn from_wu(wu: u64) -> Self {
+        Weight(wu)
+    }
+
+    // None if the weight wouldn't fit in a u64, like FeeRate::from_sat_per_vb
+    pub const fn from_vb(vb: u64) -> Option<Self> {
+        match vb.checked_mul(Self::WITNESS_SCALE_FACTOR) {
+            Some(wu) => Some(Weight(wu)),
+            None => None,
+        }
+    }
+
+    // Bytes outside the witness count four times. Real data is nowhere near
+    // u64::MAX / 4 bytes, so this saturates rather than making every const
+    // caller unwrap.
+    pub const fn from_non_witness_data_size(bytes: u64) -> Self {
+        Weight(bytes.saturating_mul(Self::WITNESS_SCALE_FACTOR))
+    }
+
+    pub const fn from_witness_data_size(bytes: u64) -> Self {
+        Weight(bytes)
+    }
+
+    pub const fn to_wu(self) -> u64 {
+        self.0
+    }
+
+    // Virtual size, rounded up like Core's GetVirtualTransactionSize
+    pub const fn to_vbytes_ceil(self) -> u64 {
+        self.0.div_ceil(Self::WITNESS_SCALE_FACTOR)
+    }
+
+    pub fn checked_add(self, rhs: Weight) -> Option<Weight> {
+        self.0.checked_add(rhs.0).map(Weight)
+    }
+
+    pub fn checked_sub(self, rhs: Weight) -> Option<Weight> {
+        self.0.checked_sub(rhs.0).map(Weight)
+    }
+}
+
+impl fmt::Display for Weight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} wu", self.0)
+    }
+}
+
+// Fee per virtual kilobyte, in satoshis, which is how Core stores it
+// (CFeeRate) - 1 sat/vB is 1000 sat/kvB. Using kvB keeps sub-sat/vB rates
+// like 0.1 sat/vB exact.
+#[derive(
+    Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct FeeRate(u64);
+
+impl FeeRate {
+    pub const ZERO: FeeRate = FeeRate(0);
+    // Core's default minrelaytxfee and incrementalrelayfee
+    pub const MIN_RELAY: FeeRate = FeeRate(1_000);
+    // Core's default dustrelayfee
+    pub const DUST_RELAY: FeeRate = FeeRate(3_000);
+
+    pub const fn from_sat_per_kvb(sat_kvb: u64) -> Self {
+        FeeRate(sat_kvb)
+    }
+
+    pub fn from_sat_per_vb(sat_vb: u64) -> Option<Self> {
+        sat_vb.checked_mul(1_000).map(FeeRate)
+    }
+
+    // The rate a transaction actually paid: fee over its vsize
+    pub fn from_fee_and_weight(fee: Amount, weight: Weight) -> Option<Self> {
+        let vsize = weight.to_vbytes_ceil();
+        if vsize == 0 {
+            return None;
+        }
+        fee.to_sat()
+            .checked_mul(1_000)
+            .map(|sat| FeeRate(sat / vsize))
+    }
+
+    pub const fn to_sat_per_kvb(self) -> u64 {
+        self.0
+    }
+
+    pub const fn to_sat_per_vb_floor(self) -> u64 {
+        self.0 / 1_000
+    }
+
+    pub const fn to_sat_per_vb_ceil(self) -> u64 {
+        self.0.div_ceil(1_000)
+    }
+
+    // Fee for `vsize` virtual bytes, rounded up so the rate is never
+    // undershot (Core's CFeeRate::GetFee)
+    pub fn fee_vb(self, vsize: u64) -> Option<Amount> {
+        self.0
+            .checked_mul(vsize)
+            .map(|fee| Amount::from_sat(fee.div_ceil(1_000)))
+    }
+
+    pub fn fee_wu(self, weight: Weight) -> Option<Amount> {
+        self.fee_vb(weight.to_vbytes_ceil())
+    }
+}
+
+// e.g. "1.5 sat/vB"
+impl fmt::Display for FeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (whole, frac) = (self.0 / 1_000, self.0 % 1_000);
+        if frac == 0 {
+            write!(f, "{} sat/vB", whole)
+        } else {
+            let frac = format!("{:03}", frac);
+            write!(f, "{}.{} sat/vB", whole, frac.trim_end_matches('0'))
+        }
+    }
+}
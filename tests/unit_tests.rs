@@ -10,7 +10,16 @@ mod tests {
         txid
     }
 
+    // Native P2WPKH example from BIP143: input 0 is a legacy P2PK spend with
+    // an empty witness, input 1 spends P2WPKH with a [signature, pubkey] stack.
+    const BIP143_P2WPKH_TX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+    // The first bitcoin transaction between two people: Satoshi paying Hal
+    // Finney 10 BTC in block 170. One input, two P2PK outputs, no witness.
+    const BLOCK_170_TX: &str = "0100000001c997a5e56e104102fa209c6a852dd90660a20b2d9c352423edce25857fcd3704000000004847304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901ffffffff0200ca9a3b00000000434104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac00286bee0000000043410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac00000000";
+
     #[test]
+    #[allow(deprecated)]
     fn test_compact_size_serialization() {
         let tests = vec![
             (0u64, vec![0x00]),
@@ -35,6 +44,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_outpoint_roundtrip() {
         let txid = dummy_txid(0xCC);
         let vout = 2;
@@ -43,9 +53,15 @@ mod tests {
         let (parsed, consumed) = OutPoint::from_bytes(&bytes).unwrap();
         assert_eq!(parsed, outpoint);
         assert_eq!(consumed, bytes.len());
+
+        // Slice decoding goes through the streaming codec, so errors say
+        // which field ran short
+        let err = OutPoint::decode(&bytes[..35]).unwrap_err();
+        assert!(matches!(&err, BitcoinError::AtField { field, .. } if field == "vout"));
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_script_roundtrip() {
         let script_data = vec![0x76, 0xA9, 0x14, 0x88, 0xAC];
         let script = Script::new(script_data.clone());
@@ -53,9 +69,16 @@ mod tests {
         let (parsed, consumed) = Script::from_bytes(&bytes).unwrap();
         assert_eq!(parsed, script);
         assert_eq!(consumed, bytes.len());
+
+        // DecodeLimits apply: a 16M length fails before anything is read
+        assert!(matches!(
+            Script::decode(&[0xFE, 0x00, 0x00, 0x00, 0x01]),
+            Err(BitcoinError::OversizedAllocation { .. })
+        ));
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_tx_input_roundtrip() {
         let outpoint = OutPoint::new(dummy_txid(1), 0);
         let script = Script::new(vec![0x01, 0x02]);
@@ -67,6 +90,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_bitcoin_tx_roundtrip() {
         let inputs = vec![TransactionInput::new(
             OutPoint::new(dummy_txid(1), 0),
@@ -110,4 +134,2745 @@ mod tests {
         assert!(output.contains("Lock Time: 0"));
         assert!(output.contains("Previous Output Vout: 7"));
     }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_decoded_matches_legacy_tuple_api() {
+        let outpoint = OutPoint::new(dummy_txid(0x11), 5);
+        let mut bytes = outpoint.to_bytes();
+        bytes.extend_from_slice(&[0xAA, 0xBB]); // trailing data must not be consumed
+
+        let decoded = OutPoint::decode(&bytes).unwrap();
+        assert_eq!(decoded.consumed, 36);
+        assert_eq!(decoded.vout, 5); // Deref to the inner OutPoint
+        assert_eq!(
+            OutPoint::from_bytes(&bytes).unwrap(),
+            decoded.clone().into_parts()
+        );
+        assert_eq!(decoded.into_inner(), outpoint);
+    }
+
+    #[test]
+    fn test_transaction_input_iteration() {
+        let inputs: Vec<_> = (0..3)
+            .map(|i| {
+                TransactionInput::new(
+                    OutPoint::new(dummy_txid(i), i as u32),
+                    Script::new(vec![]),
+                    0xFFFFFFFF,
+                )
+            })
+            .collect();
+        let mut tx = BitcoinTransaction::new(2, inputs[..2].to_vec(), 0);
+        tx.extend(inputs[2..].to_vec());
+        tx.extend([
+            TransactionOutput::new(1000, Script::new(vec![0x51])),
+            TransactionOutput::new(2000, Script::new(vec![0x52])),
+        ]);
+
+        assert_eq!(tx[InputIndex(1)], inputs[1]);
+        assert_eq!(tx[OutputIndex(1)].value.to_sat(), 2000);
+        let vouts: Vec<u32> = tx.inputs().iter().map(|i| i.previous_output.vout).collect();
+        assert_eq!(vouts, vec![0, 1, 2]);
+        let mut values = Vec::new();
+        for output in tx.outputs() {
+            values.push(output.value.to_sat());
+        }
+        assert_eq!(values, vec![1000, 2000]);
+        assert_eq!(tx.iter_inputs().nth(1), Some(&inputs[1]));
+        assert_eq!(tx.iter_outputs().count(), 2);
+    }
+
+    #[test]
+    fn test_defaults() {
+        let tx = BitcoinTransaction::default();
+        assert_eq!(tx.version, 2);
+        assert!(tx.inputs.is_empty());
+        assert_eq!(tx.lock_time, 0);
+        assert!(Script::default().is_empty());
+    }
+
+    #[test]
+    fn test_add_input_chaining_and_input_mut() {
+        let input =
+            |v: u8| TransactionInput::new(OutPoint::new(dummy_txid(v), 0), Script::default(), 0);
+        let mut tx = BitcoinTransaction::default();
+        tx.add_input(input(1)).add_input(input(2));
+        assert_eq!(tx.inputs.len(), 2);
+
+        tx.input_mut(1).unwrap().sequence = 0xFFFFFFFD;
+        assert_eq!(tx[InputIndex(1)].sequence, 0xFFFFFFFD);
+        assert!(tx.input_mut(2).is_none());
+    }
+
+    #[test]
+    fn test_merkle_branch_derives_root() {
+        // Five leaves exercises the "duplicate the odd node" rule on two levels
+        let leaves: Vec<[u8; 32]> = (1..=5).map(dummy_txid).collect();
+        let root = merkle_root(&leaves).unwrap();
+
+        for (i, leaf) in leaves.iter().enumerate() {
+            let branch = MerkleBranch::from_leaves(&leaves, i).unwrap();
+            assert_eq!(branch.hashes.len(), 3);
+            assert_eq!(branch.derive_root(*leaf), root);
+        }
+
+        // Two leaves: root is just SHA256d(a || b)
+        let pair = [dummy_txid(1), dummy_txid(2)];
+        let mut concat = dummy_txid(1).to_vec();
+        concat.extend_from_slice(&dummy_txid(2));
+        assert_eq!(merkle_root(&pair).unwrap(), hashes::sha256d(&concat));
+        assert!(MerkleBranch::from_leaves(&pair, 2).is_none());
+    }
+
+    #[test]
+    fn test_merkle_root_block_100000() {
+        // Txids are displayed byte-reversed, so flip them into internal order
+        let txid = |s: &str| {
+            let mut bytes: [u8; 32] = hex::decode(s).unwrap().try_into().unwrap();
+            bytes.reverse();
+            bytes
+        };
+        let leaves = [
+            txid("8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87"),
+            txid("fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4"),
+            txid("6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4"),
+            txid("e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d"),
+        ];
+        let expected = txid("f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766");
+        assert_eq!(merkle_root(&leaves).unwrap(), expected);
+        let branch = MerkleBranch::from_leaves(&leaves, 2).unwrap();
+        assert_eq!(branch.derive_root(leaves[2]), expected);
+    }
+
+    #[test]
+    fn test_adversarial_length_prefixes_do_not_panic() {
+        // u32::MAX overflows `offset + len` on 32-bit targets, u64::MAX
+        // overflows it everywhere. Both must come back as errors.
+        let mut huge_u32 = vec![0xFE, 0xFF, 0xFF, 0xFF, 0xFF];
+        huge_u32.extend_from_slice(&[0x51; 8]);
+        let mut huge_u64 = vec![0xFF; 9];
+        huge_u64.extend_from_slice(&[0x51; 8]);
+
+        for bytes in [&huge_u32, &huge_u64] {
+            assert!(matches!(
+                Script::decode(bytes).unwrap_err(),
+                BitcoinError::UnexpectedEof { available: 8, .. }
+                    | BitcoinError::OversizedAllocation { .. }
+            ));
+
+            let mut input = OutPoint::new(dummy_txid(1), 0).to_bytes();
+            input.extend_from_slice(bytes);
+            assert!(TransactionInput::decode(&input).is_err());
+        }
+    }
+
+    #[test]
+    fn test_sequence_and_locktime_describe() {
+        assert_eq!(Sequence(0xFFFFFFFF).describe().to_string(), "final");
+        assert_eq!(
+            Sequence::default().describe(),
+            SequenceInterpretation::NoRelativeLockTime { rbf: true }
+        );
+        assert_eq!(Sequence(144).describe().to_string(), "relative: 144 blocks");
+        assert_eq!(
+            Sequence((1 << 22) | 2).describe(),
+            SequenceInterpretation::RelativeTime { seconds: 1024 }
+        );
+
+        assert_eq!(LockTime(0).describe(), LockTimeInterpretation::None);
+        assert_eq!(
+            LockTime(840_000).describe().to_string(),
+            "absolute: block height 840000"
+        );
+        assert_eq!(
+            LockTime(1_717_200_000).describe().to_string(),
+            "absolute: unix time 2024-06-01T00:00:00Z"
+        );
+        assert_eq!(
+            LockTime(u32::MAX).describe().to_string(),
+            "absolute: unix time 2106-02-07T06:28:15Z"
+        );
+
+        let json = serde_json::to_string(&Sequence(144).describe()).unwrap();
+        assert_eq!(json, r#"{"type":"relative_blocks","blocks":144}"#);
+
+        let input = TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::default(), 144);
+        let tx = BitcoinTransaction::new(2, vec![input], 840_000);
+        let output = tx.to_string();
+        assert!(output.contains("Sequence: 0x00000090 (relative: 144 blocks)"));
+        assert!(output.contains("Lock Time: 840000 (absolute: block height 840000)"));
+    }
+
+    #[test]
+    fn test_tx_output_roundtrip() {
+        let output = TxOut::new(50_000, Script::new(vec![0x00, 0x14, 0xAA]));
+        let bytes = output.to_bytes();
+        assert_eq!(&bytes[..8], &50_000u64.to_le_bytes());
+        let decoded = TransactionOutput::decode(&bytes).unwrap();
+        assert_eq!(decoded.value, output);
+        assert_eq!(decoded.consumed, bytes.len());
+        assert!(TransactionOutput::decode(&bytes[..7]).is_err());
+    }
+
+    #[test]
+    fn test_real_legacy_transaction_roundtrip() {
+        let bytes = hex::decode(BLOCK_170_TX).unwrap();
+        let decoded = BitcoinTransaction::decode(&bytes).unwrap();
+        assert_eq!(decoded.consumed, bytes.len());
+
+        let tx = decoded.into_inner();
+        assert_eq!(tx.inputs.len(), 1);
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.outputs[0].value, Amount::from_sat(1_000_000_000)); // 10 BTC to Hal
+        assert_eq!(tx.outputs[1].value, Amount::from_sat(4_000_000_000)); // 40 BTC change
+        assert_eq!(tx.to_bytes(), bytes);
+
+        let mut built = BitcoinTransaction::new(1, tx.inputs.clone(), 0);
+        built
+            .add_output(tx.outputs[0].clone())
+            .add_output(tx.outputs[1].clone());
+        assert_eq!(built, tx);
+    }
+
+    #[test]
+    fn test_segwit_transaction_roundtrip() {
+        let bytes = hex::decode(BIP143_P2WPKH_TX).unwrap();
+        let decoded = BitcoinTransaction::decode(&bytes).unwrap();
+        assert_eq!(decoded.consumed, bytes.len());
+
+        let tx = decoded.into_inner();
+        assert!(tx.has_witness());
+        assert!(tx.inputs[0].witness.is_empty());
+        assert_eq!(tx.inputs[1].witness.len(), 2);
+        assert_eq!(tx.inputs[1].witness[1].len(), 33); // compressed pubkey
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.lock_time, 17);
+        assert_eq!(tx.to_bytes(), bytes);
+
+        // Stripping the witness gives a legacy encoding that parses back
+        // to the same tx minus witnesses
+        let stripped = tx.to_bytes_without_witness();
+        assert_eq!(stripped.len(), bytes.len() - 2 - (1 + 1 + 1 + 71 + 1 + 33));
+        let legacy = BitcoinTransaction::decode(&stripped).unwrap().into_inner();
+        assert!(!legacy.has_witness());
+        assert_eq!(legacy.outputs, tx.outputs);
+    }
+
+    #[test]
+    fn test_forced_segwit_encoding_without_witness() {
+        let bytes = hex::decode(BLOCK_170_TX).unwrap();
+        let tx = BitcoinTransaction::decode(&bytes).unwrap().into_inner();
+        assert_eq!(tx.to_bytes(), bytes); // no witness -> legacy by default
+
+        let forced = tx.to_bytes_with_witness();
+        assert_eq!(&forced[4..6], &[0x00, 0x01]);
+        assert_eq!(forced.len(), bytes.len() + 2 + tx.inputs.len());
+        assert_eq!(
+            BitcoinTransaction::decode(&forced).unwrap().into_inner(),
+            tx
+        );
+    }
+
+    #[test]
+    fn test_witness_constructors() {
+        let der = vec![0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01];
+        let mut sig = Witness::new();
+        sig.push_ecdsa_signature(&der, 0x01);
+        assert_eq!(
+            sig.last().unwrap(),
+            [der.as_slice(), &[0x01]].concat().as_slice()
+        );
+
+        let pubkey = [0x02; 33];
+        let p2wpkh = Witness::p2wpkh(sig.last().unwrap(), pubkey);
+        assert_eq!(p2wpkh.len(), 2);
+        assert_eq!(p2wpkh.second_to_last(), sig.last());
+        assert_eq!(p2wpkh.last(), Some(&pubkey[..]));
+        assert_eq!(p2wpkh.third_to_last(), None);
+        assert_eq!(p2wpkh.tapscript_parts().unwrap().1, &pubkey[..]);
+
+        // <arg1> <arg2> <script> <control block>
+        let leaf = Script::new(vec![0x51]);
+        let control_block = [0xc0; 33];
+        let mut tapscript = Witness::tapscript(&leaf, control_block, [vec![0xAA], vec![0xBB]]);
+        assert_eq!(tapscript.len(), 4);
+        assert_eq!(tapscript.get(0), Some(&[0xAA][..]));
+        assert_eq!(tapscript.third_to_last(), Some(&[0xBB][..]));
+        assert_eq!(tapscript.taproot_annex(), None);
+        assert_eq!(
+            tapscript.tapscript_parts(),
+            Some((&leaf.bytes[..], &control_block[..]))
+        );
+
+        // An annex on top doesn't move where the script and control block are found
+        tapscript.push(vec![0x50, 0x01]);
+        assert_eq!(tapscript.taproot_annex(), Some(&[0x50, 0x01][..]));
+        assert_eq!(
+            tapscript.tapscript_parts(),
+            Some((&leaf.bytes[..], &control_block[..]))
+        );
+        // A lone 0x50-prefixed item is a key-path signature, not an annex
+        assert_eq!(Witness::from_items(vec![vec![0x50]]).taproot_annex(), None);
+    }
+
+    #[test]
+    fn test_witness_roundtrip_and_serde() {
+        let mut witness = Witness::default();
+        witness
+            .push(vec![0x30, 0x44])
+            .push(vec![0x02; 33])
+            .push(Vec::new());
+        assert_eq!(witness.len(), 3);
+        assert_eq!(witness.get(1).unwrap().len(), 33);
+        assert_eq!(witness.last(), Some(&[][..]));
+        assert_eq!(&witness[0], &[0x30, 0x44]);
+
+        let bytes = witness.to_bytes();
+        assert_eq!(bytes[..4], [0x03, 0x02, 0x30, 0x44]);
+        let decoded = Witness::decode(&bytes).unwrap();
+        assert_eq!(decoded.consumed, bytes.len());
+        assert_eq!(decoded.into_inner(), witness);
+        assert!(Witness::decode(&bytes[..bytes.len() - 2]).is_err());
+
+        let json = serde_json::to_string(&Witness::from_items(vec![vec![0xAB, 0xCD]])).unwrap();
+        assert_eq!(json, r#"["abcd"]"#);
+
+        // Legacy inputs leave the witness out of JSON entirely
+        let mut input =
+            TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::default(), 0);
+        assert!(!serde_json::to_string(&input).unwrap().contains("witness"));
+        input.witness = witness;
+        let json = serde_json::to_string(&input).unwrap();
+        assert_eq!(
+            serde_json::from_str::<TransactionInput>(&json).unwrap(),
+            input
+        );
+    }
+
+    #[test]
+    fn test_txid() {
+        let tx = BitcoinTransaction::decode(&hex::decode(BLOCK_170_TX).unwrap())
+            .unwrap()
+            .into_inner();
+        let txid = tx.txid();
+        assert_eq!(
+            txid.to_string(),
+            "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+        );
+        assert_eq!(txid.0[0], 0x16); // stored in internal (hash) byte order
+        assert_eq!(txid.to_string().parse::<Txid>().unwrap(), txid);
+        assert!("abcd".parse::<Txid>().is_err());
+
+        // The txid ignores witness data
+        let segwit = BitcoinTransaction::decode(&hex::decode(BIP143_P2WPKH_TX).unwrap())
+            .unwrap()
+            .into_inner();
+        let mut stripped = segwit.clone();
+        stripped.inputs[1].witness = Witness::new();
+        assert_eq!(segwit.txid(), stripped.txid());
+    }
+
+    #[test]
+    fn test_wtxid() {
+        let segwit = BitcoinTransaction::decode(&hex::decode(BIP143_P2WPKH_TX).unwrap())
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            segwit.wtxid().to_string(),
+            "c36c38370907df2324d9ce9d149d191192f338b37665a82e78e76a12c909b762"
+        );
+        assert_ne!(segwit.wtxid().0, segwit.txid().0);
+
+        // No witness: wtxid == txid
+        let legacy = BitcoinTransaction::decode(&hex::decode(BLOCK_170_TX).unwrap())
+            .unwrap()
+            .into_inner();
+        assert_eq!(legacy.wtxid().0, legacy.txid().0);
+
+        // Coinbase: all zeros, even with a witness (the witness reserved value)
+        let mut coinbase_input = TransactionInput::new(
+            OutPoint::null(),
+            Script::new(vec![0x03, 0x01, 0x02, 0x03]),
+            0,
+        );
+        coinbase_input.witness.push(vec![0u8; 32]);
+        let mut coinbase = BitcoinTransaction::new(2, vec![coinbase_input], 0);
+        coinbase.add_output(TxOut::new(5_000_000_000, Script::new(vec![0x51])));
+        assert!(coinbase.is_coinbase());
+        assert_eq!(coinbase.wtxid().0, [0u8; 32]);
+        assert_ne!(coinbase.txid().0, [0u8; 32]);
+    }
+
+    #[test]
+    fn test_transaction_normalization() {
+        let bytes = hex::decode(BLOCK_170_TX).unwrap();
+        let tx = BitcoinTransaction::decode(&bytes).unwrap().into_inner();
+
+        // A segwit marker with no witness data normalizes to legacy
+        assert_eq!(
+            BitcoinTransaction::normalize(&tx.to_bytes_with_witness(), true).unwrap(),
+            bytes
+        );
+        assert_eq!(
+            BitcoinTransaction::normalize_hex(&format!(" {}\n", BLOCK_170_TX.to_uppercase()), true)
+                .unwrap(),
+            BLOCK_170_TX.to_lowercase()
+        );
+        let mut trailing = bytes.clone();
+        trailing.push(0x00);
+        assert_eq!(
+            BitcoinTransaction::normalize(&trailing, true),
+            Err(BitcoinError::TrailingBytes(1))
+        );
+
+        // Dropping the witness gives the same bytes for a malleated copy
+        let segwit = BitcoinTransaction::decode(&hex::decode(BIP143_P2WPKH_TX).unwrap())
+            .unwrap()
+            .into_inner();
+        let mut malleated = segwit.clone();
+        malleated.inputs[1].witness = Witness::from_items(vec![vec![0x01]]);
+        assert!(segwit.canonical_eq(&segwit.clone()));
+        assert!(!segwit.canonical_eq(&malleated));
+        assert_eq!(
+            BitcoinTransaction::normalize(&segwit.to_bytes(), false).unwrap(),
+            BitcoinTransaction::normalize(&malleated.to_bytes(), false).unwrap()
+        );
+
+        // Sorting goes by displayed txid, then dedups cleanly
+        let mut txs = vec![segwit.clone(), tx.clone(), malleated.clone(), tx.clone()];
+        let mut sorted = txs.clone();
+        sorted.sort();
+        BitcoinTransaction::sort_by_txid(&mut txs);
+        assert_eq!(txs, sorted);
+        txs.dedup();
+        assert_eq!(txs.len(), 3);
+        assert!(
+            txs.windows(2)
+                .all(|w| w[0].txid().to_string() <= w[1].txid().to_string())
+        );
+
+        // PartialOrd follows the displayed txid, then the witness
+        let (low, high) = if tx.txid().to_string() < segwit.txid().to_string() {
+            (&tx, &segwit)
+        } else {
+            (&segwit, &tx)
+        };
+        assert!(low < high);
+        assert!(low.txid() < high.txid());
+        assert_ne!(
+            segwit.partial_cmp(&malleated),
+            Some(std::cmp::Ordering::Equal)
+        );
+        assert!(tx <= tx.clone());
+    }
+
+    #[test]
+    fn test_decode_error_reports_field_and_offset() {
+        // Cut block 170's tx off partway through the first scriptSig, which
+        // starts at byte 41 (version 4 + count 1 + outpoint 36)
+        let bytes = hex::decode(BLOCK_170_TX).unwrap();
+        let err = BitcoinTransaction::decode(&bytes[..60]).unwrap_err();
+        assert_eq!(
+            err,
+            BitcoinError::AtField {
+                field: "inputs[0].script_sig".to_string(),
+                offset: 41,
+                source: Box::new(BitcoinError::UnexpectedEof {
+                    needed: 72,
+                    available: 18,
+                }),
+            }
+        );
+
+        // Missing lock_time on an otherwise complete tx
+        let err = BitcoinTransaction::decode(&bytes[..bytes.len() - 2]).unwrap_err();
+        match err {
+            BitcoinError::AtField { field, offset, .. } => {
+                assert_eq!(field, "lock_time");
+                assert_eq!(offset, bytes.len() - 4);
+            }
+            other => panic!("expected AtField, got {:?}", other),
+        }
+
+        // List and witness indexes show up in the path too. Input 1's
+        // witness ends with a 33-byte pubkey, just before the lock_time.
+        let bytes = hex::decode(BIP143_P2WPKH_TX).unwrap();
+        match BitcoinTransaction::decode(&bytes[..bytes.len() - 10]).unwrap_err() {
+            BitcoinError::AtField { field, offset, .. } => {
+                assert_eq!(field, "witnesses[1][1]");
+                assert_eq!(offset, bytes.len() - 4 - 34);
+            }
+            other => panic!("expected AtField, got {:?}", other),
+        }
+        assert_eq!(
+            PathSegment::NamedIndex("witnesses", 3).to_string(),
+            "witnesses[3]"
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_compact_size_minimal_encoding() {
+        // FD 05 00 is 5 written the long way
+        let padded = [0xFD, 0x05, 0x00];
+        assert_eq!(
+            CompactSize::decode(&padded).unwrap().value,
+            CompactSize::new(5)
+        );
+        assert_eq!(
+            CompactSize::decode_minimal(&padded),
+            Err(BitcoinError::NonMinimalVarInt)
+        );
+        assert_eq!(
+            CompactSize::from_bytes_minimal(&[0xFE, 0xFF, 0xFF, 0x00, 0x00]),
+            Err(BitcoinError::NonMinimalVarInt)
+        );
+
+        for value in [0u64, 0xFC, 0xFD, 0xFFFF, 0x1_0000, 0xFFFF_FFFF, u64::MAX] {
+            let bytes = CompactSize::new(value).to_bytes();
+            assert_eq!(CompactSize::new(value).encoded_len(), bytes.len());
+            assert_eq!(
+                CompactSize::from_bytes_minimal(&bytes).unwrap(),
+                (CompactSize::new(value), bytes.len())
+            );
+        }
+
+        // Pad block 170's input count: transactions decode leniently by
+        // default, and strictly under DecodeLimits::minimal()
+        let bytes = hex::decode(BLOCK_170_TX).unwrap();
+        let mut padded_tx = bytes[..4].to_vec();
+        padded_tx.extend_from_slice(&[0xFD, 0x01, 0x00]);
+        padded_tx.extend_from_slice(&bytes[5..]);
+        let tx = BitcoinTransaction::decode(&padded_tx).unwrap();
+        assert_eq!(tx.to_bytes(), bytes);
+        assert_eq!(
+            BitcoinTransaction::decode_with_limits(&padded_tx, DecodeLimits::minimal())
+                .unwrap_err()
+                .root_cause(),
+            &BitcoinError::NonMinimalVarInt
+        );
+
+        // A script on its own follows the same policy as one in a transaction
+        let padded_script = [0xFD, 0x01, 0x00, 0x51];
+        assert_eq!(
+            Script::decode(&padded_script).unwrap().value,
+            Script::new(vec![0x51])
+        );
+        assert_eq!(
+            TransactionOutput::decode(&[&[0u8; 8][..], &padded_script].concat())
+                .unwrap()
+                .script_pubkey,
+            Script::new(vec![0x51])
+        );
+    }
+
+    #[test]
+    fn test_address_clusters_common_input_ownership() {
+        use std::collections::HashMap;
+
+        let script = |b: u8| Script::new(vec![0x00, 0x14, b]);
+        let mut prevouts = HashMap::new();
+        for (i, b) in [b'a', b'b', b'e'].into_iter().enumerate() {
+            prevouts.insert(OutPoint::new(dummy_txid(i as u8), 0), script(b));
+        }
+        let spend = |outpoints: &[OutPoint], outputs: &[u8]| {
+            let inputs = outpoints
+                .iter()
+                .map(|op| TransactionInput::new(op.clone(), Script::new(vec![]), 0xFFFFFFFF))
+                .collect();
+            let mut tx = BitcoinTransaction::new(2, inputs, 0);
+            for &b in outputs {
+                tx.add_output(TxOut::new(1_000, script(b)));
+            }
+            tx
+        };
+
+        // a + b spent together, paying c and d
+        let tx1 = spend(
+            &[
+                OutPoint::new(dummy_txid(0), 0),
+                OutPoint::new(dummy_txid(1), 0),
+            ],
+            b"cd",
+        );
+        prevouts.insert(OutPoint::new(tx1.txid().0, 0), script(b'c'));
+        // c + e spent together
+        let tx2 = spend(
+            &[
+                OutPoint::new(tx1.txid().0, 0),
+                OutPoint::new(dummy_txid(2), 0),
+            ],
+            b"f",
+        );
+        // A coinjoin mixing a and e must not merge anything
+        let coinjoin = spend(
+            &[
+                OutPoint::new(dummy_txid(0), 0),
+                OutPoint::new(dummy_txid(2), 0),
+            ],
+            b"gh",
+        );
+
+        let mut clusters = AddressClusters::new();
+        clusters.exclude(coinjoin.txid());
+        for tx in [&tx1, &tx2, &coinjoin] {
+            clusters.add_transaction(tx, |op| prevouts.get(op).cloned());
+        }
+
+        assert!(clusters.same_cluster(&script(b'a'), &script(b'b')));
+        assert!(clusters.same_cluster(&script(b'c'), &script(b'e')));
+        assert!(!clusters.same_cluster(&script(b'a'), &script(b'e')));
+        assert!(!clusters.same_cluster(&script(b'g'), &script(b'h')));
+        assert_eq!(clusters.cluster_size(&script(b'd')), Some(1));
+        assert_eq!(clusters.cluster_of(&script(b'b')).unwrap().len(), 2);
+        assert_eq!(clusters.cluster_of(&script(b'z')), None);
+        // {a,b} {c,e} {d} {f} {g} {h}
+        assert_eq!(clusters.len(), 8);
+        assert_eq!(clusters.clusters().len(), 6);
+    }
+
+    #[test]
+    fn test_decode_limits_reject_oversized_counts() {
+        // Version, then an input count of 2^32 with nothing behind it
+        let mut bytes = 1u32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&[0xFF, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+        let err = BitcoinTransaction::decode(&bytes).unwrap_err();
+        assert!(matches!(
+            err.root_cause(),
+            BitcoinError::OversizedAllocation {
+                requested: 0x1_0000_0000,
+                ..
+            }
+        ));
+
+        // Block 170's tx is fine by default but over tighter custom limits
+        let tx_bytes = hex::decode(BLOCK_170_TX).unwrap();
+        assert!(BitcoinTransaction::decode_with_limits(&tx_bytes, DecodeLimits::default()).is_ok());
+        let limits = DecodeLimits {
+            max_outputs: 1,
+            ..DecodeLimits::default()
+        };
+        let err = BitcoinTransaction::decode_with_limits(&tx_bytes, limits).unwrap_err();
+        assert_eq!(
+            err.root_cause(),
+            &BitcoinError::OversizedAllocation {
+                requested: 2,
+                limit: 1
+            }
+        );
+        let limits = DecodeLimits {
+            max_total_size: 200,
+            ..DecodeLimits::default()
+        };
+        assert!(matches!(
+            BitcoinTransaction::decode_with_limits(&tx_bytes, limits)
+                .unwrap_err()
+                .root_cause(),
+            BitcoinError::OversizedAllocation { .. }
+        ));
+    }
+
+    #[test]
+    fn test_unbounded_counts_never_preallocate() {
+        // With every limit off, a count of 2^32 + 1 reaches the list and
+        // witness decoders. Capacity is clamped before any usize cast, so
+        // this is a clean EOF instead of a huge (or truncated) allocation.
+        let count = [0xFF, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00];
+        let mut reader = &count[..];
+        let mut decoder = Decoder::with_budget(
+            &mut reader,
+            DecodeLimits::unlimited(),
+            DecodeBudget::unlimited(),
+        );
+        let err = decoder
+            .decode_list::<TransactionInput>(u64::MAX)
+            .unwrap_err();
+        assert!(matches!(
+            err.root_cause(),
+            BitcoinError::UnexpectedEof { .. }
+        ));
+        let mut reader = &count[..];
+        let mut decoder = Decoder::with_budget(
+            &mut reader,
+            DecodeLimits::unlimited(),
+            DecodeBudget::unlimited(),
+        );
+        let err = Witness::consensus_decode_from(&mut decoder).unwrap_err();
+        assert!(matches!(
+            err.root_cause(),
+            BitcoinError::UnexpectedEof { .. }
+        ));
+    }
+
+    // Where usize is 32 bits a length over u32::MAX can't be converted at
+    // all, and must be an error rather than wrapping to something small
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    fn test_lengths_over_usize_are_rejected() {
+        let mut bytes = vec![0xFF, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00];
+        bytes.push(0x51);
+        let mut reader = &bytes[..];
+        let mut decoder = Decoder::with_budget(
+            &mut reader,
+            DecodeLimits::unlimited(),
+            DecodeBudget::unlimited(),
+        );
+        assert_eq!(
+            Script::consensus_decode_from(&mut decoder)
+                .unwrap_err()
+                .root_cause(),
+            &BitcoinError::OversizedAllocation {
+                requested: 0x1_0000_0001,
+                limit: usize::MAX as u64
+            }
+        );
+    }
+
+    #[test]
+    fn test_coinjoin_classification() {
+        let tx_with = |inputs: usize, values: &[u64]| {
+            let inputs = (0..inputs)
+                .map(|i| {
+                    TransactionInput::new(
+                        OutPoint::new(dummy_txid(i as u8), 0),
+                        Script::new(vec![]),
+                        0xFFFFFFFF,
+                    )
+                })
+                .collect();
+            let mut tx = BitcoinTransaction::new(2, inputs, 0);
+            for &value in values {
+                tx.add_output(TxOut::new(value, Script::new(vec![0x51])));
+            }
+            tx
+        };
+
+        let whirlpool = classify_coinjoin(&tx_with(5, &[1_000_000; 5])).unwrap();
+        assert_eq!(whirlpool.kind, CoinJoinKind::Whirlpool);
+        assert_eq!(whirlpool.denomination, Amount::from_sat(1_000_000));
+        assert!(whirlpool.confidence > 0.9);
+
+        let wasabi = classify_coinjoin(&tx_with(60, &[10_000_000; 40])).unwrap();
+        assert_eq!(wasabi.kind, CoinJoinKind::Wasabi);
+        assert_eq!(wasabi.equal_outputs, 40);
+
+        // 3 makers + taker: 4 equal outputs plus 3 change outputs
+        let joinmarket = classify_coinjoin(&tx_with(
+            6,
+            &[
+                2_500_000, 2_500_000, 2_500_000, 2_500_000, 31_337, 400_120, 9_999,
+            ],
+        ))
+        .unwrap();
+        assert_eq!(joinmarket.kind, CoinJoinKind::JoinMarket);
+        assert_eq!(joinmarket.equal_outputs, 4);
+
+        // Ordinary payment + change, and the real block 170 tx
+        assert_eq!(classify_coinjoin(&tx_with(1, &[50_000, 12_345])), None);
+        let block_170 = BitcoinTransaction::decode(&hex::decode(BLOCK_170_TX).unwrap()).unwrap();
+        assert_eq!(classify_coinjoin(&block_170), None);
+    }
+
+    #[test]
+    fn test_murmur3_vectors() {
+        // From Bitcoin Core's hash_tests.cpp
+        let cases: [(u32, u32, &str); 8] = [
+            (0x00000000, 0x00000000, ""),
+            (0x6a396f08, 0xFBA4C795, ""),
+            (0x81f16f39, 0xffffffff, ""),
+            (0x514e28b7, 0x00000000, "00"),
+            (0xea3f0b17, 0xFBA4C795, "00"),
+            (0x16c6b7ab, 0x00000000, "0011"),
+            (0xb4471bf8, 0x00000000, "00112233"),
+            (0xb4698def, 0x00000000, "001122334455667788"),
+        ];
+        for (expected, seed, data) in cases {
+            assert_eq!(
+                hashes::murmur3_32(seed, &hex::decode(data).unwrap()),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_siphash_vectors() {
+        use hashes::siphash::{SipHasher24, siphash24};
+        use std::hash::Hasher;
+
+        // Reference key 00..0f and messages 00..(n-1), as in Core's hash_tests
+        let (k0, k1) = (0x0706050403020100, 0x0F0E0D0C0B0A0908);
+        let message: Vec<u8> = (0..16).collect();
+        assert_eq!(siphash24(k0, k1, &[]), 0x726fdb47dd0e0e31);
+        assert_eq!(siphash24(k0, k1, &message[..1]), 0x74f839c593dc67fd);
+        assert_eq!(siphash24(k0, k1, &message[..8]), 0x93f5f5799a932462);
+        assert_eq!(siphash24(k0, k1, &message[..15]), 0xa129ca6149be45e5);
+        assert_eq!(siphash24(k0, k1, &message), 0x3f2acc7f57c29bdb);
+
+        // Writing in pieces gives the same answer as one call
+        let mut hasher = SipHasher24::new(k0, k1);
+        hasher.write(&message[..3]);
+        hasher.write(&message[3..11]);
+        hasher.write(&message[11..]);
+        assert_eq!(hasher.finish(), 0x3f2acc7f57c29bdb);
+    }
+
+    #[test]
+    fn test_seen_tracker_rolls_over() {
+        let mut tracker = SeenTracker::new(100, 0.0001);
+        let txid = |i: u32| {
+            let mut bytes = [0u8; 32];
+            bytes[..4].copy_from_slice(&i.to_le_bytes());
+            Txid(bytes)
+        };
+
+        for i in 0..100 {
+            assert!(tracker.check_and_insert_txid(&txid(i)));
+        }
+        // Everything recent is remembered, nothing is processed twice
+        for i in 0..100 {
+            assert!(tracker.contains_txid(&txid(i)));
+            assert!(!tracker.check_and_insert_txid(&txid(i)));
+        }
+
+        // After a few hundred more, the first batch has rolled out
+        for i in 100..400 {
+            tracker.insert_txid(&txid(i));
+        }
+        let still_seen = (0..100)
+            .filter(|&i| tracker.contains_txid(&txid(i)))
+            .count();
+        assert!(still_seen < 5, "{} old entries survived", still_seen);
+        assert!(tracker.contains_txid(&txid(399)));
+
+        tracker.reset();
+        assert!(!tracker.contains_txid(&txid(399)));
+    }
+
+    #[test]
+    fn test_opcode_byte_roundtrip() {
+        for byte in 0..=255u8 {
+            let op = Opcode::from_byte(byte);
+            assert_eq!(op.to_byte(), byte);
+            if let Some(named) = Opcode::from_name(&op.to_string()) {
+                assert_eq!(named, op);
+            }
+        }
+        assert_eq!(Opcode::from_byte(0xac).to_string(), "OP_CHECKSIG");
+        assert_eq!(Opcode::from_byte(0x14), Opcode::PushBytes(20));
+        assert_eq!(Opcode::from_byte(0xc0), Opcode::Unknown(0xc0));
+        assert_eq!(Opcode::Op16.small_int(), Some(16));
+        assert_eq!(Opcode::from_small_int(0), Some(Opcode::Op0));
+    }
+
+    #[test]
+    fn test_script_instructions() {
+        // P2PKH: OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+        let mut p2pkh = vec![0x76, 0xa9, 0x14];
+        p2pkh.extend_from_slice(&[0x11; 20]);
+        p2pkh.extend_from_slice(&[0x88, 0xac]);
+        let script = Script::new(p2pkh);
+        let instructions: Vec<_> = script.instructions().collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::Op(Opcode::Dup),
+                Instruction::Op(Opcode::Hash160),
+                Instruction::PushBytes(&[0x11; 20]),
+                Instruction::Op(Opcode::EqualVerify),
+                Instruction::Op(Opcode::CheckSig),
+            ]
+        );
+
+        // OP_PUSHDATA1 and OP_PUSHDATA2 read their length prefix
+        let mut pushdata = vec![0x4c, 0x03, 1, 2, 3, 0x4d, 0x00, 0x01];
+        pushdata.extend_from_slice(&[0xEE; 256]);
+        let script = Script::new(pushdata);
+        let mut iter = script.instructions();
+        assert_eq!(iter.next(), Some(Ok(Instruction::PushBytes(&[1, 2, 3]))));
+        assert_eq!(iter.next(), Some(Ok(Instruction::PushBytes(&[0xEE; 256]))));
+        assert_eq!(iter.next(), None);
+
+        // A push running off the end is an error, and iteration stops there
+        let script = Script::new(vec![0x51, 0x4d, 0xFF, 0x00, 0xAA]);
+        let mut iter = script.instructions();
+        assert_eq!(iter.next(), Some(Ok(Instruction::Op(Opcode::Op1))));
+        assert_eq!(
+            iter.next(),
+            Some(Err(BitcoinError::MalformedPush {
+                offset: 1,
+                needed: 255,
+                available: 1
+            }))
+        );
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_script_validate() {
+        // 520 bytes is the largest push allowed, 521 is one too many
+        let ok = Script::builder().push_slice(&[0xAA; 520]).into_script();
+        assert_eq!(ok.validate(), Ok(()));
+        let too_big = Script::builder()
+            .push_opcode(Opcode::Op1)
+            .push_slice(&[0xAA; 521])
+            .into_script();
+        assert_eq!(
+            too_big.validate(),
+            Err(BitcoinError::OversizedPush {
+                offset: 1,
+                len: 521,
+                limit: 520
+            })
+        );
+
+        let long = Script::new(vec![0x61; 10_001]); // OP_NOP x 10,001
+        assert_eq!(
+            long.validate(),
+            Err(BitcoinError::ScriptTooLarge {
+                len: 10_001,
+                limit: 10_000
+            })
+        );
+        assert!(Script::new(vec![0x02, 0xAA]).validate().is_err()); // truncated push
+
+        // The lenient decoder keeps the truncated push, strict mode refuses it
+        let bytes = Script::new(vec![0x02, 0xAA]).to_bytes();
+        assert!(Script::decode(&bytes).is_ok());
+        assert!(matches!(
+            Script::decode_strict(&bytes),
+            Err(BitcoinError::MalformedPush { .. })
+        ));
+        assert_eq!(Script::from_bytes_strict(&ok.to_bytes()).unwrap().0, ok);
+    }
+
+    #[test]
+    fn test_script_classification() {
+        // Real outputs: block 170 pays to bare pubkeys, the BIP143 example to P2PKH
+        let block_170 = BitcoinTransaction::decode(&hex::decode(BLOCK_170_TX).unwrap()).unwrap();
+        assert_eq!(
+            block_170.outputs[0].script_pubkey.classify(),
+            ScriptType::P2pk
+        );
+        let bip143 = BitcoinTransaction::decode(&hex::decode(BIP143_P2WPKH_TX).unwrap()).unwrap();
+        assert!(bip143.outputs[0].script_pubkey.is_p2pkh());
+
+        let with_program = |version_op: u8, len: usize| {
+            let mut bytes = vec![version_op, len as u8];
+            bytes.extend(vec![0xAB; len]);
+            Script::new(bytes)
+        };
+        assert!(with_program(0x00, 20).is_p2wpkh());
+        assert!(with_program(0x00, 32).is_p2wsh());
+        assert!(with_program(0x51, 32).is_p2tr());
+        assert_eq!(with_program(0x00, 32).classify(), ScriptType::P2wsh);
+        assert_eq!(
+            with_program(0x52, 40).classify(),
+            ScriptType::WitnessUnknown { version: 2 }
+        );
+        assert_eq!(with_program(0x00, 25).classify(), ScriptType::NonStandard);
+
+        let mut p2sh = vec![0xa9, 0x14];
+        p2sh.extend_from_slice(&[0u8; 20]);
+        p2sh.push(0x87);
+        assert_eq!(Script::new(p2sh).classify(), ScriptType::P2sh);
+        assert!(Script::new(vec![0x6a, 0x04, 1, 2, 3, 4]).is_op_return());
+
+        // 1-of-2 bare multisig
+        let mut multisig = vec![0x51, 0x21];
+        multisig.extend_from_slice(&[0x02; 33]);
+        multisig.push(0x21);
+        multisig.extend_from_slice(&[0x03; 33]);
+        multisig.extend_from_slice(&[0x52, 0xae]);
+        assert_eq!(
+            Script::new(multisig).classify(),
+            ScriptType::Multisig {
+                required: 1,
+                keys: 2
+            }
+        );
+        assert_eq!(ScriptType::P2wpkh.to_string(), "witness_v0_keyhash");
+        assert_eq!(
+            serde_json::to_string(&ScriptType::OpReturn).unwrap(),
+            "\"nulldata\""
+        );
+    }
+
+    #[test]
+    fn test_op_return_classification() {
+        let op_return = || Script::builder().push_opcode(Opcode::Return);
+
+        // Omni simple send: "omni", version 0, type 0, then property and amount
+        let omni = hex::decode("6f6d6e6900000000000000010000000005f5e100").unwrap();
+        let script = op_return().push_slice(&omni).into_script();
+        assert_eq!(
+            classify_op_return(&script),
+            Some(OpReturnData::Omni {
+                payload: omni[4..].to_vec()
+            })
+        );
+
+        // Runestone pushes are joined together
+        let script = op_return()
+            .push_opcode(Opcode::Op13)
+            .push_slice(&[0x14, 0x01])
+            .push_slice(&[0x04, 0x02])
+            .into_script();
+        assert_eq!(
+            classify_op_return(&script),
+            Some(OpReturnData::Runestone {
+                payload: vec![0x14, 0x01, 0x04, 0x02]
+            })
+        );
+
+        let script = op_return()
+            .push_slice("hello, world".as_bytes())
+            .into_script();
+        let data = classify_op_return(&script).unwrap();
+        assert!(matches!(&data, OpReturnData::Text { text, .. } if text == "hello, world"));
+        assert_eq!(data.payload(), b"hello, world");
+        assert_eq!(
+            serde_json::to_value(&data).unwrap()["protocol"],
+            serde_json::json!("text")
+        );
+
+        // Binary data, non-push opcodes and truncated pushes are all Unknown
+        for (script, payload) in [
+            (
+                op_return().push_slice(&[0x00, 0xff]).into_script(),
+                vec![0x00, 0xff],
+            ),
+            (
+                op_return().push_opcode(Opcode::Op1).into_script(),
+                vec![0x51],
+            ),
+            (Script::new(vec![0x6a, 0x05, 0x01]), vec![0x05, 0x01]),
+            (op_return().into_script(), vec![]),
+        ] {
+            assert_eq!(
+                classify_op_return(&script),
+                Some(OpReturnData::Unknown { payload })
+            );
+        }
+        assert_eq!(classify_op_return(&Script::new(vec![0x51])), None);
+    }
+
+    #[test]
+    fn test_script_builder_minimal_pushes() {
+        // P2PKH built from parts matches the template
+        let p2pkh = Script::builder()
+            .push_opcode(Opcode::Dup)
+            .push_opcode(Opcode::Hash160)
+            .push_slice(&[0x11; 20])
+            .push_opcode(Opcode::EqualVerify)
+            .push_opcode(Opcode::CheckSig)
+            .into_script();
+        assert!(p2pkh.is_p2pkh());
+
+        // Push encodings by size, and the single-byte values with opcodes
+        let pushed = |data: &[u8]| Script::builder().push_slice(data).into_script().bytes;
+        assert_eq!(pushed(&[]), vec![0x00]);
+        assert_eq!(pushed(&[0x05]), vec![0x55]);
+        assert_eq!(pushed(&[0x81]), vec![0x4f]);
+        assert_eq!(pushed(&[0x00]), vec![0x01, 0x00]);
+        assert_eq!(pushed(&[0xAA; 75])[0], 0x4b);
+        assert_eq!(pushed(&[0xAA; 76])[..2], [0x4c, 76]);
+        assert_eq!(pushed(&[0xAA; 256])[..3], [0x4d, 0x00, 0x01]);
+        assert_eq!(pushed(&[0xAA; 70_000])[..5], [0x4e, 0x70, 0x11, 0x01, 0x00]);
+
+        // Script numbers
+        let int = |n: i64| Script::builder().push_int(n).into_script().bytes;
+        assert_eq!(int(0), vec![0x00]);
+        assert_eq!(int(-1), vec![0x4f]);
+        assert_eq!(int(16), vec![0x60]);
+        assert_eq!(int(17), vec![0x01, 0x11]);
+        assert_eq!(int(-2), vec![0x01, 0x82]);
+        assert_eq!(int(128), vec![0x02, 0x80, 0x00]);
+        assert_eq!(int(-255), vec![0x02, 0xff, 0x80]);
+        // 500000 as in a CLTV script
+        assert_eq!(int(500_000), vec![0x03, 0x20, 0xa1, 0x07]);
+
+        let multisig = Script::builder()
+            .push_int(1)
+            .push_key([0x02; 33])
+            .push_key([0x03; 33])
+            .push_int(2)
+            .push_opcode(Opcode::CheckMultiSig)
+            .into_script();
+        assert_eq!(
+            multisig.classify(),
+            ScriptType::Multisig {
+                required: 1,
+                keys: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_script_asm_roundtrip() {
+        let bip143 = BitcoinTransaction::decode(&hex::decode(BIP143_P2WPKH_TX).unwrap()).unwrap();
+        let p2pkh = &bip143.outputs[0].script_pubkey;
+        let asm = p2pkh.to_asm();
+        assert_eq!(
+            asm,
+            format!(
+                "OP_DUP OP_HASH160 {} OP_EQUALVERIFY OP_CHECKSIG",
+                hex::encode(&p2pkh.bytes[3..23])
+            )
+        );
+        assert_eq!(&Script::from_asm(&asm).unwrap(), p2pkh);
+
+        // Numbers: small-int opcodes, short pushes as script numbers
+        let script = Script::builder()
+            .push_int(0)
+            .push_int(-1)
+            .push_int(16)
+            .push_int(500_000)
+            .push_int(-300)
+            .push_opcode(Opcode::CheckLockTimeVerify)
+            .push_opcode(Opcode::Unknown(0xc0))
+            .into_script();
+        let asm = script.to_string();
+        assert_eq!(
+            asm,
+            "0 -1 16 500000 -300 OP_CHECKLOCKTIMEVERIFY OP_UNKNOWN_192"
+        );
+        assert_eq!(Script::from_asm(&asm).unwrap(), script);
+
+        // Names without OP_, and a leading-zero token is hex
+        assert_eq!(
+            Script::from_asm("DUP 0011").unwrap().bytes,
+            vec![0x76, 0x02, 0x00, 0x11]
+        );
+        assert_eq!(Script::new(vec![0x51, 0x4c, 0x05]).to_asm(), "1 [error]");
+
+        // Hand-written ASM uses the OP_n names; they parse to the same bytes
+        // to_asm writes as plain numbers
+        let p2wpkh = format!("OP_0 {}", "ab".repeat(20));
+        let script = Script::from_asm(&p2wpkh).unwrap();
+        assert!(script.is_p2wpkh());
+        assert_eq!(script.to_asm(), format!("0 {}", "ab".repeat(20)));
+        assert_eq!(Script::from_asm(&script.to_asm()).unwrap(), script);
+        let keys = [
+            format!("02{}", "11".repeat(32)),
+            format!("03{}", "22".repeat(32)),
+        ];
+        let multisig = Script::from_asm(&format!(
+            "OP_2 {} {} OP_2 OP_CHECKMULTISIG",
+            keys[0], keys[1]
+        ))
+        .unwrap();
+        assert_eq!(multisig.bytes[0], 0x52);
+        assert_eq!(&multisig.bytes[multisig.len() - 2..], &[0x52, 0xae]);
+        assert_eq!(Script::from_asm(&multisig.to_asm()).unwrap(), multisig);
+        assert_eq!(
+            Script::from_asm("OP_FALSE OP_TRUE OP_16").unwrap().bytes,
+            vec![0x00, 0x51, 0x60]
+        );
+        assert_eq!(
+            Script::from_asm("OP_DUP OP_BOGUS"),
+            Err(BitcoinError::InvalidAsm {
+                token: "OP_BOGUS".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_address_roundtrips() {
+        // hash160 of the compressed pubkey for private key 1
+        let hash: [u8; 20] = hex::decode("751e76e8199196d454941c45d1b3a323f1433bd6")
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        let p2pkh = Address::p2pkh(hash, Network::Mainnet);
+        assert_eq!(p2pkh.to_string(), "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH");
+        assert_eq!(
+            Address::p2pkh(hash, Network::Testnet).to_string(),
+            "mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r"
+        );
+        assert!(p2pkh.script_pubkey().is_p2pkh());
+
+        // BIP173 and BIP350 vectors
+        let p2wpkh: Address = "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4"
+            .parse()
+            .unwrap();
+        assert_eq!(p2wpkh, Address::p2wpkh(hash, Network::Mainnet));
+        assert_eq!(
+            hex::encode(&p2wpkh.script_pubkey().bytes),
+            "0014751e76e8199196d454941c45d1b3a323f1433bd6"
+        );
+        let p2tr: Address = "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0"
+            .parse()
+            .unwrap();
+        assert!(p2tr.script_pubkey().is_p2tr());
+        assert_eq!(
+            Address::from_script(&p2tr.script_pubkey(), Network::Mainnet).unwrap(),
+            p2tr
+        );
+
+        // Every kind survives Display -> FromStr and serde on every network
+        for network in [Network::Mainnet, Network::Testnet, Network::Regtest] {
+            for address in [
+                Address::p2pkh(hash, network),
+                Address::p2sh(hash, network),
+                Address::p2wpkh(hash, network),
+                Address::p2wsh([7u8; 32], network),
+                Address::p2tr([9u8; 32], network),
+            ] {
+                let parsed: Address = address.to_string().parse().unwrap();
+                assert_eq!(parsed.payload(), address.payload());
+                let json = serde_json::to_string(&address).unwrap();
+                assert_eq!(json, format!("\"{}\"", address));
+                let back: Address = serde_json::from_str(&json).unwrap();
+                assert_eq!(back.payload(), address.payload());
+            }
+        }
+
+        // Bad checksum, and a v0 program under a bech32m checksum
+        assert_eq!(
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5".parse::<Address>(),
+            Err(BitcoinError::InvalidChecksum)
+        );
+        assert!(
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh"
+                .parse::<Address>()
+                .is_err()
+        );
+        assert!(Address::from_script(&Script::new(vec![0x6a]), Network::Mainnet).is_err());
+
+        // An HRP that only starts with "bc" is not mainnet
+        let prefixed = bech32::encode_segwit("bc1x", 0, &[7; 20]).unwrap();
+        assert!(matches!(
+            prefixed.parse::<Address>(),
+            Err(BitcoinError::InvalidAddress(_))
+        ));
+
+        // Segwit payloads are checked up front, so Display can't fail
+        assert!(Address::from_witness_program(17, &[0; 32], Network::Mainnet).is_err());
+        assert!(Address::from_witness_program(0, &[0; 25], Network::Mainnet).is_err());
+        let v2 = Address::from_witness_program(2, &[0; 16], Network::Mainnet).unwrap();
+        assert_eq!(v2.to_string().parse::<Address>().unwrap(), v2);
+        assert_eq!(v2.script_pubkey().bytes[..2], [0x52, 0x10]);
+    }
+
+    #[test]
+    fn test_bech32_module() {
+        // BIP173 (bech32) and BIP350 (bech32m) generic vectors
+        for (s, variant) in [
+            ("A12UEL5L", bech32::Variant::Bech32),
+            ("a12uel5l", bech32::Variant::Bech32),
+            (
+                "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw",
+                bech32::Variant::Bech32,
+            ),
+            ("A1LQFN3A", bech32::Variant::Bech32m),
+            (
+                "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx",
+                bech32::Variant::Bech32m,
+            ),
+        ] {
+            let (hrp, data, got) = bech32::decode(s).unwrap();
+            assert_eq!(got, variant);
+            assert_eq!(
+                bech32::encode(&hrp, &data, variant).unwrap(),
+                s.to_lowercase()
+            );
+        }
+        // The data part of "abcdef1qpzry..." is every character in order
+        let (_, data, _) = bech32::decode("abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw").unwrap();
+        assert_eq!(data, (0..32).collect::<Vec<u8>>());
+
+        assert!(bech32::decode("A1G7SGD8").is_err()); // mixed-checksum garbage
+        assert!(bech32::decode("a12UEL5L").is_err()); // mixed case
+        assert!(bech32::encode("", &[], bech32::Variant::Bech32).is_err());
+
+        // Witness-version-aware encoding: v0 -> bech32, v1+ -> bech32m
+        let v1 = bech32::encode_segwit("tb", 1, &[0xAB; 32]).unwrap();
+        assert_eq!(bech32::decode(&v1).unwrap().2, bech32::Variant::Bech32m);
+        assert_eq!(
+            bech32::decode_segwit(&v1).unwrap(),
+            ("tb".to_string(), 1, vec![0xAB; 32])
+        );
+        assert!(bech32::encode_segwit("bc", 0, &[0u8; 25]).is_err());
+        assert!(bech32::encode_segwit("bc", 17, &[0u8; 32]).is_err());
+    }
+
+    #[test]
+    fn test_utxo_bundles_outpoint_and_txout() {
+        let mut tx = BitcoinTransaction::new(2, vec![], 0);
+        tx.add_output(TxOut::new(1_000, Script::new(vec![0x51])))
+            .add_output(TxOut::new(2_000, Script::new(vec![0x52])));
+
+        let utxo = Utxo::from_transaction(&tx, 1).unwrap();
+        assert_eq!(
+            utxo.outpoint,
+            OutPoint {
+                txid: tx.txid(),
+                vout: 1
+            }
+        );
+        assert_eq!(utxo.value(), Amount::from_sat(2_000));
+        assert_eq!(utxo.script_pubkey(), &Script::new(vec![0x52]));
+        assert!(Utxo::from_transaction(&tx, 2).is_none());
+
+        // Round trip through the tuple form
+        let (outpoint, txout): (OutPoint, TxOut) = utxo.clone().into();
+        assert_eq!(Utxo::from((outpoint, txout)), utxo);
+
+        let input = TransactionInput::from(&utxo);
+        assert_eq!(input.previous_output, utxo.outpoint);
+        assert!(input.script_sig.is_empty());
+        assert!(Sequence(input.sequence).signals_rbf());
+
+        // Derivation info only shows up in JSON when it's set
+        let json = serde_json::to_value(&utxo).unwrap();
+        assert!(json.get("derivation").is_none());
+        let owned = utxo.with_derivation(DerivationInfo::new(
+            [0xDE, 0xAD, 0xBE, 0xEF],
+            vec![0x8000_0054, 0, 5],
+        ));
+        let back: Utxo = serde_json::from_value(serde_json::to_value(&owned).unwrap()).unwrap();
+        assert_eq!(back, owned);
+    }
+
+    #[test]
+    fn test_network_parameters() {
+        assert_eq!(Network::Mainnet.magic(), [0xf9, 0xbe, 0xb4, 0xd9]);
+        assert_eq!(Network::Testnet4.default_port(), 48333);
+        assert_eq!(Network::Testnet4.bech32_hrp(), "tb");
+        for network in Network::ALL {
+            assert_eq!(Network::from_magic(network.magic()), Some(network));
+            assert_eq!(network.to_string().parse::<Network>().unwrap(), network);
+        }
+        assert_eq!(Network::from_magic([0; 4]), None);
+        assert_eq!("main".parse::<Network>().unwrap(), Network::Mainnet);
+
+        // "tb1..." parses as testnet, but is just as valid on testnet4
+        let hash = [0x11; 20];
+        let parsed: Address = Address::p2wpkh(hash, Network::Testnet4)
+            .to_string()
+            .parse()
+            .unwrap();
+        assert_eq!(parsed.network, Network::Testnet);
+        let on_testnet4 = parsed.clone().require_network(Network::Testnet4).unwrap();
+        assert_eq!(on_testnet4, Address::p2wpkh(hash, Network::Testnet4));
+        assert!(parsed.clone().require_network(Network::Regtest).is_err());
+        assert!(parsed.require_network(Network::Mainnet).is_err());
+
+        // Base58 test addresses are shared with regtest
+        let legacy = Address::p2pkh(hash, Network::Testnet);
+        assert!(legacy.is_valid_for_network(Network::Regtest));
+        assert!(!legacy.is_valid_for_network(Network::Mainnet));
+    }
+
+    #[test]
+    fn test_amount() {
+        assert_eq!(Amount::from_btc(0.001).unwrap(), Amount::from_sat(100_000));
+        assert_eq!(Amount::from_sat(150_000_000).to_btc(), 1.5);
+        assert_eq!(Amount::from_sat(100_000).to_string(), "0.00100000 BTC");
+        // Rounds to the nearest satoshi rather than truncating 0.1 + 0.2 noise
+        assert_eq!(
+            Amount::from_btc(0.1 + 0.2).unwrap(),
+            Amount::from_sat(30_000_000)
+        );
+
+        assert!(Amount::from_btc(-1.0).is_err());
+        assert!(Amount::from_btc(f64::NAN).is_err());
+        assert!(Amount::from_btc(21_000_000.0).is_ok());
+        assert!(Amount::from_btc(21_000_000.00000001).is_err());
+        assert!(Amount::from_sat_checked(Amount::MAX_MONEY.to_sat() + 1).is_err());
+        assert!(!Amount::from_sat(u64::MAX).is_valid());
+
+        let a = Amount::from_sat(5);
+        assert_eq!(a.checked_sub(Amount::from_sat(6)), None);
+        assert_eq!(Amount::from_sat(u64::MAX).checked_add(a), None);
+        assert_eq!(a.checked_mul(3), Some(Amount::from_sat(15)));
+        assert_eq!(a.checked_div(0), None);
+        assert_eq!(Amount::checked_sum([a, a, a]), Some(Amount::from_sat(15)));
+
+        // Still 8 bytes on the wire and a plain integer in JSON
+        let out = TxOut::new(Amount::ONE_BTC, Script::new(vec![0x51]));
+        assert_eq!(&out.to_bytes()[..8], &100_000_000u64.to_le_bytes());
+        assert_eq!(serde_json::to_value(&out).unwrap()["value"], 100_000_000);
+    }
+
+    #[test]
+    fn test_malleability_analysis() {
+        // Minimal DER signature with r = 1, the given S and a sighash byte
+        fn sig(s: [u8; 32], sighash_type: u8) -> Vec<u8> {
+            let pad = usize::from(s[0] & 0x80 != 0);
+            let mut der = vec![0x30, (37 + pad) as u8, 0x02, 0x01, 0x01, 0x02];
+            der.push((32 + pad) as u8);
+            der.extend(std::iter::repeat_n(0x00, pad));
+            der.extend_from_slice(&s);
+            der.push(sighash_type);
+            der
+        }
+        let low_s = [0x11; 32];
+        let high_s = [0xEE; 32];
+
+        let input = |script_sig: Script| {
+            TransactionInput::new(OutPoint::new(dummy_txid(1), 0), script_sig, 0xFFFFFFFF)
+        };
+        let clean = input(
+            Script::builder()
+                .push_slice(&sig(low_s, 0x01))
+                .push_key([0x02; 33])
+                .into_script(),
+        );
+        let high = input(
+            Script::builder()
+                .push_slice(&sig(high_s, 0x81))
+                .into_script(),
+        );
+        // OP_PUSHDATA1 for 2 bytes, then OP_NOP, then a signature with sighash 0x04
+        let mut messy = vec![0x4c, 0x02, 0xAA, 0xBB, 0x61];
+        messy.extend(
+            Script::builder()
+                .push_slice(&sig(low_s, 0x04))
+                .into_script()
+                .bytes,
+        );
+        let messy = input(Script::new(messy));
+        let mut segwit = input(Script::default());
+        segwit.witness = Witness::p2wpkh(sig(high_s, 0x01), [0x02; 33]);
+
+        let tx = BitcoinTransaction::new(2, vec![clean, high, messy, segwit], 0);
+        let report = analyze_malleability(&tx);
+        assert!(report.is_malleable());
+        assert_eq!(
+            report.for_input(0).collect::<Vec<_>>(),
+            [&MalleabilityVector::NonSegwitInput]
+        );
+        assert_eq!(
+            report.for_input(1).collect::<Vec<_>>(),
+            [
+                &MalleabilityVector::NonSegwitInput,
+                &MalleabilityVector::HighS
+            ]
+        );
+        assert_eq!(
+            report.for_input(2).collect::<Vec<_>>(),
+            [
+                &MalleabilityVector::NonSegwitInput,
+                &MalleabilityVector::NonMinimalPush { offset: 0 },
+                &MalleabilityVector::NonPushOpcode { offset: 4 },
+                &MalleabilityVector::NonStandardSighash { sighash_type: 0x04 },
+            ]
+        );
+        // Witness data isn't part of the txid, even with a high-S signature
+        assert_eq!(report.for_input(3).count(), 0);
+
+        // All-segwit transactions are safe
+        let tx = BitcoinTransaction::new(2, vec![tx.inputs[3].clone()], 0);
+        assert!(!analyze_malleability(&tx).is_malleable());
+    }
+
+    #[test]
+    fn test_weight_and_fee_rate() {
+        // Legacy: every byte costs 4 wu, so vsize is just the size
+        let legacy = BitcoinTransaction::decode(&hex::decode(BLOCK_170_TX).unwrap())
+            .unwrap()
+            .into_inner();
+        assert_eq!(legacy.total_size(), 275);
+        assert_eq!(legacy.base_size(), 275);
+        assert_eq!(legacy.weight(), Weight::from_wu(1100));
+        assert_eq!(legacy.vsize(), 275);
+
+        // Segwit: the 110 bytes of marker, flag and witnesses get the discount
+        let segwit = BitcoinTransaction::decode(&hex::decode(BIP143_P2WPKH_TX).unwrap())
+            .unwrap()
+            .into_inner();
+        assert_eq!(segwit.total_size(), 343);
+        assert_eq!(segwit.base_size(), 233);
+        assert_eq!(segwit.weight(), Weight::from_wu(233 * 3 + 343));
+        assert_eq!(segwit.vsize(), 261); // 1042 / 4, rounded up
+
+        let rate = FeeRate::from_sat_per_vb(2).unwrap();
+        assert_eq!(rate.fee_wu(segwit.weight()), Some(Amount::from_sat(522)));
+        // 0.5 sat/vB on 3 vB is 1.5 sats, which rounds up
+        assert_eq!(
+            FeeRate::from_sat_per_kvb(500).fee_vb(3),
+            Some(Amount::from_sat(2))
+        );
+        assert_eq!(
+            FeeRate::from_fee_and_weight(Amount::from_sat(522), segwit.weight()),
+            Some(rate)
+        );
+        assert_eq!(
+            FeeRate::from_fee_and_weight(Amount::ONE_SAT, Weight::ZERO),
+            None
+        );
+        assert_eq!(FeeRate::from_sat_per_kvb(1_500).to_string(), "1.5 sat/vB");
+        assert_eq!(FeeRate::MIN_RELAY.to_string(), "1 sat/vB");
+        assert_eq!(Weight::from_vb(10).unwrap().to_vbytes_ceil(), 10);
+        assert_eq!(Weight::from_vb(u64::MAX / 4 + 1), None);
+        assert_eq!(
+            Weight::from_non_witness_data_size(u64::MAX).to_wu(),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn test_legacy_signature_hash() {
+        // Block 170 spends a P2PK output; its signature (checked against
+        // this hash) commits to the pubkey script as the script code
+        let tx = BitcoinTransaction::decode(&hex::decode(BLOCK_170_TX).unwrap())
+            .unwrap()
+            .into_inner();
+        let script_code = Script::builder()
+            .push_key(&tx.outputs[1].script_pubkey[1..66])
+            .push_opcode(Opcode::CheckSig)
+            .into_script();
+        let sighash = tx.legacy_signature_hash(0, &script_code, SighashType::ALL);
+        assert_eq!(
+            hex::encode(sighash),
+            "7a05c6145f10101e9d6325494245adf1297d80f8f38d4d576d57cdba220bcb19"
+        );
+
+        // OP_CODESEPARATOR is never part of what's signed
+        let mut with_separator = vec![0xab];
+        with_separator.extend_from_slice(&script_code);
+        assert_eq!(
+            tx.legacy_signature_hash(0, &Script::new(with_separator), SighashType::ALL),
+            sighash
+        );
+
+        // The SIGHASH_SINGLE bug: no output at the input's index hashes to 1
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        let mut two_inputs = tx.clone();
+        two_inputs.inputs.push(tx.inputs[0].clone());
+        two_inputs.outputs.truncate(1);
+        assert_eq!(
+            two_inputs.legacy_signature_hash(1, &script_code, SighashType::SINGLE),
+            one
+        );
+        assert_eq!(
+            tx.legacy_signature_hash(5, &script_code, SighashType::ALL),
+            one
+        );
+
+        // ANYONECANPAY doesn't commit to the other inputs; ALL does
+        let mut more = two_inputs.clone();
+        more.inputs[1].previous_output.vout = 7;
+        for (sighash_type, same) in [
+            (SighashType::ALL_ANYONECANPAY, true),
+            (SighashType::ALL, false),
+        ] {
+            assert_eq!(
+                more.legacy_signature_hash(0, &script_code, sighash_type)
+                    == two_inputs.legacy_signature_hash(0, &script_code, sighash_type),
+                same
+            );
+        }
+        // NONE doesn't commit to other inputs' sequence numbers
+        more.inputs[1] = two_inputs.inputs[1].clone();
+        more.inputs[1].sequence = 0;
+        assert_eq!(
+            more.legacy_signature_hash(0, &script_code, SighashType::NONE),
+            two_inputs.legacy_signature_hash(0, &script_code, SighashType::NONE)
+        );
+
+        assert_eq!(
+            SighashType::SINGLE_ANYONECANPAY.to_string(),
+            "SINGLE|ANYONECANPAY"
+        );
+        assert!(!SighashType::from(0x04).is_standard());
+    }
+
+    #[test]
+    fn test_decode_budget() {
+        let bytes = hex::decode(BIP143_P2WPKH_TX).unwrap();
+        let budget = DecodeBudget::default();
+        assert!(BitcoinTransaction::decode_with_budget(&bytes, budget).is_ok());
+
+        // 2 inputs + 2 outputs + 2 witness items (the first witness is empty)
+        let exhausted = |budget| {
+            BitcoinTransaction::decode_with_budget(&bytes, budget)
+                .unwrap_err()
+                .root_cause()
+                .clone()
+        };
+        let elements = DecodeBudget {
+            max_elements: 5,
+            ..budget
+        };
+        assert!(
+            BitcoinTransaction::decode_with_budget(
+                &bytes,
+                DecodeBudget {
+                    max_elements: 6,
+                    ..budget
+                }
+            )
+            .is_ok()
+        );
+        assert_eq!(
+            exhausted(elements),
+            BitcoinError::BudgetExceeded {
+                resource: BudgetResource::Elements,
+                limit: 5
+            }
+        );
+
+        // inputs -> [0] -> previous_output -> txid is 4 deep
+        let depth = DecodeBudget {
+            max_depth: 3,
+            ..budget
+        };
+        assert_eq!(
+            exhausted(depth),
+            BitcoinError::BudgetExceeded {
+                resource: BudgetResource::Depth,
+                limit: 3
+            }
+        );
+
+        let small = DecodeBudget {
+            max_bytes: 100,
+            ..budget
+        };
+        assert_eq!(
+            exhausted(small),
+            BitcoinError::BudgetExceeded {
+                resource: BudgetResource::Bytes,
+                limit: 100
+            }
+        );
+    }
+
+    #[test]
+    fn test_sighash_cache_bip143() {
+        // BIP143's native P2WPKH example: input 1 spends 6 BTC from the key
+        // hash 1d0f172a...
+        let tx = BitcoinTransaction::decode(&hex::decode(BIP143_P2WPKH_TX).unwrap())
+            .unwrap()
+            .into_inner();
+        let spent = Address::p2wpkh(
+            hex::decode("1d0f172a0ecb48aee1be1f2687d2963ae33f71a1")
+                .unwrap()
+                .try_into()
+                .unwrap(),
+            Network::Mainnet,
+        )
+        .script_pubkey();
+        let value = Amount::from_sat(600_000_000);
+
+        let mut cache = SighashCache::new(&tx);
+        let sighash = cache
+            .p2wpkh_signature_hash(1, &spent, value, SighashType::ALL)
+            .unwrap();
+        assert_eq!(
+            hex::encode(sighash),
+            "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670"
+        );
+        // Same answer through the generic v0 method, with the cached midstates
+        let script_code =
+            Script::new(hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap());
+        assert_eq!(
+            cache
+                .segwit_v0_signature_hash(1, &script_code, value, SighashType::ALL)
+                .unwrap(),
+            sighash
+        );
+        // The amount is committed to
+        assert_ne!(
+            cache
+                .segwit_v0_signature_hash(1, &script_code, Amount::ONE_BTC, SighashType::ALL)
+                .unwrap(),
+            sighash
+        );
+
+        assert_eq!(
+            cache.p2wpkh_signature_hash(2, &spent, value, SighashType::ALL),
+            Err(BitcoinError::InputIndexOutOfRange {
+                index: 2,
+                inputs: 2
+            })
+        );
+        assert!(
+            cache
+                .p2wpkh_signature_hash(1, &script_code, value, SighashType::ALL)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_taproot_sighash_cache_follows_prevouts() {
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![
+                TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::default(), 0),
+                TransactionInput::new(OutPoint::new(dummy_txid(2), 1), Script::default(), 0),
+            ],
+            0,
+        );
+        let spent = |value: u64| {
+            vec![
+                TxOut::new(
+                    value,
+                    Script::new(vec![0x51, 0x20].into_iter().chain([7; 32]).collect()),
+                ),
+                TxOut::new(5_000, Script::new(vec![0x51])),
+            ]
+        };
+        let (first, second) = (spent(10_000), spent(20_000));
+
+        // Reusing a cache with different prevouts must give the same digest
+        // as a fresh one, not one over the outputs it saw first
+        let mut cache = SighashCache::new(&tx);
+        let a = cache
+            .taproot_key_spend_signature_hash(0, &first, SighashType::DEFAULT)
+            .unwrap();
+        let b = cache
+            .taproot_key_spend_signature_hash(0, &second, SighashType::DEFAULT)
+            .unwrap();
+        assert_ne!(a, b);
+        assert_eq!(
+            SighashCache::new(&tx)
+                .taproot_key_spend_signature_hash(0, &second, SighashType::DEFAULT)
+                .unwrap(),
+            b
+        );
+        assert_eq!(
+            cache
+                .taproot_key_spend_signature_hash(0, &first, SighashType::DEFAULT)
+                .unwrap(),
+            a
+        );
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_sign_and_verify_input_ecdsa() {
+        use rust_week_3_exercises::secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+        // The signed input from BIP143's P2WPKH example checks out
+        let real = BitcoinTransaction::decode(&hex::decode(BIP143_P2WPKH_TX).unwrap())
+            .unwrap()
+            .into_inner();
+        let key_hash = hex::decode("1d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap();
+        let spent = Address::p2wpkh(key_hash.try_into().unwrap(), Network::Mainnet);
+        let utxo = Utxo::new(
+            real.inputs[1].previous_output.clone(),
+            TxOut::new(600_000_000, spent.script_pubkey()),
+        );
+        assert_eq!(
+            verify_input_ecdsa(&mut SighashCache::new(&real), 1, &utxo),
+            Ok(())
+        );
+
+        // Sign a P2PKH and a P2WPKH input with the same key
+        let secret_key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let pubkey = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key).serialize();
+        let key_hash = hashes::hash160(&pubkey);
+        let utxos = [
+            Utxo::new(
+                OutPoint::new(dummy_txid(1), 0),
+                TxOut::new(
+                    50_000,
+                    Address::p2pkh(key_hash, Network::Regtest).script_pubkey(),
+                ),
+            ),
+            Utxo::new(
+                OutPoint::new(dummy_txid(2), 1),
+                TxOut::new(
+                    70_000,
+                    Address::p2wpkh(key_hash, Network::Regtest).script_pubkey(),
+                ),
+            ),
+        ];
+        let mut tx =
+            BitcoinTransaction::new(2, utxos.iter().map(TransactionInput::from).collect(), 0);
+        tx.add_output(TxOut::new(110_000, Script::new(vec![0x51])));
+
+        let signed: Vec<_> = {
+            let mut cache = SighashCache::new(&tx);
+            (0..2)
+                .map(|i| {
+                    sign_input_ecdsa(&mut cache, i, &utxos[i], &secret_key, SighashType::ALL)
+                        .unwrap()
+                })
+                .collect()
+        };
+        for (input, (script_sig, witness)) in tx.inputs.iter_mut().zip(signed) {
+            input.script_sig = script_sig;
+            input.witness = witness;
+        }
+        assert_eq!(tx.inputs[0].script_sig.instructions().count(), 2);
+        assert!(tx.inputs[1].script_sig.is_empty());
+        assert_eq!(tx.inputs[1].witness.last(), Some(&pubkey[..]));
+
+        let mut cache = SighashCache::new(&tx);
+        for (i, utxo) in utxos.iter().enumerate() {
+            assert_eq!(verify_input_ecdsa(&mut cache, i, utxo), Ok(()));
+        }
+        assert_eq!(
+            verify_input_ecdsa(&mut cache, 0, &utxos[1]),
+            Err(BitcoinError::PrevoutMismatch { index: 0 })
+        );
+
+        // Changing what was signed breaks both signatures
+        let mut tampered = tx.clone();
+        tampered.outputs[0].value = Amount::from_sat(109_000);
+        let mut cache = SighashCache::new(&tampered);
+        for (i, utxo) in utxos.iter().enumerate() {
+            assert_eq!(
+                verify_input_ecdsa(&mut cache, i, utxo),
+                Err(BitcoinError::InvalidSignature)
+            );
+        }
+
+        let other_key = SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let mut cache = SighashCache::new(&tx);
+        assert_eq!(
+            sign_input_ecdsa(&mut cache, 0, &utxos[0], &other_key, SighashType::ALL),
+            Err(BitcoinError::InvalidKey)
+        );
+        let p2tr = Utxo::new(
+            utxos[0].outpoint.clone(),
+            TxOut::new(
+                50_000,
+                Address::p2tr([0x33; 32], Network::Regtest).script_pubkey(),
+            ),
+        );
+        assert_eq!(
+            sign_input_ecdsa(&mut cache, 0, &p2tr, &secret_key, SighashType::ALL),
+            Err(BitcoinError::UnsupportedScript(ScriptType::P2tr))
+        );
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_taproot_key_spend_signing() {
+        use rust_week_3_exercises::secp256k1::{Keypair, Secp256k1, SecretKey, XOnlyPublicKey};
+
+        // BIP86 test vector for m/86'/0'/0'/0/0
+        let internal = XOnlyPublicKey::from_slice(
+            &hex::decode("cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115")
+                .unwrap(),
+        )
+        .unwrap();
+        let output_key = taproot_output_key(&internal, None).unwrap();
+        assert_eq!(
+            hex::encode(output_key.serialize()),
+            "a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+        );
+        assert_eq!(
+            Address::p2tr(output_key.serialize(), Network::Mainnet).to_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+
+        // Spend our own P2TR output alongside someone else's input
+        let secret_key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let (internal, _) =
+            Keypair::from_secret_key(&Secp256k1::new(), &secret_key).x_only_public_key();
+        let ours = taproot_output_key(&internal, None).unwrap().serialize();
+        let prevouts = [
+            Utxo::new(
+                OutPoint::new(dummy_txid(1), 0),
+                TxOut::new(
+                    80_000,
+                    Address::p2tr(ours, Network::Regtest).script_pubkey(),
+                ),
+            ),
+            Utxo::new(
+                OutPoint::new(dummy_txid(2), 3),
+                TxOut::new(
+                    20_000,
+                    Address::p2wpkh([0x44; 20], Network::Regtest).script_pubkey(),
+                ),
+            ),
+        ];
+        let mut tx =
+            BitcoinTransaction::new(2, prevouts.iter().map(TransactionInput::from).collect(), 0);
+        tx.add_output(TxOut::new(99_000, Script::new(vec![0x51])));
+
+        for (sighash_type, len) in [(SighashType::DEFAULT, 64), (SighashType::ALL, 65)] {
+            let witness = sign_input_schnorr(
+                &mut SighashCache::new(&tx),
+                0,
+                &prevouts,
+                &secret_key,
+                None,
+                sighash_type,
+            )
+            .unwrap();
+            assert_eq!(witness.len(), 1);
+            assert_eq!(witness.items[0].len(), len);
+
+            let mut signed = tx.clone();
+            signed.inputs[0].witness = witness;
+            assert_eq!(
+                verify_input_schnorr(&mut SighashCache::new(&signed), 0, &prevouts),
+                Ok(())
+            );
+
+            // Every input's amount is committed to, not just the one being spent
+            let mut lied = prevouts.clone();
+            lied[1].txout.value = Amount::from_sat(10_000);
+            assert_eq!(
+                verify_input_schnorr(&mut SighashCache::new(&signed), 0, &lied),
+                Err(BitcoinError::InvalidSignature)
+            );
+        }
+
+        let mut cache = SighashCache::new(&tx);
+        assert_eq!(
+            sign_input_schnorr(
+                &mut cache,
+                0,
+                &prevouts,
+                &secret_key,
+                None,
+                SighashType(0x04)
+            ),
+            Err(BitcoinError::InvalidSighashType(0x04))
+        );
+        // A script tree changes the output key, so this key alone can't spend it
+        assert_eq!(
+            sign_input_schnorr(
+                &mut cache,
+                0,
+                &prevouts,
+                &secret_key,
+                Some([0x55; 32]),
+                SighashType::DEFAULT
+            ),
+            Err(BitcoinError::InvalidKey)
+        );
+        assert_eq!(
+            sign_input_schnorr(
+                &mut cache,
+                0,
+                &prevouts[..1],
+                &secret_key,
+                None,
+                SighashType::DEFAULT
+            ),
+            Err(BitcoinError::PrevoutCount {
+                expected: 2,
+                got: 1
+            })
+        );
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_taproot_script_tree() {
+        use rust_week_3_exercises::secp256k1::XOnlyPublicKey;
+        use rust_week_3_exercises::taproot::tap_leaf_hash;
+
+        // BIP341 wallet test vector: one tapscript leaf
+        let internal = XOnlyPublicKey::from_slice(
+            &hex::decode("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27")
+                .unwrap(),
+        )
+        .unwrap();
+        let leaf = Script::new(
+            hex::decode("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac")
+                .unwrap(),
+        );
+        assert_eq!(
+            hex::encode(tap_leaf_hash(0xC0, &leaf)),
+            "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21"
+        );
+        let info = TaprootSpendInfo::new(&internal, Some(&TapTree::leaf(leaf.clone()))).unwrap();
+        assert_eq!(
+            info.address(Network::Mainnet).to_string(),
+            "bc1pz37fc4cn9ah8anwm4xqqhvxygjf9rjf2resrw8h8w4tmvcs0863sa2e586"
+        );
+        assert_eq!(
+            hex::encode(info.control_block(&leaf).unwrap().to_bytes()),
+            "c1187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27"
+        );
+
+        // ((A, B), C): every leaf's control block leads back to the root the
+        // output key was tweaked with
+        let scripts: Vec<Script> = (0x51..0x54).map(|op| Script::new(vec![op])).collect();
+        let tree = TapTree::branch(
+            TapTree::branch(
+                TapTree::leaf(scripts[0].clone()),
+                TapTree::leaf(scripts[1].clone()),
+            ),
+            TapTree::leaf(scripts[2].clone()),
+        );
+        let info = TaprootSpendInfo::new(&internal, Some(&tree)).unwrap();
+        assert_eq!(info.merkle_root, Some(tree.merkle_root()));
+        assert_eq!(
+            info.output_key,
+            taproot_output_key(&internal, info.merkle_root)
+                .unwrap()
+                .serialize()
+        );
+        for (script, depth) in scripts.iter().zip([2, 2, 1]) {
+            let control_block = info.control_block(script).unwrap();
+            assert_eq!(control_block.merkle_branch.len(), depth);
+            assert_eq!(control_block.merkle_root(script), tree.merkle_root());
+            let bytes = control_block.to_bytes();
+            assert_eq!(bytes.len(), 33 + 32 * depth);
+            assert_eq!(ControlBlock::from_bytes(&bytes).unwrap(), control_block);
+
+            // The witness carries it as the last item, after the script
+            let witness = Witness::tapscript(script, &bytes, Vec::<Vec<u8>>::new());
+            assert_eq!(witness.tapscript_parts(), Some((&script[..], &bytes[..])));
+        }
+        assert_eq!(info.control_block(&Script::new(vec![0x00])), None);
+        assert_eq!(
+            ControlBlock::from_bytes(&[0xC0; 34]),
+            Err(BitcoinError::InvalidFormat)
+        );
+
+        // Without a tree it's a plain BIP86 key-path output
+        let key_only = TaprootSpendInfo::new(&internal, None).unwrap();
+        assert_eq!(key_only.merkle_root, None);
+        assert!(key_only.leaves.is_empty());
+    }
+
+    #[test]
+    fn test_signature_der() {
+        // The signature in block 170's spend (the first bitcoin payment)
+        let bytes = hex::decode(
+            "304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd41\
+             0220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901",
+        )
+        .unwrap();
+        let signature = Signature::from_bytes(&bytes).unwrap();
+        assert_eq!(signature.sighash_type, SighashType::ALL);
+        assert_eq!(signature.r[0], 0x4e);
+        assert!(signature.is_low_s());
+        assert_eq!(signature.to_bytes(), bytes);
+
+        // High bits get a sign-padding zero, leading zeros are dropped
+        let mut s = [0u8; 32];
+        s[31] = 0x01;
+        let padded = Signature {
+            r: [0x80; 32],
+            s,
+            sighash_type: SighashType::SINGLE_ANYONECANPAY,
+        };
+        let encoded = padded.to_bytes();
+        assert_eq!(encoded.len(), 2 + 35 + 3 + 1);
+        assert_eq!(&encoded[2..5], &[0x02, 33, 0x00]);
+        assert_eq!(Signature::from_bytes(&encoded), Ok(padded));
+
+        // S = n - 1 is high; flipping it gives 1
+        let mut high = signature;
+        high.s = hex::decode("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364140")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert!(!high.is_low_s());
+        assert_eq!(
+            Signature::from_bytes_standard(&high.to_bytes()),
+            Err(SignatureError::HighS)
+        );
+        assert_eq!(Signature::from_bytes(&high.to_bytes()), Ok(high));
+        assert_eq!(high.normalize_s().s, s);
+        assert_eq!(signature.normalize_s(), signature);
+
+        // Each BIP66 rule gets its own error
+        let der = &bytes[..bytes.len() - 1];
+        let with = |i: usize, byte: u8| {
+            let mut bad = bytes.clone();
+            bad[i] = byte;
+            Signature::from_bytes(&bad)
+        };
+        assert_eq!(Signature::from_bytes(&[]), Err(SignatureError::Empty));
+        assert_eq!(
+            Signature::from_bytes(&der[..5]),
+            Err(SignatureError::InvalidLength(4))
+        );
+        assert_eq!(with(0, 0x31), Err(SignatureError::NotASequence));
+        assert_eq!(
+            with(1, 0x45),
+            Err(SignatureError::SequenceLength {
+                declared: 0x45,
+                actual: 0x44
+            })
+        );
+        assert_eq!(
+            with(2, 0x03),
+            Err(SignatureError::NotAnInteger(SignatureComponent::R))
+        );
+        assert_eq!(
+            with(4, 0xce),
+            Err(SignatureError::Negative(SignatureComponent::R))
+        );
+        assert_eq!(
+            with(38, 0x00),
+            Err(SignatureError::NonMinimal(SignatureComponent::S))
+        );
+        let mut zero_r = vec![0x30, 0x06, 0x02, 0x00, 0x02, 0x02, 0x01, 0x01, 0x01];
+        assert_eq!(
+            Signature::from_bytes(&zero_r),
+            Err(SignatureError::ZeroLength(SignatureComponent::R))
+        );
+        zero_r[1] = 0x05;
+        assert!(matches!(
+            Signature::from_bytes(&zero_r),
+            Err(SignatureError::SequenceLength { .. })
+        ));
+
+        let err: BitcoinError = SignatureError::HighS.into();
+        assert_eq!(
+            err.to_string(),
+            "invalid signature encoding: S is above half the curve order"
+        );
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_keys_and_wif() {
+        // Private key 1, whose public key is the generator point
+        let wif = "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn";
+        let key: PrivateKey = wif.parse().unwrap();
+        assert_eq!(key.secret_key().secret_bytes()[31], 1);
+        assert!(key.compressed);
+        assert_eq!(key.network, Network::Mainnet);
+        assert_eq!(key.to_wif(), wif);
+
+        let pubkey = key.public_key();
+        assert_eq!(
+            pubkey.to_string(),
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+        );
+        assert_eq!(
+            pubkey.p2pkh(Network::Mainnet).to_string(),
+            "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"
+        );
+        assert_eq!(
+            pubkey.p2wpkh(Network::Mainnet).unwrap().to_string(),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+
+        // Same secret, uncompressed: different WIF, hash and address
+        let uncompressed =
+            PrivateKey::from_wif("5HpHagT65TZzG1PH3CSu63k8DbpvD8s5ip4nEB3kEsreAnchuDf").unwrap();
+        assert_eq!(uncompressed.secret_key(), key.secret_key());
+        assert!(!uncompressed.compressed);
+        // Equality covers the secret and both flags
+        assert_ne!(uncompressed, key);
+        assert_eq!(key.clone(), key);
+        let pubkey = uncompressed.public_key();
+        assert_eq!(pubkey.to_bytes().len(), 65);
+        assert_eq!(
+            pubkey.p2pkh(Network::Mainnet).to_string(),
+            "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm"
+        );
+        assert_eq!(
+            pubkey.p2wpkh(Network::Mainnet),
+            Err(BitcoinError::InvalidKey)
+        );
+        assert_eq!(PublicKey::from_slice(&pubkey.to_bytes()), Ok(pubkey));
+
+        // Test networks share one prefix
+        let testnet = PrivateKey::new(*key.secret_key(), Network::Regtest).to_wif();
+        assert_eq!(
+            testnet,
+            "cMahea7zqjxrtgAbB7LSGbcQUr1uX1ojuat9jZodMN87JcbXMTcA"
+        );
+        assert_eq!(
+            PrivateKey::from_wif(&testnet).unwrap().network,
+            Network::Testnet
+        );
+        // A mistyped character fails the checksum on the zeroizing path too
+        let mut typo = wif.to_string();
+        typo.replace_range(10..11, "z");
+        assert_eq!(
+            PrivateKey::from_wif(&typo),
+            Err(BitcoinError::InvalidChecksum)
+        );
+
+        assert_eq!(
+            PublicKey::from_slice(&[0x02; 32]),
+            Err(BitcoinError::InvalidKey)
+        );
+        assert!(matches!(
+            PrivateKey::from_wif("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"),
+            Err(BitcoinError::InvalidWif(_))
+        ));
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_bip32_derivation() {
+        // BIP32 test vector 1
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = Xpriv::new_master(Network::Mainnet, &seed).unwrap();
+        assert_eq!(
+            master.to_string(),
+            "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi"
+        );
+        assert_eq!(
+            master.to_xpub().to_string(),
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"
+        );
+        assert_eq!(hex::encode(master.fingerprint()), "3442193e");
+
+        let path: DerivationPath = "m/0'/1/2h/2/1000000000".parse().unwrap();
+        assert_eq!(path.to_string(), "m/0'/1/2'/2/1000000000");
+        let child = master.derive_path(&path).unwrap();
+        assert_eq!(child.depth, 5);
+        assert_eq!(
+            child.to_string(),
+            "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76"
+        );
+        assert_eq!(
+            child.to_xpub().to_string(),
+            "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy"
+        );
+
+        // Public derivation of the non-hardened tail matches private derivation
+        let account = master.derive_path(&"m/0'/1/2'".parse().unwrap()).unwrap();
+        let tail: DerivationPath = "2/1000000000".parse().unwrap();
+        assert_eq!(
+            account.to_xpub().derive_path(&tail).unwrap(),
+            child.to_xpub()
+        );
+        assert_eq!(
+            account.to_xpub().derive_child(0x8000_0000),
+            Err(BitcoinError::HardenedDerivationFromXpub(0x8000_0000))
+        );
+
+        // Round trips through base58, keeping the parent link
+        let xpub: Xpub = child.to_xpub().to_string().parse().unwrap();
+        assert_eq!(xpub, child.to_xpub());
+        let xprv: Xpriv = child.to_string().parse().unwrap();
+        assert_eq!(xprv, child);
+        assert_ne!(xprv, master);
+        assert_eq!(xpub.to_public_key(), child.to_private_key().public_key());
+        assert!(matches!(
+            child.to_string().parse::<Xpub>(),
+            Err(BitcoinError::InvalidExtendedKey(_))
+        ));
+
+        assert_eq!("m".parse(), Ok(DerivationPath::master()));
+        assert_eq!(
+            "84'/0'".parse::<DerivationPath>().unwrap().as_slice(),
+            &[0x8000_0054, 0x8000_0000]
+        );
+        for bad in ["m/", "m/x", "m//1", "m/2147483648", "n/1"] {
+            assert!(
+                matches!(
+                    bad.parse::<DerivationPath>(),
+                    Err(BitcoinError::InvalidDerivationPath(_))
+                ),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[cfg(feature = "bip39")]
+    #[test]
+    fn test_bip39_mnemonic() {
+        // Vectors from the BIP39 reference implementation (passphrase "TREZOR")
+        let mnemonic = Mnemonic::from_entropy(&[0; 16]).unwrap();
+        assert_eq!(
+            mnemonic.to_string(),
+            format!("{} about", ["abandon"; 11].join(" "))
+        );
+        assert_eq!(
+            hex::encode(mnemonic.to_seed("TREZOR")),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+        #[cfg(feature = "secp256k1")]
+        assert_eq!(
+            mnemonic
+                .to_xpriv(Network::Mainnet, "TREZOR")
+                .unwrap()
+                .to_string(),
+            "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF"
+        );
+
+        let phrase = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        let mnemonic: Mnemonic = phrase.parse().unwrap();
+        assert_eq!(mnemonic.entropy(), &[0x7f; 16]);
+        assert_eq!(mnemonic.word_count(), 12);
+        assert_eq!(
+            hex::encode(mnemonic.to_seed("TREZOR")),
+            "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607"
+        );
+        // Passphrases are NFKD-normalized: composed and decomposed forms,
+        // and compatibility characters like the "ﬁ" ligature, are the same
+        // wallet
+        assert_eq!(
+            mnemonic.to_seed("caf\u{e9} \u{fb01}"),
+            mnemonic.to_seed("cafe\u{301} fi")
+        );
+        assert_ne!(mnemonic.to_seed("caf\u{e9}"), mnemonic.to_seed("cafe"));
+        // Case and spacing don't matter
+        assert_eq!(
+            Mnemonic::parse(&format!("  {}\n", phrase.to_uppercase())),
+            Ok(mnemonic)
+        );
+
+        let zoo = Mnemonic::from_entropy(&[0xff; 32]).unwrap();
+        assert_eq!(zoo.words().len(), 24);
+        assert_eq!(zoo.words().last(), Some(&"vote"));
+        // Same words, different entropy lengths and a single flipped bit all
+        // go through the constant-time comparison
+        assert_ne!(zoo, Mnemonic::from_entropy(&[0xff; 16]).unwrap());
+        assert_ne!(
+            zoo,
+            Mnemonic::from_entropy(
+                &[0xff; 31]
+                    .iter()
+                    .chain([&0xfe])
+                    .copied()
+                    .collect::<Vec<_>>()
+            )
+            .unwrap()
+        );
+        assert_eq!(zoo.clone(), zoo);
+
+        let generated = Mnemonic::generate(24).unwrap();
+        assert_eq!(generated.to_string().parse(), Ok(generated.clone()));
+        assert!(!format!("{:?}", generated).contains(generated.words()[0]));
+
+        for bad in [
+            ["abandon"; 12].join(" "),
+            format!("{} abandonn", ["abandon"; 11].join(" ")),
+            ["abandon"; 13].join(" "),
+        ] {
+            assert!(
+                matches!(Mnemonic::parse(&bad), Err(BitcoinError::InvalidMnemonic(_))),
+                "{}",
+                bad
+            );
+        }
+        assert!(Mnemonic::generate(13).is_err());
+        assert!(Mnemonic::from_entropy(&[0; 17]).is_err());
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_descriptors() {
+        use rust_week_3_exercises::descriptor::checksum;
+
+        // Checksum from Bitcoin Core's descriptor docs
+        let body = "wpkh([d34db33f/84h/0h/0h]xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY/0/*)";
+        assert_eq!(checksum(body).unwrap(), "cjjspncu");
+        let descriptor: Descriptor = format!("{}#cjjspncu", body).parse().unwrap();
+        assert!(descriptor.is_ranged());
+        // Prints with ' for hardened, and a checksum to match
+        let printed = descriptor.to_string();
+        assert!(printed.starts_with("wpkh([d34db33f/84'/0'/0']xpub6DJ2"));
+        assert_eq!(printed.parse::<Descriptor>().unwrap(), descriptor);
+        assert!(matches!(
+            format!("{}#cjjspncv", body).parse::<Descriptor>(),
+            Err(BitcoinError::InvalidDescriptor(_))
+        ));
+
+        // BIP84 and BIP86 account xpubs for the "abandon ... about" mnemonic
+        let wpkh: Descriptor = "wpkh(xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            wpkh.address(0, Network::Mainnet).unwrap().to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        let tr: Descriptor = "tr(xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/*)"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            tr.address(0, Network::Mainnet).unwrap().to_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+        assert_ne!(tr.script_pubkey(1).unwrap(), tr.script_pubkey(0).unwrap());
+
+        // Fixed keys: the index doesn't matter
+        let key = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+        let pkh: Descriptor = format!("pkh({})", key).parse().unwrap();
+        assert!(!pkh.is_ranged());
+        assert_eq!(
+            pkh.address(7, Network::Mainnet).unwrap().to_string(),
+            "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"
+        );
+        let sh_wpkh: Descriptor = format!("sh(wpkh({}))", key).parse().unwrap();
+        let redeem_script = Address::p2wpkh(
+            hashes::hash160(&hex::decode(key).unwrap()),
+            Network::Mainnet,
+        )
+        .script_pubkey();
+        assert_eq!(
+            sh_wpkh.script_pubkey(0).unwrap(),
+            Address::p2sh(hashes::hash160(&redeem_script), Network::Mainnet).script_pubkey()
+        );
+
+        // sortedmulti orders the keys, multi keeps them as written
+        let other = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+        let multi: Descriptor = format!("wsh(multi(1,{},{}))", other, key).parse().unwrap();
+        let sorted: Descriptor = format!("wsh(sortedmulti(1,{},{}))", other, key)
+            .parse()
+            .unwrap();
+        let witness_script = sorted.witness_script(0).unwrap().unwrap();
+        assert_eq!(
+            witness_script.bytes,
+            Script::builder()
+                .push_int(1)
+                .push_key(hex::decode(key).unwrap())
+                .push_key(hex::decode(other).unwrap())
+                .push_int(2)
+                .push_opcode(Opcode::CheckMultiSig)
+                .into_script()
+                .bytes
+        );
+        assert_ne!(
+            multi.witness_script(0).unwrap(),
+            Some(witness_script.clone())
+        );
+        assert_eq!(
+            sorted.script_pubkey(0).unwrap(),
+            Address::p2wsh(hashes::sha256(&witness_script), Network::Mainnet).script_pubkey()
+        );
+        assert_eq!(pkh.witness_script(0), Ok(None));
+
+        // tr() with a script tree gives a control block per leaf
+        let tree: Descriptor = format!("tr({},{{pk({}),pk({})}})", &key[2..], other, key)
+            .parse()
+            .unwrap();
+        let info = tree.taproot_spend_info(0).unwrap().unwrap();
+        assert_eq!(info.leaves.len(), 2);
+        for leaf in &info.leaves {
+            let control_block = info.control_block(&leaf.script).unwrap();
+            assert_eq!(
+                control_block.merkle_root(&leaf.script),
+                info.merkle_root.unwrap()
+            );
+        }
+        assert_eq!(tree.to_string().parse::<Descriptor>().unwrap(), tree);
+
+        for bad in [
+            "foo(00)".to_string(),
+            format!("sh(pkh({}))", key),
+            format!("wpkh(04{}{})", &key[2..], &key[2..]),
+            format!("wsh(multi(3,{},{}))", key, other),
+            "wpkh(xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*')".to_string(),
+            format!("tr({},{{pk({})}})", key, key),
+        ] {
+            assert!(
+                matches!(bad.parse::<Descriptor>(), Err(BitcoinError::InvalidDescriptor(_))),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_miniscript() {
+        let a = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+        let b = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+
+        // A key that can spend any time, or a backup key after 144 blocks
+        let text = format!("or_d(pk({}),and_v(v:pk({}),older(144)))", a, b);
+        let miniscript: Miniscript = text.parse().unwrap();
+        assert_eq!(miniscript.to_string(), text);
+        let script = Script::builder()
+            .push_key(hex::decode(a).unwrap())
+            .push_opcode(Opcode::CheckSig)
+            .push_opcode(Opcode::IfDup)
+            .push_opcode(Opcode::NotIf)
+            .push_key(hex::decode(b).unwrap())
+            .push_opcode(Opcode::CheckSigVerify)
+            .push_int(144)
+            .push_opcode(Opcode::CheckSequenceVerify)
+            .push_opcode(Opcode::EndIf)
+            .into_script();
+        assert_eq!(miniscript.to_script(0).unwrap(), script);
+        // Worst case is the backup path: an empty item for the first key,
+        // then a signature
+        assert_eq!(miniscript.max_satisfaction_size(), 1 + 74);
+        assert_eq!(
+            miniscript.max_satisfaction_weight().unwrap(),
+            Weight::from_wu(4 + 1 + 75 + 1 + script.len() as u64)
+        );
+        assert_eq!(
+            miniscript.lift().to_string(),
+            format!("or(pk({}),and(pk({}),older(144)))", a, b)
+        );
+
+        // Under wsh() it's the witness script
+        let descriptor: Descriptor = format!("wsh({})", text).parse().unwrap();
+        assert_eq!(descriptor.witness_script(0).unwrap(), Some(script.clone()));
+        assert_eq!(
+            descriptor.script_pubkey(0).unwrap(),
+            Address::p2wsh(hashes::sha256(&script), Network::Mainnet).script_pubkey()
+        );
+        assert_eq!(
+            descriptor.to_string().parse::<Descriptor>().unwrap(),
+            descriptor
+        );
+
+        // Tapscript leaves use x-only keys
+        let leaf = Miniscript::parse(
+            &format!("and_v(v:pk({}),after(800000))", a),
+            ScriptContext::Tapscript,
+        )
+        .unwrap();
+        assert_eq!(leaf.to_script(0).unwrap()[..2], [0x20, 0x79]);
+        assert_eq!(leaf.max_satisfaction_size(), 66);
+
+        for bad in [
+            format!("pk_k({})", a),
+            format!("v:pk({})", a),
+            format!("and_v(pk({}),pk({}))", a, b),
+            format!("thresh(3,pk({}),s:pk({}))", a, b),
+            "older(0)".to_string(),
+            format!("sha256({})", a),
+        ] {
+            assert!(
+                matches!(
+                    bad.parse::<Miniscript>(),
+                    Err(BitcoinError::InvalidMiniscript(_))
+                ),
+                "{}",
+                bad
+            );
+        }
+        assert!(Miniscript::parse(&format!("multi(1,{})", a), ScriptContext::Tapscript).is_err());
+    }
+
+    #[test]
+    fn test_weighted_utxo_effective_value() {
+        let coin = |value: u64, script_pubkey: Script| {
+            Utxo::new(
+                OutPoint::new(dummy_txid(1), 0),
+                TxOut::new(value, script_pubkey),
+            )
+        };
+        let p2wpkh = Address::p2wpkh([0x11; 20], Network::Mainnet).script_pubkey();
+
+        // 40 bytes of outpoint + sequence, then an empty scriptSig and a
+        // [sig, key] witness: 272 wu, 68 vB
+        let small = WeightedUtxo::single_key(coin(10_000, p2wpkh.clone())).unwrap();
+        assert_eq!(small.input_weight(), Weight::from_wu(272));
+        let rate = FeeRate::from_sat_per_vb(10).unwrap();
+        assert_eq!(small.input_fee(rate), Some(Amount::from_sat(680)));
+        assert_eq!(small.effective_value(rate), 9_320);
+        assert!(small.is_economical(rate));
+
+        // At 200 sat/vB it costs more to spend than it's worth
+        let expensive = FeeRate::from_sat_per_vb(200).unwrap();
+        assert_eq!(small.effective_value(expensive), -3_600);
+        assert!(!small.is_economical(expensive));
+
+        let coins = [
+            small.clone(),
+            WeightedUtxo::single_key(coin(
+                50_000,
+                Address::p2pkh([0x22; 20], Network::Mainnet).script_pubkey(),
+            ))
+            .unwrap(),
+            WeightedUtxo::single_key(coin(
+                1_000,
+                Address::p2tr([0x33; 32], Network::Mainnet).script_pubkey(),
+            ))
+            .unwrap(),
+        ];
+        assert_eq!(coins[1].input_weight().to_vbytes_ceil(), 148);
+        assert_eq!(coins[2].input_weight().to_vbytes_ceil(), 58);
+        let worth_it: Vec<_> = coins
+            .iter()
+            .filter(|coin| coin.is_economical(FeeRate::from_sat_per_vb(20).unwrap()))
+            .map(|coin| coin.utxo.value())
+            .collect();
+        assert_eq!(
+            worth_it,
+            [Amount::from_sat(10_000), Amount::from_sat(50_000)]
+        );
+
+        // Anything else needs its satisfaction weight spelled out
+        assert_eq!(
+            WeightedUtxo::single_key(coin(1_000, Script::new(vec![0x51]))),
+            None
+        );
+        let custom = WeightedUtxo::new(coin(1_000, Script::new(vec![0x51])), Weight::from_wu(4));
+        assert_eq!(custom.input_weight(), Weight::from_wu(164));
+    }
+
+    #[test]
+    fn test_gcs_filter() {
+        // BIP158 basic filter for the testnet genesis block: its only item is
+        // the coinbase output script, keyed by the block hash
+        let genesis_script = hex::decode(
+            "4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb6\
+             49f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac",
+        )
+        .unwrap();
+        let params = GcsParams::bip158(0x719526f8d77f4943, 0xaec3ced90fa3f408);
+        let filter = GcsFilter::build(params, [&genesis_script]).unwrap();
+        assert_eq!(hex::encode(filter.to_bytes()), "019dfca8");
+        assert!(filter.contains(&genesis_script));
+
+        // A wallet filter over a few hundred outpoints
+        let params = GcsParams::bip158(1, 2);
+        let outpoints: Vec<OutPoint> = (0..200u32)
+            .map(|i| OutPoint::new(dummy_txid(i as u8), i))
+            .collect();
+        let scripts = vec![Script::new(vec![0x00, 0x14, 0xAA])];
+        let filter = GcsFilter::for_wallet(params, &outpoints, &scripts).unwrap();
+        assert_eq!(filter.len(), 201);
+        assert!(outpoints.iter().all(|op| filter.contains(&op.to_bytes())));
+        let false_positives = (1000..6000u32)
+            .filter(|&i| filter.contains(&OutPoint::new(dummy_txid(7), i).to_bytes()))
+            .count();
+        assert!(false_positives < 5);
+
+        let decoded = GcsFilter::from_bytes(&filter.to_bytes(), params).unwrap();
+        assert_eq!(decoded, filter);
+
+        let mut paying_us = BitcoinTransaction::new(2, vec![], 0);
+        paying_us.add_output(TxOut::new(1_000, scripts[0].clone()));
+        assert!(decoded.matches_transaction(&paying_us));
+        let block_170 = BitcoinTransaction::decode(&hex::decode(BLOCK_170_TX).unwrap()).unwrap();
+        assert!(!decoded.matches_transaction(&block_170));
+
+        // Parameters are public, so they're checked before anything shifts
+        // or multiplies with them
+        for p in [0, 33, 64] {
+            let bad = GcsParams { p, ..params };
+            assert!(matches!(
+                GcsFilter::build(bad, [b"x"]),
+                Err(BitcoinError::InvalidGcsFilter(_))
+            ));
+            assert!(matches!(
+                GcsFilter::from_bytes(&filter.to_bytes(), bad),
+                Err(BitcoinError::InvalidGcsFilter(_))
+            ));
+        }
+        // n * m overflowing, and a count the data is too short for
+        let huge = GcsParams {
+            m: u64::MAX,
+            ..params
+        };
+        assert!(matches!(
+            GcsFilter::build(huge, [b"x", b"y"]),
+            Err(BitcoinError::InvalidGcsFilter(_))
+        ));
+        let mut lying = CompactSize::new(1_000_000).to_bytes();
+        lying.extend_from_slice(&filter.to_bytes()[1..]);
+        assert!(matches!(
+            GcsFilter::from_bytes(&lying, params),
+            Err(BitcoinError::InvalidGcsFilter(_))
+        ));
+        // Corrupt data (a unary run that never ends) just doesn't match
+        let mut ones = CompactSize::new(1).to_bytes();
+        ones.extend(vec![0xFF; 1 << 14]);
+        let weird = GcsFilter::from_bytes(&ones, GcsParams { p: 32, ..params }).unwrap();
+        assert!(!weird.contains(b"x"));
+    }
+
+    #[test]
+    fn test_bitcoin_error_display_and_source() {
+        use std::error::Error;
+
+        let err = CompactSize::decode(&[0xFD, 0x01]).unwrap_err();
+        assert_eq!(
+            err,
+            BitcoinError::UnexpectedEof {
+                needed: 3,
+                available: 2
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "unexpected end of input: needed 3 bytes, 2 available"
+        );
+
+        // Hex errors convert with `?` and keep the original as the source
+        let err = "zz".parse::<Txid>().unwrap_err();
+        assert!(matches!(err, BitcoinError::InvalidHex(_)));
+        assert!(err.source().is_some());
+
+        // Boxable, so it works with `?` into Box<dyn Error>
+        let bytes = hex::decode(BLOCK_170_TX).unwrap();
+        let boxed: Box<dyn Error> = BitcoinTransaction::decode(&bytes[..60]).unwrap_err().into();
+        assert_eq!(
+            boxed.to_string(),
+            "failed to decode inputs[0].script_sig at byte 41"
+        );
+        assert_eq!(
+            boxed.source().unwrap().to_string(),
+            "unexpected end of input: needed 72 bytes, 18 available"
+        );
+    }
+
+    #[test]
+    fn test_consensus_traits_stream_transactions() {
+        // Two transactions back to back in one stream, as they'd appear in a
+        // block or a file
+        let mut stream = hex::decode(BIP143_P2WPKH_TX).unwrap();
+        stream.extend(hex::decode(BLOCK_170_TX).unwrap());
+
+        let mut reader = std::io::Cursor::new(&stream);
+        let first = BitcoinTransaction::consensus_decode(&mut reader).unwrap();
+        let second = BitcoinTransaction::consensus_decode(&mut reader).unwrap();
+        assert!(first.has_witness());
+        assert_eq!(second.outputs.len(), 2);
+        assert_eq!(reader.position() as usize, stream.len());
+        let err = BitcoinTransaction::consensus_decode(&mut reader).unwrap_err();
+        assert_eq!(
+            err.root_cause(),
+            &BitcoinError::UnexpectedEof {
+                needed: 4,
+                available: 0
+            }
+        );
+
+        let mut out = Vec::new();
+        let written =
+            first.consensus_encode(&mut out).unwrap() + second.consensus_encode(&mut out).unwrap();
+        assert_eq!(written, stream.len());
+        assert_eq!(out, stream);
+
+        // Legacy tx with zero inputs and zero outputs: the 0x00 after the
+        // version is an input count, not a segwit marker
+        let empty = BitcoinTransaction::default();
+        let bytes = empty.to_bytes();
+        assert_eq!(&bytes[4..6], &[0x00, 0x00]);
+        assert_eq!(
+            BitcoinTransaction::decode(&bytes).unwrap().into_inner(),
+            empty
+        );
+        // A marker with a flag other than 0x00/0x01 isn't silently read as
+        // a legacy tx with no inputs
+        let mut unknown_flag = bytes.clone();
+        unknown_flag[5] = 0x02;
+        assert_eq!(
+            BitcoinTransaction::decode(&unknown_flag).unwrap_err(),
+            BitcoinError::AtField {
+                field: "flag".to_string(),
+                offset: 5,
+                source: Box::new(BitcoinError::UnknownSegwitFlag(0x02)),
+            }
+        );
+        assert_eq!(
+            BitcoinError::UnknownSegwitFlag(0x02).to_string(),
+            "unknown segwit flag 0x02"
+        );
+
+        // Every field type works on its own too
+        let mut bytes = Vec::new();
+        CompactSize::new(0x1234)
+            .consensus_encode(&mut bytes)
+            .unwrap();
+        Script::new(vec![0xAC])
+            .consensus_encode(&mut bytes)
+            .unwrap();
+        let mut reader = bytes.as_slice();
+        assert_eq!(
+            CompactSize::consensus_decode(&mut reader).unwrap().value,
+            0x1234
+        );
+        assert_eq!(
+            Script::consensus_decode(&mut reader).unwrap().bytes,
+            vec![0xAC]
+        );
+    }
 }